@@ -0,0 +1,21 @@
+/*
+Solves (a or b) and (c and (not b))
+
+solution a = true, b = false, c = true
+*/
+use solver::{sat, SolveResult};
+
+fn main() {
+    let instance = sat!((a | b) & (c & !b));
+
+    match instance.solve() {
+        SolveResult::Satisfiable(state) => {
+            for var in instance.vars.vars() {
+                println!("{} = {:?}", instance.vars.name(var), state.value(var));
+            }
+            println!("{:#?}", instance.satisfied_by(&state));
+        },
+        SolveResult::Unsatisfiable => println!("UNSAT"),
+        SolveResult::Unknown => println!("UNKNOWN")
+    }
+}
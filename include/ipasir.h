@@ -0,0 +1,107 @@
+/*
+ * IPASIR interface of the solver library, declaring the functions that
+ * src/ipasir.rs exports. Link with libsolver.a (and -lpthread -ldl -lm)
+ * or libsolver.so from target/<profile>.
+ *
+ * Literals are nonzero DIMACS numbers, -n being the negation of n.
+ */
+
+#ifndef IPASIR_H
+#define IPASIR_H
+
+/* Generated from src/ipasir.rs with cbindgen, do not edit. */
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ * Name and version of the solver.
+ */
+const char *ipasir_signature(void);
+
+/*
+ * New solver without clauses, to be freed with `ipasir_release`.
+ */
+void *ipasir_init(void);
+
+/*
+ * Frees the solver.
+ *
+ * # Safety
+ * `solver` comes from `ipasir_init` and is not used afterwards.
+ */
+void ipasir_release(void *solver);
+
+/*
+ * Adds a literal to the clause being built, 0 adds the clause.
+ *
+ * # Safety
+ * `solver` comes from `ipasir_init`.
+ */
+void ipasir_add(void *solver, int32_t lit_or_zero);
+
+/*
+ * Assumes `lit` for the next solve only, 0 is ignored.
+ *
+ * # Safety
+ * `solver` comes from `ipasir_init`.
+ */
+void ipasir_assume(void *solver, int32_t lit);
+
+/*
+ * Solves under the assumptions, returns 10 when satisfiable, 20 when
+ * unsatisfiable and 0 when the terminate callback stopped the search.
+ *
+ * # Safety
+ * `solver` comes from `ipasir_init`.
+ */
+int ipasir_solve(void *solver);
+
+/*
+ * Value of `lit` in the model of the last solve, `lit` when true,
+ * `-lit` when false and 0 when `lit` is 0 or the solver never saw its
+ * variable.
+ *
+ * # Safety
+ * `solver` comes from `ipasir_init` and its last solve returned 10.
+ */
+int32_t ipasir_val(void *solver, int32_t lit);
+
+/*
+ * 1 when the assumption `lit` took part in making the last solve
+ * unsatisfiable, 0 otherwise, including when `lit` is 0.
+ *
+ * # Safety
+ * `solver` comes from `ipasir_init` and its last solve returned 20.
+ */
+int ipasir_failed(void *solver, int32_t lit);
+
+/*
+ * Has the solver call `terminate(data)` during search and give up
+ * when it returns nonzero. A null `terminate` removes the callback.
+ *
+ * # Safety
+ * `solver` comes from `ipasir_init` and `terminate` can be called with
+ * `data` as long as it is set.
+ */
+void ipasir_set_terminate(void *solver, void *data, int (*terminate)(void*));
+
+/*
+ * Passes every learned clause of at most `max_length` literals to
+ * `learn(data, clause)` as 0 terminated literals, valid during the
+ * call. A null `learn` removes the callback.
+ *
+ * # Safety
+ * `solver` comes from `ipasir_init` and `learn` can be called with
+ * `data` as long as it is set.
+ */
+void ipasir_set_learn(void *solver, void *data, int max_length, void (*learn)(void*, int32_t*));
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* IPASIR_H */
@@ -0,0 +1,13 @@
+"""SAT solver for instances of OR, AND, XOR, cardinality and other clauses.
+
+    >>> from solver import Clause, SatInstance
+    >>> instance = SatInstance()
+    >>> instance.add(Clause("or", ["a", "b"]))
+    >>> instance.add(Clause("and", ["c", "!b"]))
+    >>> result = instance.solve()
+    >>> result.model
+    {'a': True, 'b': False, 'c': True}
+"""
+from ._solver import Clause, Literal, SatInstance, SolveResult
+
+__all__ = ["Clause", "Literal", "SatInstance", "SolveResult"]
@@ -0,0 +1,62 @@
+from typing import Dict, List, Optional, Sequence, Union
+
+class Literal:
+    def __init__(self, name: str, negated: bool = False) -> None: ...
+    @property
+    def name(self) -> str: ...
+    @property
+    def negated(self) -> bool: ...
+    def __invert__(self) -> "Literal": ...
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+
+LiteralLike = Union[Literal, str]
+
+class Clause:
+    def __init__(self, operator: str, literals: Sequence[LiteralLike], label: Optional[str] = None) -> None: ...
+    @staticmethod
+    def at_most(k: int, literals: Sequence[LiteralLike], label: Optional[str] = None) -> "Clause": ...
+    @staticmethod
+    def at_least(k: int, literals: Sequence[LiteralLike], label: Optional[str] = None) -> "Clause": ...
+    @staticmethod
+    def exactly(k: int, literals: Sequence[LiteralLike], label: Optional[str] = None) -> "Clause": ...
+    @property
+    def operator(self) -> str: ...
+    @property
+    def bound(self) -> Optional[int]: ...
+    @property
+    def literals(self) -> List[Literal]: ...
+    @property
+    def label(self) -> Optional[str]: ...
+    def __len__(self) -> int: ...
+
+class SolveResult:
+    @property
+    def status(self) -> str: ...
+    @property
+    def satisfiable(self) -> bool: ...
+    @property
+    def model(self) -> Optional[Dict[str, bool]]: ...
+    @property
+    def core(self) -> Optional[List[int]]: ...
+    def __bool__(self) -> bool: ...
+    def __getitem__(self, name: str) -> bool: ...
+
+class SatInstance:
+    def __init__(self) -> None: ...
+    @staticmethod
+    def from_dimacs(text: str) -> "SatInstance": ...
+    @staticmethod
+    def from_expression(text: str) -> "SatInstance": ...
+    def to_dimacs(self) -> str: ...
+    def var(self, name: str) -> Literal: ...
+    def add(self, clause: Clause) -> None: ...
+    def extend(self, clauses: Sequence[Clause]) -> None: ...
+    @property
+    def variables(self) -> List[str]: ...
+    @property
+    def clauses(self) -> List[Clause]: ...
+    def __len__(self) -> int: ...
+    def solve(self, time_limit: Optional[float] = None, seed: int = 0, core: bool = False) -> SolveResult: ...
+    def unsat_core(self) -> Optional[List[int]]: ...
+    def satisfied_by(self, model: Dict[str, bool]) -> bool: ...
@@ -0,0 +1,430 @@
+/*
+Python bindings, installed as the solver package by maturin
+
+Literals and clauses name their variables instead of holding indices,
+so that they can be built before the instance they go into, which
+interns the names as clauses are added. Results carry the model as a
+dict from name to value in variable order, internal variables left out.
+
+The same tests run over the Rust API in tests/bindings.rs of the main
+crate, python/tests/test_solver.py runs them through these bindings.
+*/
+use std::collections::HashMap;
+use std::time::Duration;
+
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use solver::io::{dimacs, expr};
+use solver::{InstanceState, Operator, SolveOptions};
+
+/// Variable name, negated or not.
+#[pyclass(module = "solver", frozen, eq, hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Literal {
+    name: String,
+    negated: bool
+}
+
+/*
+Literal given to a clause, either as a Literal or as a name with an
+optional leading ! or - like in `SatInstanceBuilder`
+*/
+#[derive(FromPyObject)]
+enum LiteralArg {
+    Literal(Literal),
+    Name(String)
+}
+
+impl LiteralArg {
+    fn literal(self) -> Literal {
+        match self {
+            LiteralArg::Literal(literal) => literal,
+            LiteralArg::Name(name) => match name.strip_prefix('!').or_else(|| name.strip_prefix('-')) {
+                Some(name) => Literal { name: name.to_string(), negated: true },
+                None => Literal { name, negated: false }
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl Literal {
+    #[new]
+    #[pyo3(signature = (name, negated = false))]
+    fn new(name: String, negated: bool) -> Literal {
+        Literal { name, negated }
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[getter]
+    fn negated(&self) -> bool {
+        self.negated
+    }
+
+    fn __invert__(&self) -> Literal {
+        Literal { name: self.name.clone(), negated: !self.negated }
+    }
+
+    fn __str__(&self) -> String {
+        if self.negated { format!("!{}", self.name) } else { self.name.clone() }
+    }
+
+    fn __repr__(&self) -> String {
+        if self.negated {
+            format!("Literal('{}', negated=True)", self.name)
+        } else {
+            format!("Literal('{}')", self.name)
+        }
+    }
+}
+
+
+/// Literals joined by one operator, optionally labelled.
+#[pyclass(module = "solver", frozen)]
+#[derive(Debug, Clone)]
+struct Clause {
+    operator: Operator,
+    literals: Vec<Literal>,
+    label: Option<String>
+}
+
+/*
+Python name of an operator and its bound for cardinality operators
+*/
+fn operator_name(operator: &Operator) -> (&'static str, Option<usize>) {
+    match *operator {
+        Operator::OR => ("or", None),
+        Operator::AND => ("and", None),
+        Operator::NAND => ("nand", None),
+        Operator::NOR => ("nor", None),
+        Operator::XOR => ("xor", None),
+        Operator::XNOR => ("xnor", None),
+        Operator::EQUIV => ("equiv", None),
+        Operator::IMPLIES => ("implies", None),
+        Operator::AtMost(k) => ("at_most", Some(k)),
+        Operator::AtLeast(k) => ("at_least", Some(k)),
+        Operator::Exactly(k) => ("exactly", Some(k))
+    }
+}
+
+fn operator(name: &str) -> PyResult<Operator> {
+    let operator = match name.to_ascii_lowercase().as_str() {
+        "or" => Operator::OR,
+        "and" => Operator::AND,
+        "nand" => Operator::NAND,
+        "nor" => Operator::NOR,
+        "xor" => Operator::XOR,
+        "xnor" => Operator::XNOR,
+        "equiv" => Operator::EQUIV,
+        "implies" => Operator::IMPLIES,
+        "at_most" | "at_least" | "exactly" => {
+            let message = format!("'{}' takes a bound, use Clause.{}(k, literals)", name, name);
+            return Err(PyValueError::new_err(message))
+        },
+        _ => return Err(PyValueError::new_err(format!("unknown operator '{}'", name)))
+    };
+    Ok(operator)
+}
+
+impl Clause {
+    fn with(operator: Operator, literals: Vec<LiteralArg>, label: Option<String>) -> Clause {
+        let literals = literals.into_iter().map(LiteralArg::literal).collect();
+        Clause { operator, literals, label }
+    }
+}
+
+#[pymethods]
+impl Clause {
+    /// Clause of one of the operators or, and, nand, nor, xor, xnor,
+    /// equiv and implies over Literals or names such as "!a".
+    #[new]
+    #[pyo3(signature = (operator, literals, label = None))]
+    fn new(operator: &str, literals: Vec<LiteralArg>, label: Option<String>) -> PyResult<Clause> {
+        Ok(Clause::with(self::operator(operator)?, literals, label))
+    }
+
+    /// At most `k` of the literals are true.
+    #[staticmethod]
+    #[pyo3(signature = (k, literals, label = None))]
+    fn at_most(k: usize, literals: Vec<LiteralArg>, label: Option<String>) -> Clause {
+        Clause::with(Operator::AtMost(k), literals, label)
+    }
+
+    /// At least `k` of the literals are true.
+    #[staticmethod]
+    #[pyo3(signature = (k, literals, label = None))]
+    fn at_least(k: usize, literals: Vec<LiteralArg>, label: Option<String>) -> Clause {
+        Clause::with(Operator::AtLeast(k), literals, label)
+    }
+
+    /// Exactly `k` of the literals are true.
+    #[staticmethod]
+    #[pyo3(signature = (k, literals, label = None))]
+    fn exactly(k: usize, literals: Vec<LiteralArg>, label: Option<String>) -> Clause {
+        Clause::with(Operator::Exactly(k), literals, label)
+    }
+
+    #[getter]
+    fn operator(&self) -> &'static str {
+        operator_name(&self.operator).0
+    }
+
+    /// The k of cardinality clauses, None for the others.
+    #[getter]
+    fn bound(&self) -> Option<usize> {
+        operator_name(&self.operator).1
+    }
+
+    #[getter]
+    fn literals(&self) -> Vec<Literal> {
+        self.literals.clone()
+    }
+
+    #[getter]
+    fn label(&self) -> Option<String> {
+        self.label.clone()
+    }
+
+    fn __len__(&self) -> usize {
+        self.literals.len()
+    }
+
+    fn __repr__(&self) -> String {
+        let literals: Vec<String> = self.literals.iter().map(|l| format!("'{}'", l.__str__())).collect();
+        let literals = literals.join(", ");
+        let label = self.label.as_ref().map_or(String::new(), |label| format!(", label='{}'", label));
+        match operator_name(&self.operator) {
+            (name, Some(k)) => format!("Clause.{}({}, [{}]{})", name, k, literals, label),
+            (name, None) => format!("Clause('{}', [{}]{})", name, literals, label)
+        }
+    }
+}
+
+
+/// Conjunction of clauses over named variables.
+#[pyclass(module = "solver", name = "SatInstance")]
+#[derive(Debug, Clone, Default)]
+struct Instance {
+    instance: solver::SatInstance
+}
+
+#[pymethods]
+impl Instance {
+    #[new]
+    fn new() -> Instance {
+        Instance::default()
+    }
+
+    /// Instance read from DIMACS CNF, raises ValueError when malformed.
+    #[staticmethod]
+    fn from_dimacs(text: &str) -> PyResult<Instance> {
+        let instance = dimacs::parse(text).map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Ok(Instance { instance })
+    }
+
+    /// Instance of an infix expression such as "(a | b) & !c",
+    /// raises ValueError when malformed.
+    #[staticmethod]
+    fn from_expression(text: &str) -> PyResult<Instance> {
+        let instance = expr::parse_instance(text).map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Ok(Instance { instance })
+    }
+
+    /// The instance in DIMACS CNF.
+    fn to_dimacs(&self) -> PyResult<String> {
+        let mut out = Vec::new();
+        dimacs::write(&self.instance, &mut out)?;
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+
+    /// Positive literal of the variable `name`, added on first use.
+    fn var(&mut self, name: &str) -> Literal {
+        self.instance.var(name);
+        Literal { name: name.to_string(), negated: false }
+    }
+
+    fn add(&mut self, clause: Clause) {
+        let vars = &mut self.instance.vars;
+        let literals = clause
+            .literals
+            .iter()
+            .map(|literal| solver::Literal::new(vars.intern(&literal.name), literal.negated))
+            .collect();
+        self.instance.add_clause(solver::Clause { operator: clause.operator, literals, label: clause.label });
+    }
+
+    fn extend(&mut self, clauses: Vec<Clause>) {
+        for clause in clauses {
+            self.add(clause);
+        }
+    }
+
+    /// Names of the variables in order, without internal ones.
+    #[getter]
+    fn variables(&self) -> Vec<String> {
+        let vars = &self.instance.vars;
+        vars.vars().filter(|&var| !vars.is_internal(var)).map(|var| vars.name(var).to_string()).collect()
+    }
+
+    #[getter]
+    fn clauses(&self) -> Vec<Clause> {
+        let vars = &self.instance.vars;
+        let literal = |literal: &solver::Literal| Literal {
+            name: vars.name(literal.var()).to_string(),
+            negated: literal.is_negated()
+        };
+        self.instance
+            .clauses
+            .iter()
+            .map(|clause| Clause {
+                operator: clause.operator.clone(),
+                literals: clause.literals.iter().map(literal).collect(),
+                label: clause.label.clone()
+            })
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.instance.clauses.len()
+    }
+
+    /// Solves the instance, giving up after `time_limit` seconds.
+    /// With `core`, unsatisfiable results list the indices of clauses
+    /// that are unsatisfiable together.
+    #[pyo3(signature = (time_limit = None, seed = 0, core = false))]
+    fn solve(&self, py: Python<'_>, time_limit: Option<f64>, seed: u64, core: bool) -> PyResult<SolveResult> {
+        let time_limit = match time_limit {
+            Some(seconds) if seconds < 0.0 || !seconds.is_finite() =>
+                return Err(PyValueError::new_err("time_limit must be a nonnegative number of seconds")),
+            limit => limit.map(Duration::from_secs_f64)
+        };
+        let options = SolveOptions { time_limit, seed };
+        let instance = &self.instance;
+        let (result, core) = py.allow_threads(|| {
+            if core {
+                let (result, core, _) = instance.solve_with_core(&options);
+                (result, core)
+            } else {
+                (instance.solve_with(&options).0, None)
+            }
+        });
+        Ok(SolveResult::new(result, core, &instance.vars))
+    }
+
+    /// Indices of clauses that are unsatisfiable together,
+    /// None when the instance is satisfiable.
+    fn unsat_core(&self, py: Python<'_>) -> Option<Vec<usize>> {
+        let instance = &self.instance;
+        py.allow_threads(|| instance.unsat_core())
+    }
+
+    /// True when `model`, a dict from name to bool, satisfies every
+    /// clause. Variables missing from it are unassigned.
+    fn satisfied_by(&self, model: HashMap<String, bool>) -> PyResult<bool> {
+        let vars = &self.instance.vars;
+        let mut state = InstanceState::new(vars.len());
+        for (name, value) in model {
+            let var = vars.lookup(&name).ok_or_else(|| PyKeyError::new_err(name))?;
+            state.assign(solver::Literal::new(var, !value));
+        }
+        Ok(self.instance.satisfied_by(&state))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<SatInstance with {} variables and {} clauses>", self.variables().len(), self.instance.clauses.len())
+    }
+}
+
+
+/// Outcome of `SatInstance.solve`, true when satisfiable.
+#[pyclass(module = "solver", frozen)]
+#[derive(Debug, Clone)]
+struct SolveResult {
+    status: &'static str,
+    model: Option<Vec<(String, bool)>>,
+    core: Option<Vec<usize>>
+}
+
+impl SolveResult {
+    fn new(result: solver::SolveResult, core: Option<Vec<usize>>, vars: &solver::VarTable) -> SolveResult {
+        match result {
+            solver::SolveResult::Satisfiable(state) => {
+                let model = vars
+                    .vars()
+                    .filter_map(|var| state.value(var).map(|value| (vars.name(var).to_string(), value)))
+                    .collect();
+                SolveResult { status: "sat", model: Some(model), core: None }
+            },
+            solver::SolveResult::Unsatisfiable => SolveResult { status: "unsat", model: None, core },
+            solver::SolveResult::Unknown => SolveResult { status: "unknown", model: None, core: None }
+        }
+    }
+}
+
+#[pymethods]
+impl SolveResult {
+    /// One of "sat", "unsat" and "unknown" when the time limit ran out.
+    #[getter]
+    fn status(&self) -> &'static str {
+        self.status
+    }
+
+    #[getter]
+    fn satisfiable(&self) -> bool {
+        self.model.is_some()
+    }
+
+    /// Dict from variable name to value, None unless satisfiable.
+    #[getter]
+    fn model<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let model = match &self.model {
+            Some(model) => model,
+            None => return Ok(None)
+        };
+        let dict = PyDict::new_bound(py);
+        for (name, value) in model {
+            dict.set_item(name, value)?;
+        }
+        Ok(Some(dict))
+    }
+
+    /// Clause indices of the unsatisfiable core when solved with `core`.
+    #[getter]
+    fn core(&self) -> Option<Vec<usize>> {
+        self.core.clone()
+    }
+
+    fn __bool__(&self) -> bool {
+        self.satisfiable()
+    }
+
+    /// Value of the variable `name` in the model.
+    fn __getitem__(&self, name: &str) -> PyResult<bool> {
+        self.model
+            .iter()
+            .flatten()
+            .find(|(var, _)| var == name)
+            .map(|&(_, value)| value)
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<SolveResult {}>", self.status)
+    }
+}
+
+
+#[pymodule]
+fn _solver(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Literal>()?;
+    module.add_class::<Clause>()?;
+    module.add_class::<Instance>()?;
+    module.add_class::<SolveResult>()?;
+    Ok(())
+}
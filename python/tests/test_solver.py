@@ -0,0 +1,98 @@
+"""Tests of the Python bindings, mirrored over the Rust API in
+tests/bindings.rs of the main crate. Run with pytest after
+`maturin develop` in python/."""
+import pytest
+
+from solver import Clause, Literal, SatInstance
+
+
+def test_model_by_name():
+    instance = SatInstance()
+    instance.add(Clause("or", ["a", "b"]))
+    instance.add(Clause("and", ["c", "!b"]))
+    result = instance.solve()
+    assert result
+    assert result.status == "sat"
+    assert result.model == {"a": True, "b": False, "c": True}
+    assert list(result.model) == ["a", "b", "c"]
+    assert result["c"] is True
+    with pytest.raises(KeyError):
+        result["d"]
+    assert instance.satisfied_by(result.model)
+
+
+def test_unsat_core():
+    instance = SatInstance()
+    instance.extend([
+        Clause("or", ["a", "b"], label="a or b"),
+        Clause("and", ["!a"], label="not a"),
+        Clause("or", ["c"], label="c"),
+        Clause("and", ["!b"], label="not b"),
+    ])
+    result = instance.solve(core=True)
+    assert not result
+    assert result.status == "unsat"
+    assert result.model is None
+    assert result.core == [0, 1, 3]
+    assert [instance.clauses[k].label for k in result.core] == ["a or b", "not a", "not b"]
+    assert instance.unsat_core() == [0, 1, 3]
+
+
+def test_cardinality():
+    instance = SatInstance()
+    a, b, c = instance.var("a"), instance.var("b"), instance.var("c")
+    instance.add(Clause.exactly(2, [a, b, c]))
+    instance.add(Clause("and", [~a]))
+    assert instance.solve().model == {"a": False, "b": True, "c": True}
+    instance.add(Clause.at_most(1, [b, c]))
+    assert instance.solve().status == "unsat"
+
+
+def test_xor_parity():
+    instance = SatInstance()
+    instance.add(Clause("xor", ["a", "b", "c"]))
+    instance.add(Clause("and", ["a", "b"]))
+    assert instance.solve()["c"] is True
+
+
+def test_literals():
+    a = Literal("a")
+    assert ~a == Literal("a", negated=True)
+    assert ~~a == a
+    assert len({a, Literal("a"), ~a}) == 2
+    assert str(~a) == "!a"
+    clause = Clause("or", [~a, "-b", "!c", "d"])
+    assert [str(literal) for literal in clause.literals] == ["!a", "!b", "!c", "d"]
+    assert clause.operator == "or" and clause.bound is None
+    assert Clause.at_least(2, ["a", "b"]).bound == 2
+    with pytest.raises(ValueError):
+        Clause("at_most", ["a"])
+    with pytest.raises(ValueError):
+        Clause("maybe", ["a"])
+
+
+def test_dimacs():
+    instance = SatInstance.from_dimacs("p cnf 2 2\n1 2 0\n-1 0\n")
+    assert instance.variables == ["1", "2"]
+    assert instance.solve().model == {"1": False, "2": True}
+    assert len(SatInstance.from_dimacs(instance.to_dimacs())) == 2
+    with pytest.raises(ValueError):
+        SatInstance.from_dimacs("p cnf 1 1\n1 x 0\n")
+
+
+def test_expression_hides_internal_variables():
+    instance = SatInstance.from_expression("(a | b) & (c & !b)")
+    assert instance.variables == ["a", "b", "c"]
+    assert instance.solve().model == {"a": True, "b": False, "c": True}
+    with pytest.raises(ValueError):
+        SatInstance.from_expression("a & | b")
+
+
+def test_satisfied_by():
+    instance = SatInstance()
+    instance.add(Clause("implies", ["a", "b"]))
+    assert instance.satisfied_by({"a": True, "b": True})
+    assert not instance.satisfied_by({"a": True, "b": False})
+    assert not instance.satisfied_by({"a": True})
+    with pytest.raises(KeyError):
+        instance.satisfied_by({"z": True})
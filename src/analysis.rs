@@ -0,0 +1,116 @@
+/*
+Variable and occurrence analysis of an instance
+
+Counts how often each variable appears positively and negatively
+and how long the clauses are. Polarity is the one a literal is used
+with: literals of NAND and NOR clauses and the premises of IMPLIES are
+used negated, literals of parity and equivalence clauses both ways.
+Cardinality clauses use their literals positively for AtLeast,
+negated for AtMost and both ways for Exactly, pseudo-Boolean
+constraints as their normal form does. Used for reporting and as
+input for heuristics such as pure literal elimination.
+*/
+use std::collections::BTreeMap;
+
+use crate::formula::{Clause, Literal, Operator, SatInstance, Var};
+
+/// Positive and negative occurrences of one variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarOccurrences {
+    pub var: Var,
+    pub positive: usize,
+    pub negative: usize
+}
+
+impl VarOccurrences {
+    pub fn total(&self) -> usize {
+        self.positive + self.negative
+    }
+
+    /// The only polarity the variable appears with, if it is pure.
+    pub fn pure_literal(&self) -> Option<Literal> {
+        match (self.positive, self.negative) {
+            (0, 0) => None,
+            (_, 0) => Some(self.var.pos()),
+            (0, _) => Some(self.var.neg()),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Analysis {
+    /// Variables that appear in some clause, in index order.
+    pub variables: Vec<VarOccurrences>,
+    /// Number of clauses of each length.
+    pub clause_lengths: BTreeMap<usize, usize>,
+    /// Literals whose variable never appears with the opposite sign.
+    pub pure_literals: Vec<Literal>,
+    /// Variables of the VarTable that no clause mentions.
+    pub unused: Vec<Var>
+}
+
+impl SatInstance {
+    pub fn analyze(&self) -> Analysis {
+        let mut clause_lengths = BTreeMap::new();
+        let mut used = Vec::new();
+        for clause in &self.clauses {
+            *clause_lengths.entry(clause.literals.len()).or_insert(0) += 1;
+            used.extend(polarities(clause));
+        }
+        for normalized in self.pb_constraints.iter().flat_map(|c| c.normalized()) {
+            used.extend(normalized.terms.iter().map(|&(_, literal)| literal));
+        }
+
+        let mut occurrences: Vec<(usize, usize)> = vec![(0, 0); self.vars.len()];
+        for literal in used {
+            let index = literal.var().index();
+            if index >= occurrences.len() {
+                occurrences.resize(index + 1, (0, 0));
+            }
+            if literal.is_negated() {
+                occurrences[index].1 += 1;
+            } else {
+                occurrences[index].0 += 1;
+            }
+        }
+
+        let mut analysis = Analysis { clause_lengths, ..Default::default() };
+        for (index, (positive, negative)) in occurrences.into_iter().enumerate() {
+            let var = Var::new(index);
+            if positive + negative == 0 {
+                analysis.unused.push(var);
+                continue;
+            }
+            let variable = VarOccurrences { var, positive, negative };
+            if let Some(literal) = variable.pure_literal() {
+                analysis.pure_literals.push(literal);
+            }
+            analysis.variables.push(variable);
+        }
+        analysis
+    }
+}
+
+
+/*
+Literals of `clause` with the polarity they are used with,
+twice with both signs when it is used both ways
+*/
+fn polarities(clause: &Clause) -> Vec<Literal> {
+    let last = clause.literals.len().saturating_sub(1);
+    let mut literals = Vec::new();
+    for (index, &literal) in clause.literals.iter().enumerate() {
+        match clause.operator {
+            Operator::OR | Operator::AND | Operator::AtLeast(_) => literals.push(literal),
+            Operator::NAND | Operator::NOR | Operator::AtMost(_) => literals.push(!literal),
+            Operator::IMPLIES if index < last => literals.push(!literal),
+            Operator::IMPLIES => literals.push(literal),
+            Operator::XOR | Operator::XNOR | Operator::EQUIV | Operator::Exactly(_) => {
+                literals.push(literal);
+                literals.push(!literal);
+            }
+        }
+    }
+    literals
+}
@@ -0,0 +1,163 @@
+/*
+Assignment of values to the variables of an instance
+
+Values are kept in a dense array indexed by variable, so looking
+one up is O(1). The trail records literals in the order they were
+made true, popping it undoes assignments for backtracking.
+*/
+use std::ops::Not;
+
+use crate::formula::{Literal, Var, VarTable};
+
+/// Three-valued truth of a literal, clause or instance
+/// under a possibly partial assignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Truth {
+    True,
+    False,
+    Unknown
+}
+
+impl Truth {
+    /// Kleene conjunction, False wins over Unknown.
+    pub fn and(self, other: Truth) -> Truth {
+        match (self, other) {
+            (Truth::False, _) | (_, Truth::False) => Truth::False,
+            (Truth::True, Truth::True) => Truth::True,
+            _ => Truth::Unknown
+        }
+    }
+
+    /// Kleene disjunction, True wins over Unknown.
+    pub fn or(self, other: Truth) -> Truth {
+        match (self, other) {
+            (Truth::True, _) | (_, Truth::True) => Truth::True,
+            (Truth::False, Truth::False) => Truth::False,
+            _ => Truth::Unknown
+        }
+    }
+}
+
+impl Not for Truth {
+    type Output = Truth;
+
+    fn not(self) -> Truth {
+        match self {
+            Truth::True => Truth::False,
+            Truth::False => Truth::True,
+            Truth::Unknown => Truth::Unknown
+        }
+    }
+}
+
+impl From<Option<bool>> for Truth {
+    fn from(value: Option<bool>) -> Truth {
+        match value {
+            Some(true) => Truth::True,
+            Some(false) => Truth::False,
+            None => Truth::Unknown
+        }
+    }
+}
+
+/// Partial or complete assignment of the variables of an instance,
+/// such as a model found by the solver.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InstanceState {
+    values: Vec<Option<bool>>,
+    trail: Vec<Literal>
+}
+
+impl InstanceState {
+    /// State with `vars` unassigned variables.
+    pub fn new(vars: usize) -> InstanceState {
+        InstanceState {
+            values: vec![None; vars],
+            trail: Vec::new()
+        }
+    }
+
+    /// Complete state with variable i assigned `values[i]`.
+    pub fn from_values(values: &[bool]) -> InstanceState {
+        let mut state = InstanceState::new(values.len());
+        for (index, &value) in values.iter().enumerate() {
+            state.assign(Literal::new(Var::new(index), !value));
+        }
+        state
+    }
+
+    /// Adds unassigned variables up to `vars` in total.
+    pub fn grow(&mut self, vars: usize) {
+        if self.values.len() < vars {
+            self.values.resize(vars, None);
+        }
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Value of `var`, `None` when it is unassigned or out of range.
+    pub fn value(&self, var: Var) -> Option<bool> {
+        self.values.get(var.index()).copied().flatten()
+    }
+
+    /// Value of `literal`, `None` when its variable is unassigned.
+    pub fn literal_value(&self, literal: Literal) -> Option<bool> {
+        self.value(literal.var()).map(|value| value != literal.is_negated())
+    }
+
+    pub fn literal_truth(&self, literal: Literal) -> Truth {
+        Truth::from(self.literal_value(literal))
+    }
+
+    pub fn is_assigned(&self, var: Var) -> bool {
+        self.value(var).is_some()
+    }
+
+    /// True when every variable has a value.
+    pub fn is_complete(&self) -> bool {
+        self.trail.len() == self.values.len()
+    }
+
+    /// Makes `literal` true and records it on the trail,
+    /// its variable must be unassigned.
+    pub fn assign(&mut self, literal: Literal) {
+        let var = literal.var().index();
+        self.grow(var + 1);
+        debug_assert!(self.values[var].is_none(), "variable {} assigned twice", var);
+        self.values[var] = Some(!literal.is_negated());
+        self.trail.push(literal);
+    }
+
+    /// Undoes the latest assignment.
+    pub fn pop(&mut self) -> Option<Literal> {
+        let literal = self.trail.pop()?;
+        self.values[literal.var().index()] = None;
+        Some(literal)
+    }
+
+    /// Undoes assignments until only the first `len` remain on the trail.
+    pub fn backtrack(&mut self, len: usize) {
+        while self.trail.len() > len {
+            self.pop();
+        }
+    }
+
+    /// Assigned literals in assignment order.
+    pub fn trail(&self) -> &[Literal] {
+        &self.trail
+    }
+
+    /// The same state over the variables of `vars`, with internal ones unassigned.
+    pub fn project(&self, vars: &VarTable) -> InstanceState {
+        let mut state = InstanceState::new(vars.len());
+        for &literal in &self.trail {
+            let var = literal.var();
+            if var.index() < vars.len() && !vars.is_internal(var) {
+                state.assign(literal);
+            }
+        }
+        state
+    }
+}
@@ -0,0 +1,760 @@
+/*
+Lowering of clauses into conjunctive normal form
+
+DPLL and the DIMACS writer work on plain disjunctions, every other
+operator is rewritten into them, as the CDCL solver needs for the
+operators that are neither cardinality nor parity constraints:
+
+    OR       l1 | .. | ln                   the clause itself
+    AND      l1 & .. & ln                   one unit clause per literal
+    NAND     !(l1 & .. & ln)                !l1 | .. | !ln
+    NOR      !(l1 | .. | ln)                one unit clause !li per literal
+    IMPLIES  l1 & .. & ln-1 -> ln           !l1 | .. | !ln-1 | ln
+    EQUIV    l1 = .. = ln                   cycle of implications li -> li+1
+    XOR      odd number of literals true    parity, see below
+    XNOR     even number of literals true   parity, see below
+    AtMost   at most k literals true        cardinality, see below
+    AtLeast  at least k literals true       at most n - k negated literals true
+    Exactly  exactly k literals true        both of the above
+
+A parity constraint over n literals is written out directly when n
+is small, one clause for each of the 2^(n-1) assignments with the
+wrong parity. Longer ones are cut into chunks linked by auxiliary
+variables t = l1 ^ l2 ^ l3, which number past the variables of the
+instance and never reach its VarTable.
+
+Cardinality clauses are lowered with the CardinalityEncoding chosen in
+CnfOptions, all of which only encode the upper bound:
+
+    Pairwise            every k + 1 literals have a false one,
+                        (n choose k + 1) clauses and no auxiliary variables
+    SequentialCounter   Sinz' unary counter after each literal, O(n k)
+    Totalizer           unary counts merged up a binary tree, O(n k)
+                        auxiliary variables and O(n k^2) clauses
+    CardinalityNetwork  odd-even merge sorting network of half comparators,
+                        O(n log^2 n), the k + 1st largest output is false
+    Commander           groups of three with a commander variable each,
+                        for at most one; larger k use SequentialCounter
+
+Pseudo-Boolean constraints are normalized to "sum of weight * literal
+>= bound" first, see the `pb` module. Weights above the bound are
+lowered to it, and when all weights are equal the constraint is a
+cardinality clause. Otherwise the PbEncoding of CnfOptions applies:
+
+    Bdd                 decision diagram over the literals by falling
+                        weight, one node per distinct remaining bound
+    Adder               binary adder network summing the weights,
+                        compared against the bound bit by bit
+    SortingNetwork      cardinality network over the literals repeated
+                        weight times, only suited to small weights
+*/
+use std::collections::HashMap;
+
+use crate::formula::{Clause, Literal, Operator, SatInstance, Var};
+use crate::pb::{Overflow, PbConstraint};
+
+/*
+Parity constraints longer than this are cut into chunks
+*/
+const MAX_PARITY: usize = 4;
+
+/*
+Group size of the commander encoding, up to two groups are encoded pairwise
+*/
+const COMMANDER_GROUP: usize = 3;
+
+/// CNF encoding of cardinality clauses, see the `cnf` module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CardinalityEncoding {
+    Pairwise,
+    #[default]
+    SequentialCounter,
+    Totalizer,
+    CardinalityNetwork,
+    Commander
+}
+
+/// CNF encoding of pseudo-Boolean constraints, see the `cnf` module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PbEncoding {
+    #[default]
+    Bdd,
+    Adder,
+    SortingNetwork
+}
+
+/// Encoding choices for `SatInstance::to_cnf_with`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CnfOptions {
+    pub cardinality: CardinalityEncoding,
+    pub pb: PbEncoding
+}
+
+/*
+Output of a gate, constant when the inputs decide it
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Bit {
+    Const(bool),
+    Literal(Literal)
+}
+
+impl Bit {
+    fn negate(self) -> Bit {
+        match self {
+            Bit::Const(value) => Bit::Const(!value),
+            Bit::Literal(literal) => Bit::Literal(!literal)
+        }
+    }
+}
+
+/// Instance in conjunctive normal form.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cnf {
+    /// Number of variables, the ones past the VarTable
+    /// of the instance are auxiliary.
+    pub vars: usize,
+    /// Disjunctions of literals.
+    pub clauses: Vec<Vec<Literal>>
+}
+
+impl Cnf {
+    /// Empty CNF over `vars` variables.
+    pub fn new(vars: usize) -> Cnf {
+        Cnf { vars, clauses: Vec::new() }
+    }
+
+    /// New auxiliary variable.
+    pub fn fresh(&mut self) -> Var {
+        self.vars += 1;
+        Var::new(self.vars - 1)
+    }
+
+    /// Adds disjunctions equivalent to `clause`, up to the values
+    /// of the auxiliary variables the chosen encodings introduce.
+    pub fn add_clause(&mut self, clause: &Clause, options: &CnfOptions) {
+        let literals = &clause.literals;
+        match clause.operator {
+            Operator::OR => self.clauses.push(literals.clone()),
+            Operator::AND => {
+                for &literal in literals {
+                    self.clauses.push(vec![literal]);
+                }
+            },
+            Operator::NAND => self.clauses.push(literals.iter().map(|&l| !l).collect()),
+            Operator::NOR => {
+                for &literal in literals {
+                    self.clauses.push(vec![!literal]);
+                }
+            },
+            Operator::IMPLIES => {
+                let mut lowered: Vec<Literal> = literals.iter().map(|&l| !l).collect();
+                if let Some(consequent) = lowered.last_mut() {
+                    *consequent = !*consequent;
+                }
+                self.clauses.push(lowered);
+            },
+            Operator::EQUIV => {
+                for (index, &literal) in literals.iter().enumerate() {
+                    let next = literals[(index + 1) % literals.len()];
+                    if next != literal {
+                        self.clauses.push(vec![!literal, next]);
+                    }
+                }
+            },
+            Operator::XOR => self.add_parity(literals.clone(), true),
+            Operator::XNOR => self.add_parity(literals.clone(), false),
+            Operator::AtMost(k) => self.add_at_most(literals, k, options.cardinality),
+            Operator::AtLeast(k) => self.add_at_least(literals, k, options.cardinality),
+            Operator::Exactly(k) => {
+                self.add_at_most(literals, k, options.cardinality);
+                self.add_at_least(literals, k, options.cardinality);
+            }
+        }
+    }
+
+    fn add_at_least(&mut self, literals: &[Literal], k: usize, encoding: CardinalityEncoding) {
+        if k > literals.len() {
+            self.clauses.push(Vec::new());
+            return
+        }
+        let negated: Vec<Literal> = literals.iter().map(|&l| !l).collect();
+        self.add_at_most(&negated, literals.len() - k, encoding);
+    }
+
+    fn add_at_most(&mut self, literals: &[Literal], k: usize, encoding: CardinalityEncoding) {
+        if k >= literals.len() {
+            return
+        }
+        if k == 0 {
+            for &literal in literals {
+                self.clauses.push(vec![!literal]);
+            }
+            return
+        }
+        match encoding {
+            CardinalityEncoding::Pairwise => self.add_pairwise(literals, k),
+            CardinalityEncoding::SequentialCounter => self.add_sequential_counter(literals, k),
+            CardinalityEncoding::Totalizer => {
+                let outputs = self.add_totalizer(literals, k + 1);
+                self.clauses.push(vec![!outputs[k]]);
+            },
+            CardinalityEncoding::CardinalityNetwork => self.add_cardinality_network(literals, k),
+            CardinalityEncoding::Commander if k == 1 => self.add_commander(literals),
+            CardinalityEncoding::Commander => self.add_sequential_counter(literals, k)
+        }
+    }
+
+    /*
+    One clause of negations for every k + 1 literals
+    */
+    fn add_pairwise(&mut self, literals: &[Literal], k: usize) {
+        let mut chosen: Vec<usize> = (0..=k).collect();
+        loop {
+            self.clauses.push(chosen.iter().map(|&index| !literals[index]).collect());
+            // Next combination in lexicographic order
+            let mut position = k + 1;
+            loop {
+                if position == 0 {
+                    return
+                }
+                position -= 1;
+                if chosen[position] < literals.len() - (k + 1 - position) {
+                    break;
+                }
+            }
+            chosen[position] += 1;
+            for next in position + 1..=k {
+                chosen[next] = chosen[next - 1] + 1;
+            }
+        }
+    }
+
+    /*
+    counter[i][j] holds when more than j of the first i + 1 literals are true
+    */
+    fn add_sequential_counter(&mut self, literals: &[Literal], k: usize) {
+        let n = literals.len();
+        let counter: Vec<Vec<Literal>> = (0..n - 1)
+            .map(|_| (0..k).map(|_| self.fresh().pos()).collect())
+            .collect();
+
+        self.clauses.push(vec![!literals[0], counter[0][0]]);
+        for &count in &counter[0][1..] {
+            self.clauses.push(vec![!count]);
+        }
+        for i in 1..n - 1 {
+            self.clauses.push(vec![!literals[i], counter[i][0]]);
+            self.clauses.push(vec![!counter[i - 1][0], counter[i][0]]);
+            for j in 1..k {
+                self.clauses.push(vec![!literals[i], !counter[i - 1][j - 1], counter[i][j]]);
+                self.clauses.push(vec![!counter[i - 1][j], counter[i][j]]);
+            }
+            self.clauses.push(vec![!literals[i], !counter[i - 1][k - 1]]);
+        }
+        self.clauses.push(vec![!literals[n - 1], !counter[n - 2][k - 1]]);
+    }
+
+    /*
+    Unary count of the true literals, output i holds when more than i
+    of them are true. Counts are capped at `limit` outputs.
+    */
+    fn add_totalizer(&mut self, literals: &[Literal], limit: usize) -> Vec<Literal> {
+        if literals.len() == 1 {
+            return literals.to_vec()
+        }
+        let (left, right) = literals.split_at(literals.len() / 2);
+        let left = self.add_totalizer(left, limit);
+        let right = self.add_totalizer(right, limit);
+        let outputs: Vec<Literal> = (0..limit.min(left.len() + right.len()))
+            .map(|_| self.fresh().pos())
+            .collect();
+        for i in 0..=left.len() {
+            for j in 0..=right.len() {
+                if i + j == 0 {
+                    continue;
+                }
+                let mut clause = Vec::new();
+                if i > 0 {
+                    clause.push(!left[i - 1]);
+                }
+                if j > 0 {
+                    clause.push(!right[j - 1]);
+                }
+                clause.push(outputs[(i + j).min(outputs.len()) - 1]);
+                self.clauses.push(clause);
+            }
+        }
+        outputs
+    }
+
+    /*
+    Sorts the literals true first with Batcher's odd-even merge sort,
+    padding to a power of two with constant false wires (None)
+    */
+    fn add_cardinality_network(&mut self, literals: &[Literal], k: usize) {
+        let n = literals.len().next_power_of_two();
+        let mut wires: Vec<Option<Literal>> = literals.iter().map(|&l| Some(l)).collect();
+        wires.resize(n, None);
+
+        let mut p = 1;
+        while p < n {
+            let mut step = p;
+            while step >= 1 {
+                let mut j = step % p;
+                while j + step < n {
+                    for i in 0..step.min(n - j - step) {
+                        if (i + j) / (2 * p) == (i + j + step) / (2 * p) {
+                            let (high, low) = self.comparator(wires[i + j], wires[i + j + step]);
+                            wires[i + j] = high;
+                            wires[i + j + step] = low;
+                        }
+                    }
+                    j += 2 * step;
+                }
+                step /= 2;
+            }
+            p *= 2;
+        }
+        if let Some(output) = wires[k] {
+            self.clauses.push(vec![!output]);
+        }
+    }
+
+    /*
+    Half comparator for upper bounds: the inputs imply their maximum
+    and together their minimum
+    */
+    fn comparator(&mut self, a: Option<Literal>, b: Option<Literal>) -> (Option<Literal>, Option<Literal>) {
+        let (a, b) = match (a, b) {
+            (Some(a), Some(b)) => (a, b),
+            (a, None) | (None, a) => return (a, None)
+        };
+        let (high, low) = (self.fresh().pos(), self.fresh().pos());
+        self.clauses.push(vec![!a, high]);
+        self.clauses.push(vec![!b, high]);
+        self.clauses.push(vec![!a, !b, low]);
+        (Some(high), Some(low))
+    }
+
+    /*
+    At most one literal: at most one per group, a true literal makes
+    the commander of its group true and at most one commander is true
+    */
+    fn add_commander(&mut self, literals: &[Literal]) {
+        if literals.len() <= 2 * COMMANDER_GROUP {
+            self.add_pairwise(literals, 1);
+            return
+        }
+        let mut commanders = Vec::new();
+        for group in literals.chunks(COMMANDER_GROUP) {
+            if group.len() > 1 {
+                self.add_pairwise(group, 1);
+            }
+            let commander = self.fresh().pos();
+            for &literal in group {
+                self.clauses.push(vec![!literal, commander]);
+            }
+            commanders.push(commander);
+        }
+        self.add_commander(&commanders);
+    }
+
+    /// Adds disjunctions equivalent to `constraint`, up to the values
+    /// of the auxiliary variables the chosen encodings introduce.
+    pub fn add_pb_constraint(&mut self, constraint: &PbConstraint, options: &CnfOptions) -> Result<(), Overflow> {
+        for normalized in constraint.normalize()? {
+            self.add_linear(&normalized.terms, normalized.bound, options);
+        }
+        Ok(())
+    }
+
+    /*
+    Sum of the weights of the true literals is at least `bound`
+    */
+    fn add_linear(&mut self, terms: &[(u64, Literal)], bound: i64, options: &CnfOptions) {
+        if bound <= 0 {
+            return
+        }
+        let bound = bound as u64;
+        let mut terms: Vec<(u64, Literal)> = terms
+            .iter()
+            .map(|&(weight, literal)| (weight.min(bound), literal))
+            .filter(|&(weight, _)| weight > 0)
+            .collect();
+        let total: u64 = terms.iter().map(|&(weight, _)| weight).sum();
+        if total < bound {
+            self.clauses.push(Vec::new());
+            return
+        }
+
+        let weight = terms[0].0;
+        if terms.iter().all(|&(w, _)| w == weight) {
+            let literals: Vec<Literal> = terms.iter().map(|&(_, literal)| literal).collect();
+            let k = bound.div_ceil(weight);
+            self.add_at_least(&literals, k as usize, options.cardinality);
+            return
+        }
+
+        match options.pb {
+            PbEncoding::Bdd => {
+                terms.sort_by_key(|&(weight, _)| std::cmp::Reverse(weight));
+                let mut rest: Vec<u64> = vec![0; terms.len() + 1];
+                for index in (0..terms.len()).rev() {
+                    rest[index] = rest[index + 1] + terms[index].0;
+                }
+                let root = self.add_bdd(&terms, &rest, 0, bound, &mut HashMap::new());
+                self.assert_bit(root);
+            },
+            PbEncoding::Adder => {
+                let sum = self.add_adder(&terms);
+                let at_least = self.add_greater_equal(&sum, bound);
+                self.assert_bit(at_least);
+            },
+            PbEncoding::SortingNetwork => {
+                let negated: Vec<Literal> = terms
+                    .iter()
+                    .flat_map(|&(weight, literal)| std::iter::repeat_n(!literal, weight as usize))
+                    .collect();
+                self.add_at_most(&negated, (total - bound) as usize, CardinalityEncoding::CardinalityNetwork);
+            }
+        }
+    }
+
+    fn assert_bit(&mut self, bit: Bit) {
+        match bit {
+            Bit::Const(true) => {},
+            Bit::Const(false) => self.clauses.push(Vec::new()),
+            Bit::Literal(literal) => self.clauses.push(vec![literal])
+        }
+    }
+
+    /*
+    Node that implies the terms from `index` on sum to at least `bound`,
+    `rest[index]` is the sum of their weights
+    */
+    fn add_bdd(
+        &mut self,
+        terms: &[(u64, Literal)],
+        rest: &[u64],
+        index: usize,
+        bound: u64,
+        nodes: &mut HashMap<(usize, u64), Bit>
+    ) -> Bit {
+        if bound == 0 {
+            return Bit::Const(true)
+        }
+        if rest[index] < bound {
+            return Bit::Const(false)
+        }
+        if let Some(&node) = nodes.get(&(index, bound)) {
+            return node
+        }
+        let (weight, literal) = terms[index];
+        let high = self.add_bdd(terms, rest, index + 1, bound.saturating_sub(weight), nodes);
+        let low = self.add_bdd(terms, rest, index + 1, bound, nodes);
+        let node = if high == low {
+            high
+        } else {
+            let node = self.fresh().pos();
+            // node & literal -> high, node & !literal -> low
+            for (branch, condition) in [(high, !literal), (low, literal)] {
+                match branch {
+                    Bit::Const(true) => {},
+                    Bit::Const(false) => self.clauses.push(vec![!node, condition]),
+                    Bit::Literal(branch) => self.clauses.push(vec![!node, condition, branch])
+                }
+            }
+            Bit::Literal(node)
+        };
+        nodes.insert((index, bound), node);
+        node
+    }
+
+    /*
+    Gates defined by both directions of their equivalence
+    */
+    fn gate_and(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(false), _) | (_, Bit::Const(false)) => Bit::Const(false),
+            (Bit::Const(true), other) | (other, Bit::Const(true)) => other,
+            (Bit::Literal(a), Bit::Literal(b)) => {
+                let output = self.fresh().pos();
+                self.clauses.push(vec![!output, a]);
+                self.clauses.push(vec![!output, b]);
+                self.clauses.push(vec![output, !a, !b]);
+                Bit::Literal(output)
+            }
+        }
+    }
+
+    fn gate_or(&mut self, a: Bit, b: Bit) -> Bit {
+        self.gate_and(a.negate(), b.negate()).negate()
+    }
+
+    fn gate_xor(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(value), other) | (other, Bit::Const(value)) => {
+                if value { other.negate() } else { other }
+            },
+            (Bit::Literal(a), Bit::Literal(b)) => {
+                let output = self.fresh().pos();
+                self.add_direct_parity(vec![a, b, output], false);
+                Bit::Literal(output)
+            }
+        }
+    }
+
+    fn gate_majority(&mut self, a: Bit, b: Bit, c: Bit) -> Bit {
+        let ab = self.gate_and(a, b);
+        let ac = self.gate_and(a, c);
+        let bc = self.gate_and(b, c);
+        let either = self.gate_or(ab, ac);
+        self.gate_or(either, bc)
+    }
+
+    /*
+    Bits of the sum of the weights of the true literals, least significant first
+    */
+    fn add_adder(&mut self, terms: &[(u64, Literal)]) -> Vec<Bit> {
+        let mut columns: Vec<Vec<Bit>> = vec![Vec::new(); 64];
+        for &(weight, literal) in terms {
+            for (bit, column) in columns.iter_mut().enumerate() {
+                if weight >> bit & 1 == 1 {
+                    column.push(Bit::Literal(literal));
+                }
+            }
+        }
+
+        let mut sum = Vec::new();
+        let mut bit = 0;
+        while bit < columns.len() {
+            while columns[bit].len() >= 2 {
+                let a = columns[bit].pop().unwrap();
+                let b = columns[bit].pop().unwrap();
+                let c = columns[bit].pop().unwrap_or(Bit::Const(false));
+                let ab = self.gate_xor(a, b);
+                let total = self.gate_xor(ab, c);
+                let carry = self.gate_majority(a, b, c);
+                columns[bit].insert(0, total);
+                if bit + 1 == columns.len() {
+                    columns.push(Vec::new());
+                }
+                columns[bit + 1].push(carry);
+            }
+            sum.push(columns[bit].pop().unwrap_or(Bit::Const(false)));
+            bit += 1;
+        }
+        while sum.last() == Some(&Bit::Const(false)) {
+            sum.pop();
+        }
+        sum
+    }
+
+    /*
+    Bit that holds when the binary number `sum` is at least `bound`
+    */
+    fn add_greater_equal(&mut self, sum: &[Bit], bound: u64) -> Bit {
+        let width = sum.len().max(64 - bound.leading_zeros() as usize);
+        let mut at_least = Bit::Const(true);
+        for bit in 0..width {
+            let digit = sum.get(bit).copied().unwrap_or(Bit::Const(false));
+            at_least = if bound >> bit & 1 == 1 {
+                self.gate_and(digit, at_least)
+            } else {
+                self.gate_or(digit, at_least)
+            };
+        }
+        at_least
+    }
+
+    /*
+    Number of true literals is odd when `odd`, otherwise even
+    */
+    fn add_parity(&mut self, mut literals: Vec<Literal>, odd: bool) {
+        while literals.len() > MAX_PARITY {
+            let rest = literals.split_off(MAX_PARITY - 1);
+            let chunk = std::mem::replace(&mut literals, rest);
+            // t = l1 ^ l2 ^ l3, so l1 ^ l2 ^ l3 ^ t is even
+            let t = self.fresh().pos();
+            self.add_direct_parity(chunk.iter().copied().chain(Some(t)).collect(), false);
+            literals.push(t);
+        }
+        self.add_direct_parity(literals, odd);
+    }
+
+    /*
+    One clause per assignment of the wrong parity, falsified by exactly that assignment
+    */
+    fn add_direct_parity(&mut self, literals: Vec<Literal>, odd: bool) {
+        for mask in 0..1u64 << literals.len() {
+            if (mask.count_ones() % 2 == 1) == odd {
+                continue;
+            }
+            let clause = literals
+                .iter()
+                .enumerate()
+                .map(|(bit, &literal)| if mask >> bit & 1 == 1 { !literal } else { literal })
+                .collect();
+            self.clauses.push(clause);
+        }
+    }
+}
+
+impl SatInstance {
+    /// The instance lowered into conjunctive normal form with the
+    /// default encodings, satisfied by the same assignments of the
+    /// variables of the instance.
+    pub fn to_cnf(&self) -> Cnf {
+        self.to_cnf_with(&CnfOptions::default())
+    }
+
+    /// The instance lowered into conjunctive normal form with the encodings of `options`.
+    pub fn to_cnf_with(&self, options: &CnfOptions) -> Cnf {
+        let mut cnf = Cnf::new(self.used_vars());
+        for clause in &self.clauses {
+            cnf.add_clause(clause, options);
+        }
+        for normalized in self.pb_constraints.iter().flat_map(PbConstraint::normalized) {
+            cnf.add_linear(&normalized.terms, normalized.bound, options);
+        }
+        cnf
+    }
+
+    /*
+    Variables of the VarTable, the clauses and the constraints,
+    auxiliary variables number from here
+    */
+    pub(crate) fn used_vars(&self) -> usize {
+        let clauses = self.clauses.iter().flat_map(|clause| clause.literals.iter().copied());
+        let constraints = self.pb_constraints
+            .iter()
+            .flat_map(|constraint| constraint.terms.iter().map(|&(_, literal)| literal));
+        let used = clauses
+            .chain(constraints)
+            .map(|literal| literal.var().index() + 1)
+            .max()
+            .unwrap_or(0);
+        used.max(self.vars.len())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assignment::{InstanceState, Truth};
+    use crate::pb::Comparison;
+    use crate::solver::cdcl::{Solver, Status};
+
+    const CARDINALITY: [CardinalityEncoding; 5] = [
+        CardinalityEncoding::Pairwise,
+        CardinalityEncoding::SequentialCounter,
+        CardinalityEncoding::Totalizer,
+        CardinalityEncoding::CardinalityNetwork,
+        CardinalityEncoding::Commander
+    ];
+
+    const PB: [PbEncoding; 3] = [PbEncoding::Bdd, PbEncoding::Adder, PbEncoding::SortingNetwork];
+
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    /*
+    Checks that the CNF with every assignment of the first `vars`
+    variables as assumptions is satisfiable exactly when `holds` is
+    True for that assignment
+    */
+    fn check_exhaustively<F: Fn(&InstanceState) -> Truth>(cnf: &Cnf, vars: usize, holds: F, what: &str) {
+        let mut solver = Solver::new();
+        for clause in &cnf.clauses {
+            solver.add_clause(clause);
+        }
+        for bits in 0..1u32 << vars {
+            let values: Vec<bool> = (0..vars).map(|var| bits >> var & 1 == 1).collect();
+            let state = InstanceState::from_values(&values);
+            let units: Vec<Literal> = (0..vars).map(|var| Literal::new(Var::new(var), !values[var])).collect();
+            let satisfiable = solver.solve_with_assumptions(&units) == Status::Satisfiable;
+            assert_eq!(satisfiable, holds(&state) == Truth::True, "{} under {:?}", what, values);
+        }
+    }
+
+    #[test]
+    fn cardinality_encodings_are_exact() {
+        for encoding in CARDINALITY {
+            let options = CnfOptions { cardinality: encoding, ..CnfOptions::default() };
+            for n in 0..=6 {
+                for k in 0..=n + 1 {
+                    for negated in [0, 0b10110] {
+                        let literals: Vec<Literal> = (0..n)
+                            .map(|var| Literal::new(Var::new(var), negated >> var & 1 == 1))
+                            .collect();
+                        for operator in [Operator::AtMost(k), Operator::AtLeast(k), Operator::Exactly(k)] {
+                            let clause = Clause::new(operator, literals.clone());
+                            let mut cnf = Cnf::new(n);
+                            cnf.add_clause(&clause, &options);
+                            let what = format!("{:?} {:?}", encoding, clause);
+                            check_exhaustively(&cnf, n, |state| clause.evaluate(state), &what);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cardinality_encodings_count_repeated_literals() {
+        let mut random = Random(0x9e37_79b9);
+        for _ in 0..300 {
+            let vars = 1 + random.below(4);
+            let len = random.below(7);
+            let literals: Vec<Literal> = (0..len)
+                .map(|_| Literal::new(Var::new(random.below(vars)), random.below(2) == 1))
+                .collect();
+            let k = random.below(len + 2);
+            let operator = [Operator::AtMost(k), Operator::AtLeast(k), Operator::Exactly(k)][random.below(3)].clone();
+            let clause = Clause::new(operator, literals);
+            for encoding in CARDINALITY {
+                let options = CnfOptions { cardinality: encoding, ..CnfOptions::default() };
+                let mut cnf = Cnf::new(vars);
+                cnf.add_clause(&clause, &options);
+                let what = format!("{:?} {:?}", encoding, clause);
+                check_exhaustively(&cnf, vars, |state| clause.evaluate(state), &what);
+            }
+        }
+    }
+
+    #[test]
+    fn pb_encodings_are_exact() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        for _ in 0..600 {
+            let vars = 1 + random.below(5);
+            let terms: Vec<(i64, Literal)> = (0..random.below(6))
+                .map(|_| {
+                    let coefficient = random.below(11) as i64 - 5;
+                    (coefficient, Literal::new(Var::new(random.below(vars)), random.below(2) == 1))
+                })
+                .collect();
+            let comparison = [Comparison::AtLeast, Comparison::AtMost, Comparison::Equal][random.below(3)];
+            let bound = random.below(15) as i64 - 4;
+            let constraint = PbConstraint::new(terms, comparison, bound);
+            for encoding in PB {
+                for cardinality in [CardinalityEncoding::SequentialCounter, CardinalityEncoding::Totalizer] {
+                    let options = CnfOptions { cardinality, pb: encoding };
+                    let mut cnf = Cnf::new(vars);
+                    cnf.add_pb_constraint(&constraint, &options).unwrap();
+                    let what = format!("{:?} {:?}", options, constraint);
+                    check_exhaustively(&cnf, vars, |state| constraint.evaluate(state), &what);
+                }
+            }
+        }
+    }
+}
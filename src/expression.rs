@@ -0,0 +1,155 @@
+/*
+General Boolean formula over the variables of a VarTable
+
+Unlike Clause, formulas nest arbitrarily. Solving one means lowering
+it into clauses first, see the tseitin module.
+*/
+use crate::assignment::{InstanceState, Truth};
+use crate::formula::{Var, VarTable};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Formula {
+    Const(bool),
+    Var(Var),
+    Not(Box<Formula>),
+    /// Conjunction, true when empty.
+    And(Vec<Formula>),
+    /// Disjunction, false when empty.
+    Or(Vec<Formula>),
+    Xor(Box<Formula>, Box<Formula>),
+    Implies(Box<Formula>, Box<Formula>),
+    Iff(Box<Formula>, Box<Formula>),
+    /// If-then-else: `Ite(c, a, b)` is `a` when `c` holds, otherwise `b`.
+    Ite(Box<Formula>, Box<Formula>, Box<Formula>)
+}
+
+impl Formula {
+    pub fn var(var: Var) -> Formula {
+        Formula::Var(var)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(formula: Formula) -> Formula {
+        Formula::Not(Box::new(formula))
+    }
+
+    pub fn and(formulas: Vec<Formula>) -> Formula {
+        Formula::And(formulas)
+    }
+
+    pub fn or(formulas: Vec<Formula>) -> Formula {
+        Formula::Or(formulas)
+    }
+
+    pub fn xor(a: Formula, b: Formula) -> Formula {
+        Formula::Xor(Box::new(a), Box::new(b))
+    }
+
+    pub fn implies(a: Formula, b: Formula) -> Formula {
+        Formula::Implies(Box::new(a), Box::new(b))
+    }
+
+    pub fn iff(a: Formula, b: Formula) -> Formula {
+        Formula::Iff(Box::new(a), Box::new(b))
+    }
+
+    pub fn ite(condition: Formula, then: Formula, otherwise: Formula) -> Formula {
+        Formula::Ite(Box::new(condition), Box::new(then), Box::new(otherwise))
+    }
+
+    /// Kleene evaluation under a possibly partial assignment.
+    pub fn evaluate(&self, state: &InstanceState) -> Truth {
+        match self {
+            Formula::Const(value) => Truth::from(Some(*value)),
+            Formula::Var(var) => Truth::from(state.value(*var)),
+            Formula::Not(formula) => !formula.evaluate(state),
+            Formula::And(formulas) => formulas
+                .iter()
+                .fold(Truth::True, |truth, f| truth.and(f.evaluate(state))),
+            Formula::Or(formulas) => formulas
+                .iter()
+                .fold(Truth::False, |truth, f| truth.or(f.evaluate(state))),
+            Formula::Xor(a, b) => {
+                match (a.evaluate(state), b.evaluate(state)) {
+                    (Truth::Unknown, _) | (_, Truth::Unknown) => Truth::Unknown,
+                    (a, b) => Truth::from(Some(a != b))
+                }
+            },
+            Formula::Implies(a, b) => (!a.evaluate(state)).or(b.evaluate(state)),
+            Formula::Iff(a, b) => {
+                match (a.evaluate(state), b.evaluate(state)) {
+                    (Truth::Unknown, _) | (_, Truth::Unknown) => Truth::Unknown,
+                    (a, b) => Truth::from(Some(a == b))
+                }
+            },
+            Formula::Ite(condition, then, otherwise) => {
+                let then = then.evaluate(state);
+                let otherwise = otherwise.evaluate(state);
+                match condition.evaluate(state) {
+                    Truth::True => then,
+                    Truth::False => otherwise,
+                    // Known either way when both branches agree
+                    Truth::Unknown if then == otherwise => then,
+                    Truth::Unknown => Truth::Unknown
+                }
+            }
+        }
+    }
+
+    /// Distinct variables of the formula in index order.
+    pub fn vars(&self) -> Vec<Var> {
+        let mut vars = Vec::new();
+        self.collect_vars(&mut vars);
+        vars.sort();
+        vars.dedup();
+        vars
+    }
+
+    fn collect_vars(&self, vars: &mut Vec<Var>) {
+        match self {
+            Formula::Const(_) => {},
+            Formula::Var(var) => vars.push(*var),
+            Formula::Not(formula) => formula.collect_vars(vars),
+            Formula::And(formulas) | Formula::Or(formulas) => {
+                for formula in formulas {
+                    formula.collect_vars(vars);
+                }
+            },
+            Formula::Xor(a, b) | Formula::Implies(a, b) | Formula::Iff(a, b) => {
+                a.collect_vars(vars);
+                b.collect_vars(vars);
+            },
+            Formula::Ite(condition, then, otherwise) => {
+                condition.collect_vars(vars);
+                then.collect_vars(vars);
+                otherwise.collect_vars(vars);
+            }
+        }
+    }
+
+    /// Fully parenthesized text using the names of `vars`.
+    pub fn display(&self, vars: &VarTable) -> String {
+        let join = |formulas: &[Formula], operator: &str| {
+            let parts: Vec<String> = formulas.iter().map(|f| f.display(vars)).collect();
+            format!("({})", parts.join(operator))
+        };
+        match self {
+            Formula::Const(value) => value.to_string(),
+            Formula::Var(var) => vars.name(*var).to_string(),
+            Formula::Not(formula) => format!("!{}", formula.display(vars)),
+            Formula::And(formulas) if formulas.is_empty() => String::from("true"),
+            Formula::Or(formulas) if formulas.is_empty() => String::from("false"),
+            Formula::And(formulas) => join(formulas, " & "),
+            Formula::Or(formulas) => join(formulas, " | "),
+            Formula::Xor(a, b) => format!("({} ^ {})", a.display(vars), b.display(vars)),
+            Formula::Implies(a, b) => format!("({} -> {})", a.display(vars), b.display(vars)),
+            Formula::Iff(a, b) => format!("({} <-> {})", a.display(vars), b.display(vars)),
+            Formula::Ite(condition, then, otherwise) => format!(
+                "ite({}, {}, {})",
+                condition.display(vars),
+                then.display(vars),
+                otherwise.display(vars)
+            )
+        }
+    }
+}
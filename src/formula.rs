@@ -0,0 +1,698 @@
+/*
+SAT instance is built from N clauses
+
+Clauses can either have AND or OR operator
+and N literals.
+
+Literal is either positive or negative variable. Variables are
+dense indices into the VarTable of the instance, which keeps
+their human-readable names.
+*/
+use std::collections::HashMap;
+use std::ops::Not;
+
+use crate::assignment::{InstanceState, Truth};
+use crate::pb::{Overflow, PbConstraint};
+
+/// Boolean variable, a dense index into a `VarTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Var(u32);
+
+impl Var {
+    pub fn new(index: usize) -> Var {
+        Var(index as u32)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Positive literal of this variable.
+    pub fn pos(self) -> Literal {
+        Literal::pos(self)
+    }
+
+    /// Negated literal of this variable.
+    #[allow(clippy::should_implement_trait)]
+    pub fn neg(self) -> Literal {
+        Literal::neg(self)
+    }
+}
+
+
+/// Variable or its negation, packed as variable * 2 + sign.
+///
+/// Literals of the same variable sort next to each other,
+/// positive one first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Literal(u32);
+
+impl Literal {
+    pub fn new(var: Var, negated: bool) -> Literal {
+        Literal(var.0 << 1 | negated as u32)
+    }
+
+    /// Positive literal of `var`.
+    pub fn pos(var: Var) -> Literal {
+        Literal::new(var, false)
+    }
+
+    /// Negated literal of `var`.
+    pub fn neg(var: Var) -> Literal {
+        Literal::new(var, true)
+    }
+
+    pub fn var(self) -> Var {
+        Var(self.0 >> 1)
+    }
+
+    pub fn is_negated(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    /// The same variable with opposite sign.
+    pub fn negate(self) -> Literal {
+        Literal(self.0 ^ 1)
+    }
+
+    /// Packed encoding, dense over literals so it can index tables.
+    pub fn code(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn same_var_as(self, other: Self) -> bool {
+        self.var() == other.var()
+    }
+
+    pub fn inverse_of(self, other: Self) -> bool {
+        self == other.negate()
+    }
+}
+
+impl Not for Literal {
+    type Output = Literal;
+
+    fn not(self) -> Literal {
+        self.negate()
+    }
+}
+
+
+/// Interns variable names into dense `Var` indices.
+///
+/// Variables introduced while lowering formulas into clauses are
+/// internal, they are left out of the models returned to the user.
+/// Their names are never looked up, a variable of the user taking the
+/// name of one gets a variable of its own and the internal one is
+/// renamed.
+#[derive(Debug, Clone, Default)]
+pub struct VarTable {
+    names: Vec<String>,
+    indices: HashMap<String, Var>,
+    internal: Vec<bool>
+}
+
+impl VarTable {
+    pub fn new() -> VarTable {
+        VarTable::default()
+    }
+
+    fn add(&mut self, name: String, internal: bool) -> Var {
+        let var = Var::new(self.names.len());
+        self.indices.insert(name.clone(), var);
+        self.names.push(name);
+        self.internal.push(internal);
+        var
+    }
+
+    /*
+    `prefix` followed by a number that no variable has
+    */
+    fn unused_name(&self, prefix: &str) -> String {
+        let mut number = self.names.len();
+        while self.indices.contains_key(&format!("{}{}", prefix, number)) {
+            number += 1;
+        }
+        format!("{}{}", prefix, number)
+    }
+
+    /*
+    Frees `name` for a variable of the user, false when one has it
+    */
+    fn claim(&mut self, name: &str) -> bool {
+        let var = match self.indices.get(name) {
+            Some(&var) => var,
+            None => return true
+        };
+        if !self.internal[var.index()] {
+            return false
+        }
+        let name = self.unused_name(name.trim_end_matches(|c: char| c.is_ascii_digit()));
+        self.set_name(var, name);
+        true
+    }
+
+    fn set_name(&mut self, var: Var, name: String) {
+        let old = std::mem::replace(&mut self.names[var.index()], name.clone());
+        self.indices.remove(&old);
+        self.indices.insert(name, var);
+    }
+
+    /// Variable called `name`, added to the table on first use.
+    pub fn intern(&mut self, name: &str) -> Var {
+        if let Some(var) = self.lookup(name) {
+            return var
+        }
+        self.claim(name);
+        self.add(name.to_string(), false)
+    }
+
+    /// New internal variable named `prefix` followed by a number
+    /// that no other variable uses.
+    pub fn fresh(&mut self, prefix: &str) -> Var {
+        let name = self.unused_name(prefix);
+        self.add(name, true)
+    }
+
+    /// True for variables added by `fresh`.
+    pub fn is_internal(&self, var: Var) -> bool {
+        self.internal[var.index()]
+    }
+
+    /// Variable called `name`, internal variables excepted.
+    pub fn lookup(&self, name: &str) -> Option<Var> {
+        self.indices.get(name).copied().filter(|&var| !self.is_internal(var))
+    }
+
+    pub fn name(&self, var: Var) -> &str {
+        &self.names[var.index()]
+    }
+
+    /// Renames `var`, unless another variable of the user already
+    /// has `name`.
+    pub fn rename(&mut self, var: Var, name: &str) -> bool {
+        if self.indices.get(name) == Some(&var) || !self.claim(name) {
+            return false
+        }
+        self.set_name(var, name.to_string());
+        true
+    }
+
+    /// Name of the variable of `literal`, prefixed with `!` when negated.
+    pub fn literal_name(&self, literal: Literal) -> String {
+        let name = self.name(literal.var());
+        if literal.is_negated() {
+            format!("!{}", name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// All variables in index order.
+    pub fn vars(&self) -> impl Iterator<Item = Var> {
+        (0..self.names.len()).map(Var::new)
+    }
+}
+
+
+
+/// How the literals of a clause are combined.
+///
+/// The CDCL solver propagates the cardinality operators as linear
+/// constraints and `XOR` and `XNOR` by Gaussian elimination. The other
+/// operators reach it, and every operator reaches DPLL and the DIMACS
+/// writer, through the CNF lowering described in the `cnf` module.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operator {
+    /// At least one literal is true.
+    OR,
+    /// Every literal is true.
+    AND,
+    /// Some literal is false.
+    NAND,
+    /// No literal is true.
+    NOR,
+    /// An odd number of literals is true.
+    XOR,
+    /// An even number of literals is true.
+    XNOR,
+    /// All literals have the same value.
+    EQUIV,
+    /// The last literal is true when all the others are,
+    /// false when there are no literals at all.
+    IMPLIES,
+    /// At most k literals are true.
+    AtMost(usize),
+    /// At least k literals are true.
+    AtLeast(usize),
+    /// Exactly k literals are true.
+    Exactly(usize)
+}
+
+
+/// Literals joined by one operator, optionally labelled
+/// to tell it apart in unsatisfiable cores.
+#[derive(Debug, Clone)]
+pub struct Clause {
+    pub operator: Operator,
+    pub literals: Vec<Literal>,
+    pub label: Option<String>
+}
+
+
+impl Clause {
+    /// Disjunction of `literals`.
+    pub fn or(literals: Vec<Literal>) -> Clause {
+        Clause { operator: Operator::OR, literals, label: None }
+    }
+
+    /// Conjunction of `literals`.
+    pub fn and(literals: Vec<Literal>) -> Clause {
+        Clause { operator: Operator::AND, literals, label: None }
+    }
+
+    /// Exclusive or of `literals`, true when an odd number of them is.
+    pub fn xor(literals: Vec<Literal>) -> Clause {
+        Clause { operator: Operator::XOR, literals, label: None }
+    }
+
+    pub fn new(operator: Operator, literals: Vec<Literal>) -> Clause {
+        Clause { operator, literals, label: None }
+    }
+
+    /// The clause with `label`.
+    pub fn labelled(mut self, label: &str) -> Clause {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// Operator applied to the literal names, as in `OR(a, !b)`
+    /// or `AtMost(1, a, b, c)`.
+    pub fn display(&self, vars: &VarTable) -> String {
+        let (name, bound) = match self.operator {
+            Operator::AtMost(k) => (String::from("AtMost"), Some(k)),
+            Operator::AtLeast(k) => (String::from("AtLeast"), Some(k)),
+            Operator::Exactly(k) => (String::from("Exactly"), Some(k)),
+            ref operator => (format!("{:?}", operator), None)
+        };
+        let arguments: Vec<String> = bound
+            .map(|k| k.to_string())
+            .into_iter()
+            .chain(self.literals.iter().map(|&literal| vars.literal_name(literal)))
+            .collect();
+        format!("{}({})", name, arguments.join(", "))
+    }
+
+    /// Kleene evaluation: True or False once `state` decides the
+    /// clause, even if some of its literals are still unassigned.
+    pub fn evaluate(&self, state: &InstanceState) -> Truth {
+        self.evaluate_with(|literal| state.literal_truth(literal))
+    }
+
+    pub(crate) fn evaluate_with<F: Fn(Literal) -> Truth>(&self, truth: F) -> Truth {
+        let truths = self.literals.iter().map(|&literal| truth(literal));
+        match self.operator {
+            Operator::OR => truths.fold(Truth::False, Truth::or),
+            Operator::AND => truths.fold(Truth::True, Truth::and),
+            Operator::NAND => !truths.fold(Truth::True, Truth::and),
+            Operator::NOR => !truths.fold(Truth::False, Truth::or),
+            Operator::XOR => Truth::from(odd_parity(truths)),
+            Operator::XNOR => Truth::from(odd_parity(truths).map(|odd| !odd)),
+            // One literal, even repeated, always equals itself
+            Operator::EQUIV if self.literals.iter().all(|&literal| literal == self.literals[0]) => Truth::True,
+            Operator::EQUIV => {
+                let (mut some_true, mut some_false, mut unknown) = (false, false, false);
+                for truth in truths {
+                    match truth {
+                        Truth::True => some_true = true,
+                        Truth::False => some_false = true,
+                        Truth::Unknown => unknown = true
+                    }
+                }
+                match (some_true && some_false, unknown) {
+                    (true, _) => Truth::False,
+                    (false, true) => Truth::Unknown,
+                    (false, false) => Truth::True
+                }
+            },
+            Operator::IMPLIES => {
+                let last = self.literals.len().saturating_sub(1);
+                truths
+                    .enumerate()
+                    .map(|(index, truth)| if index < last { !truth } else { truth })
+                    .fold(Truth::False, Truth::or)
+            },
+            Operator::AtMost(k) => {
+                let (trues, unknowns) = count(truths);
+                at_most(trues, unknowns, k)
+            },
+            Operator::AtLeast(k) => {
+                let (trues, unknowns) = count(truths);
+                at_least(trues, unknowns, k)
+            },
+            Operator::Exactly(k) => {
+                let (trues, unknowns) = count(truths);
+                at_most(trues, unknowns, k).and(at_least(trues, unknowns, k))
+            }
+        }
+    }
+
+    /// True when `state` decides the clause to be true.
+    pub fn satisfied_by(&self, state: &InstanceState) -> bool {
+        self.evaluate(state) == Truth::True
+    }
+
+    /// The only unassigned literal of an undecided clause, with
+    /// the sign that has to become true for the clause to hold.
+    pub fn unit_literal(&self, state: &InstanceState) -> Option<Literal> {
+        if self.evaluate(state) != Truth::Unknown {
+            return None
+        }
+        let mut unassigned = self.literals
+            .iter()
+            .filter(|&&literal| !state.is_assigned(literal.var()));
+        let literal = match (unassigned.next(), unassigned.next()) {
+            (Some(&literal), None) => literal,
+            _ => return None
+        };
+        let truth = self.evaluate_with(|other| {
+            if other.var() == literal.var() {
+                Truth::from(Some(other == literal))
+            } else {
+                state.literal_truth(other)
+            }
+        });
+        Some(if truth == Truth::True { literal } else { !literal })
+    }
+}
+
+/*
+Whether an odd number of `truths` is True, None when some are Unknown
+*/
+fn odd_parity<I: Iterator<Item = Truth>>(mut truths: I) -> Option<bool> {
+    truths.try_fold(false, |odd, truth| match truth {
+        Truth::Unknown => None,
+        truth => Some(odd != (truth == Truth::True))
+    })
+}
+
+/*
+Number of True and of Unknown `truths`
+*/
+fn count<I: Iterator<Item = Truth>>(truths: I) -> (usize, usize) {
+    truths.fold((0, 0), |(trues, unknowns), truth| match truth {
+        Truth::True => (trues + 1, unknowns),
+        Truth::False => (trues, unknowns),
+        Truth::Unknown => (trues, unknowns + 1)
+    })
+}
+
+fn at_most(trues: usize, unknowns: usize, k: usize) -> Truth {
+    if trues > k {
+        Truth::False
+    } else if trues + unknowns <= k {
+        Truth::True
+    } else {
+        Truth::Unknown
+    }
+}
+
+fn at_least(trues: usize, unknowns: usize, k: usize) -> Truth {
+    if trues >= k {
+        Truth::True
+    } else if trues + unknowns < k {
+        Truth::False
+    } else {
+        Truth::Unknown
+    }
+}
+
+
+/// Conjunction of clauses and pseudo-Boolean constraints
+/// over the variables of `vars`.
+#[derive(Debug, Clone, Default)]
+pub struct SatInstance {
+    pub vars: VarTable,
+    pub clauses: Vec<Clause>,
+    /// Constraints that normalize, as `add_pb_constraint` checks.
+    pub pb_constraints: Vec<PbConstraint>
+}
+
+impl SatInstance {
+    /// Instance without clauses, which every state satisfies.
+    pub fn new() -> SatInstance {
+        SatInstance::default()
+    }
+
+    pub fn builder() -> SatInstanceBuilder {
+        SatInstanceBuilder::default()
+    }
+
+    /// Variable called `name`, added to the instance on first use.
+    pub fn var(&mut self, name: &str) -> Var {
+        self.vars.intern(name)
+    }
+
+    pub fn add_clause(&mut self, clause: Clause) {
+        self.clauses.push(clause);
+    }
+
+    /// Adds `constraint` unless its normal form overflows, see `PbConstraint::normalize`.
+    pub fn add_pb_constraint(&mut self, constraint: PbConstraint) -> Result<(), Overflow> {
+        constraint.normalize()?;
+        self.pb_constraints.push(constraint);
+        Ok(())
+    }
+
+    /// Kleene conjunction of the clauses and constraints under `state`.
+    pub fn evaluate(&self, state: &InstanceState) -> Truth {
+        self.clauses
+            .iter()
+            .map(|c| c.evaluate(state))
+            .chain(self.pb_constraints.iter().map(|c| c.evaluate(state)))
+            .fold(Truth::True, Truth::and)
+    }
+
+    /// True when `state` satisfies every clause and constraint.
+    pub fn satisfied_by(&self, state: &InstanceState) -> bool {
+        self.clauses.iter().all(|c| c.satisfied_by(state))
+            && self.pb_constraints.iter().all(|c| c.satisfied_by(state))
+    }
+
+    /// Indices of undecided clauses with exactly one unassigned literal.
+    pub fn unit_clauses(&self, state: &InstanceState) -> Vec<usize> {
+        (0..self.clauses.len())
+            .filter(|&index| self.clauses[index].unit_literal(state).is_some())
+            .collect()
+    }
+
+    /// Indices of clauses that `state` makes false.
+    pub fn conflicting_clauses(&self, state: &InstanceState) -> Vec<usize> {
+        (0..self.clauses.len())
+            .filter(|&index| self.clauses[index].evaluate(state) == Truth::False)
+            .collect()
+    }
+}
+
+
+/// Builds a `SatInstance` clause by clause.
+///
+/// Literals are given as variable names, a leading `!` or `-` negates:
+///
+/// ```
+/// use solver::SatInstance;
+///
+/// // (a or b) and (c and not b)
+/// let instance = SatInstance::builder()
+///     .or(&["a", "b"])
+///     .and(&["c", "!b"])
+///     .build();
+/// assert_eq!(instance.clauses.len(), 2);
+/// assert_eq!(instance.vars.len(), 3);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SatInstanceBuilder {
+    instance: SatInstance
+}
+
+impl SatInstanceBuilder {
+    /// Variable called `name`, for building clauses by hand.
+    pub fn var(&mut self, name: &str) -> Var {
+        self.instance.var(name)
+    }
+
+    fn literal(&mut self, name: &str) -> Literal {
+        match name.strip_prefix('!').or_else(|| name.strip_prefix('-')) {
+            Some(name) => self.var(name).neg(),
+            None => self.var(name).pos()
+        }
+    }
+
+    fn literals(&mut self, names: &[&str]) -> Vec<Literal> {
+        names.iter().map(|name| self.literal(name)).collect()
+    }
+
+    pub fn clause(&mut self, clause: Clause) -> &mut Self {
+        self.instance.add_clause(clause);
+        self
+    }
+
+    pub fn or(&mut self, names: &[&str]) -> &mut Self {
+        let literals = self.literals(names);
+        self.clause(Clause::or(literals))
+    }
+
+    pub fn and(&mut self, names: &[&str]) -> &mut Self {
+        let literals = self.literals(names);
+        self.clause(Clause::and(literals))
+    }
+
+    pub fn xor(&mut self, names: &[&str]) -> &mut Self {
+        let literals = self.literals(names);
+        self.clause(Clause::xor(literals))
+    }
+
+    /// Labels the clause added last.
+    pub fn label(&mut self, label: &str) -> &mut Self {
+        if let Some(clause) = self.instance.clauses.last_mut() {
+            clause.label = Some(label.to_string());
+        }
+        self
+    }
+
+    pub fn build(&self) -> SatInstance {
+        self.instance.clone()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [Operator; 11] = [
+        Operator::OR,
+        Operator::AND,
+        Operator::NAND,
+        Operator::NOR,
+        Operator::XOR,
+        Operator::XNOR,
+        Operator::EQUIV,
+        Operator::IMPLIES,
+        Operator::AtMost(1),
+        Operator::AtLeast(2),
+        Operator::Exactly(1)
+    ];
+
+    /*
+    Truth of the clause over every completion of `state`: True or False
+    when all completions agree, Unknown otherwise
+    */
+    fn brute_force(clause: &Clause, state: &InstanceState, vars: usize) -> Truth {
+        let open: Vec<Var> = (0..vars).map(Var::new).filter(|&var| !state.is_assigned(var)).collect();
+        let (mut some_true, mut some_false) = (false, false);
+        for bits in 0..1u32 << open.len() {
+            let mut complete = state.clone();
+            for (index, &var) in open.iter().enumerate() {
+                complete.assign(Literal::new(var, bits >> index & 1 == 0));
+            }
+            match clause.evaluate(&complete) {
+                Truth::True => some_true = true,
+                Truth::False => some_false = true,
+                Truth::Unknown => panic!("{:?} undecided by a complete state", clause)
+            }
+        }
+        match (some_true, some_false) {
+            (true, false) => Truth::True,
+            (false, true) => Truth::False,
+            _ => Truth::Unknown
+        }
+    }
+
+    #[test]
+    fn evaluate_agrees_with_completions() {
+        let vars = 4;
+        let mut random: u64 = 0x853c_49e6_748f_ea9b;
+        let mut next = |n: u64| {
+            random ^= random << 13;
+            random ^= random >> 7;
+            random ^= random << 17;
+            random % n
+        };
+        for _ in 0..5000 {
+            // Distinct variables, Kleene evaluation cannot see that x and !x are related
+            let mut order: Vec<usize> = (0..vars).collect();
+            for index in (1..vars).rev() {
+                order.swap(index, next(index as u64 + 1) as usize);
+            }
+            let len = next(vars as u64 + 1) as usize;
+            let literals = order[..len].iter().map(|&var| Literal::new(Var::new(var), next(2) == 1)).collect();
+            let clause = Clause::new(OPERATORS[next(OPERATORS.len() as u64) as usize].clone(), literals);
+
+            let mut state = InstanceState::new(vars);
+            for var in 0..vars {
+                if next(2) == 1 {
+                    state.assign(Literal::new(Var::new(var), next(2) == 1));
+                }
+            }
+            let expected = brute_force(&clause, &state, vars);
+            assert_eq!(clause.evaluate(&state), expected, "{:?} under {:?}", clause, state.trail());
+
+            let open: Vec<Literal> = clause.literals.iter().copied().filter(|l| !state.is_assigned(l.var())).collect();
+            let unit = clause.unit_literal(&state);
+            if expected == Truth::Unknown && open.len() == 1 {
+                let literal = unit.expect("unit clause without unit literal");
+                let mut forced = state.clone();
+                forced.assign(literal);
+                assert_eq!(clause.evaluate(&forced), Truth::True, "{:?} under {:?}", clause, state.trail());
+            } else {
+                assert_eq!(unit, None, "{:?} under {:?}", clause, state.trail());
+            }
+        }
+    }
+
+    #[test]
+    fn equiv_of_one_literal_is_true() {
+        let x = Var::new(0);
+        let state = InstanceState::new(1);
+        for literals in [vec![x.pos()], vec![x.neg(), x.neg()], vec![]] {
+            let clause = Clause::new(Operator::EQUIV, literals);
+            assert_eq!(clause.evaluate(&state), Truth::True);
+            assert_eq!(clause.unit_literal(&state), None);
+        }
+        let clause = Clause::new(Operator::EQUIV, vec![x.pos(), x.neg()]);
+        assert_eq!(clause.evaluate(&InstanceState::from_values(&[true])), Truth::False);
+    }
+
+    #[test]
+    fn internal_names_stay_internal() {
+        let mut vars = VarTable::new();
+        let a = vars.intern("a");
+        let t = vars.fresh("t");
+        assert_eq!(vars.name(t), "t1");
+        assert_eq!(vars.lookup("t1"), None);
+
+        // The user's variable gets the name and the internal one another
+        let user = vars.intern("t1");
+        assert_ne!(user, t);
+        assert_eq!(vars.lookup("t1"), Some(user));
+        assert_eq!(vars.intern("t1"), user);
+        assert!(vars.is_internal(t) && !vars.is_internal(user));
+        assert_ne!(vars.name(t), "t1");
+        assert!(vars.rename(a, vars.name(t).to_string().as_str()));
+        assert_ne!(vars.name(a), vars.name(t));
+        assert!(!vars.rename(a, "t1"));
+
+        let names: std::collections::HashSet<&str> = vars.vars().map(|var| vars.name(var)).collect();
+        assert_eq!(names.len(), vars.len());
+    }
+}
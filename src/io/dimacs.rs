@@ -0,0 +1,431 @@
+/*
+DIMACS CNF reader and writer
+
+    c comment
+    p cnf <variables> <clauses>
+    1 -2 0
+    2 3
+    -1 0
+
+Every clause is terminated by 0 and may span several lines.
+Variable n is Var n - 1 and is named after its number, negative
+numbers are negated literals. A line starting with % ends the input, as in
+the SATLIB benchmark files.
+
+As in CryptoMiniSat, a clause starting with x is an XOR clause, which
+counts towards the clauses of the header:
+
+    x1 -2 3 0      1 xor not 2 xor 3
+
+Literals have to be within the variables of the header, but the
+instance only gets the variables up to the largest one used. The
+clause count of the header is only checked by a strict `parse_with`.
+
+Instances with other names are written with a comment line
+"c var <number> <name>" per variable, which the reader uses to
+restore the original names. Variables named there are kept even when
+no clause uses them, the others are named after their numbers.
+*/
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::{Clause, CnfOptions, InstanceState, Literal, Operator, SatInstance, Var};
+
+/// What was wrong with the input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    MissingHeader,
+    DuplicateHeader,
+    InvalidHeader(String),
+    InvalidLiteral(String),
+    VariableOutOfRange { variable: u64, variables: u64 },
+    UnterminatedClause,
+    ClauseCountMismatch { expected: u64, found: u64 },
+    DuplicateName(String)
+}
+
+/// Malformed input at 1-based `line` and `column`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::MissingHeader =>
+                write!(f, "clause before the 'p cnf' header"),
+            ParseErrorKind::DuplicateHeader =>
+                write!(f, "more than one 'p cnf' header"),
+            ParseErrorKind::InvalidHeader(header) =>
+                write!(f, "invalid header '{}', expected 'p cnf <variables> <clauses>'", header),
+            ParseErrorKind::InvalidLiteral(token) =>
+                write!(f, "invalid literal '{}'", token),
+            ParseErrorKind::VariableOutOfRange { variable, variables } =>
+                write!(f, "variable {} is out of range, header declares {}", variable, variables),
+            ParseErrorKind::UnterminatedClause =>
+                write!(f, "last clause is not terminated by 0"),
+            ParseErrorKind::ClauseCountMismatch { expected, found } =>
+                write!(f, "header declares {} clauses but {} were found", expected, found),
+            ParseErrorKind::DuplicateName(name) =>
+                write!(f, "variable name '{}' is taken by another variable", name)
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl Error for ParseError {}
+
+/// How `parse_with` reads its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseOptions {
+    /// Rejects input with another number of clauses than its header
+    /// declares, which many published instances have.
+    pub strict: bool
+}
+
+
+/*
+Splits a line into whitespace separated tokens with their 1-based columns
+*/
+pub(super) fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (column, (offset, c)) in line.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some((column, offset)),
+            (true, Some((token_column, token_offset))) => {
+                tokens.push((token_column + 1, &line[token_offset..offset]));
+                start = None;
+            },
+            _ => {}
+        }
+    }
+    if let Some((token_column, token_offset)) = start {
+        tokens.push((token_column + 1, &line[token_offset..]));
+    }
+    tokens
+}
+
+fn parse_header(line: &str, line_number: usize) -> Result<(u64, u64), ParseError> {
+    let invalid = |column| ParseError {
+        line: line_number,
+        column,
+        kind: ParseErrorKind::InvalidHeader(line.trim().to_string())
+    };
+    let fields = tokens(line);
+    match fields.as_slice() {
+        [_, (_, "cnf"), (variables_column, variables), (clauses_column, clauses)] => {
+            let variables = variables.parse().map_err(|_| invalid(*variables_column))?;
+            let clauses = clauses.parse().map_err(|_| invalid(*clauses_column))?;
+            Ok((variables, clauses))
+        },
+        [_, (format_column, _), ..] => Err(invalid(*format_column)),
+        _ => Err(invalid(1))
+    }
+}
+
+/// Parses DIMACS CNF into an instance of OR clauses,
+/// and XOR clauses for the clauses starting with x.
+pub fn parse(input: &str) -> Result<SatInstance, ParseError> {
+    parse_with(input, &ParseOptions::default())
+}
+
+/// Parses DIMACS CNF as `parse` does, as strictly as `options` ask.
+pub fn parse_with(input: &str, options: &ParseOptions) -> Result<SatInstance, ParseError> {
+    let mut header: Option<(u64, u64)> = None;
+    let mut instance = SatInstance::new();
+    let mut literals: Vec<Literal> = Vec::new();
+    let mut operator = Operator::OR;
+    let mut names: HashMap<u64, (String, usize, usize)> = HashMap::new();
+    let mut used: u64 = 0;
+    let mut end = (1, 1);
+
+    for (index, line) in input.lines().enumerate() {
+        let line_number = index + 1;
+        end = (line_number, line.chars().count() + 1);
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with('c') || trimmed.is_empty() {
+            if let Some((number, offset, name)) = parse_name_comment(trimmed) {
+                let column = line[..line.len() - trimmed.len() + offset].chars().count() + 1;
+                names.insert(number, (name, line_number, column));
+            }
+            continue;
+        }
+        if trimmed.starts_with('%') {
+            break;
+        }
+        if trimmed.starts_with('p') {
+            if header.is_some() {
+                return Err(ParseError {
+                    line: line_number,
+                    column: line.len() - trimmed.len() + 1,
+                    kind: ParseErrorKind::DuplicateHeader
+                })
+            }
+            header = Some(parse_header(line, line_number)?);
+            continue;
+        }
+
+        let mut tokens = tokens(line);
+        if let Some((column, token)) = tokens.first_mut() {
+            if let Some(rest) = token.strip_prefix('x') {
+                if !literals.is_empty() {
+                    let kind = ParseErrorKind::UnterminatedClause;
+                    return Err(ParseError { line: line_number, column: *column, kind })
+                }
+                operator = Operator::XOR;
+                *token = rest;
+                *column += 1;
+            }
+        }
+        for (column, token) in tokens.into_iter().filter(|(_, token)| !token.is_empty()) {
+            let error = |kind| ParseError { line: line_number, column, kind };
+            let variables = match header {
+                Some((variables, _)) => variables,
+                None => return Err(error(ParseErrorKind::MissingHeader))
+            };
+            let value: i64 = token
+                .parse()
+                .map_err(|_| error(ParseErrorKind::InvalidLiteral(token.to_string())))?;
+
+            if value == 0 {
+                let operator = std::mem::replace(&mut operator, Operator::OR);
+                instance.add_clause(Clause::new(operator, std::mem::take(&mut literals)));
+                continue;
+            }
+            let variable = value.unsigned_abs();
+            if variable > variables {
+                return Err(error(ParseErrorKind::VariableOutOfRange { variable, variables }))
+            }
+            used = used.max(variable);
+            literals.push(Literal::new(Var::new(variable as usize - 1), value < 0));
+        }
+    }
+
+    let (line, column) = end;
+    let error = |kind| ParseError { line, column, kind };
+    let (variables, expected) = match header {
+        Some(header) => header,
+        None => return Err(error(ParseErrorKind::MissingHeader))
+    };
+    if !literals.is_empty() || operator != Operator::OR {
+        return Err(error(ParseErrorKind::UnterminatedClause))
+    }
+    let found = instance.clauses.len() as u64;
+    if options.strict && found != expected {
+        return Err(error(ParseErrorKind::ClauseCountMismatch { expected, found }))
+    }
+
+    names.retain(|&number, _| number >= 1 && number <= variables);
+    let count = names.keys().copied().max().unwrap_or(0).max(used);
+    let mut numbers: HashMap<String, u64> = HashMap::new();
+    for number in 1..=count {
+        let name = names.get(&number).map_or_else(|| number.to_string(), |(name, _, _)| name.clone());
+        if let Some(other) = numbers.insert(name, number) {
+            // Numbers name no other variable, so a comment named one of the two
+            let (name, line, column) = names.get(&number).or_else(|| names.get(&other)).unwrap();
+            return Err(ParseError { line: *line, column: *column, kind: ParseErrorKind::DuplicateName(name.clone()) })
+        }
+    }
+    for number in 1..=count {
+        match names.get(&number) {
+            Some((name, _, _)) => instance.var(name),
+            None => instance.var(&number.to_string())
+        };
+    }
+    Ok(instance)
+}
+
+/*
+Number and name of a "c var <number> <name>" comment, with the byte
+offset of the name in `line`
+*/
+fn parse_name_comment(line: &str) -> Option<(u64, usize, String)> {
+    let rest = line.strip_prefix("c var ")?.trim_start();
+    let split = rest.find(char::is_whitespace)?;
+    let number = rest[..split].parse().ok()?;
+    let name = rest[split..].trim();
+    if name.is_empty() {
+        return None
+    }
+    let offset = line.len() - rest[split..].trim_start().len();
+    Some((number, offset, name.to_string()))
+}
+
+
+pub(super) fn dimacs_literal(literal: Literal) -> String {
+    let number = literal.var().index() + 1;
+    if literal.is_negated() {
+        format!("-{}", number)
+    } else {
+        number.to_string()
+    }
+}
+
+/// Writes the instance in DIMACS CNF, lowering clauses with
+/// other operators than OR as `SatInstance::to_cnf` does.
+///
+/// Variable n is written as number n + 1, name comments are only
+/// written when some variable is not already named after its number,
+/// naming the variables the lowering adds too.
+pub fn write<W: Write>(instance: &SatInstance, out: &mut W) -> io::Result<()> {
+    write_with(instance, &CnfOptions::default(), out)
+}
+
+/// Writes the instance in DIMACS CNF using the encodings of `options`.
+pub fn write_with<W: Write>(instance: &SatInstance, options: &CnfOptions, out: &mut W) -> io::Result<()> {
+    let vars = &instance.vars;
+    let cnf = instance.to_cnf_with(options);
+
+    let numeric = vars.vars().all(|var| vars.name(var) == (var.index() + 1).to_string());
+    if !numeric {
+        for var in vars.vars() {
+            writeln!(out, "c var {} {}", var.index() + 1, vars.name(var))?;
+        }
+        // Variables of the lowering are named too, as their numbers may be names of others
+        let mut names = vars.clone();
+        for number in vars.len() + 1..=cnf.vars {
+            let var = names.fresh("aux");
+            writeln!(out, "c var {} {}", number, names.name(var))?;
+        }
+    }
+    writeln!(out, "p cnf {} {}", cnf.vars, cnf.clauses.len())?;
+    for clause in &cnf.clauses {
+        for &literal in clause {
+            write!(out, "{} ", dimacs_literal(literal))?;
+        }
+        writeln!(out, "0")?;
+    }
+    Ok(())
+}
+
+/// Writes a satisfying state in SAT competition format,
+/// unassigned and unknown variables are left out of the value lines.
+pub fn write_model<W: Write>(state: &InstanceState, out: &mut W) -> io::Result<()> {
+    let values = (0..state.num_vars()).filter_map(|index| {
+        let var = Var::new(index);
+        state.value(var).map(|value| Literal::new(var, !value))
+    });
+
+    writeln!(out, "s SATISFIABLE")?;
+    let mut line = String::from("v");
+    for literal in values {
+        let literal = dimacs_literal(literal);
+        if line.len() + literal.len() + 1 > 78 {
+            writeln!(out, "{}", line)?;
+            line = String::from("v");
+        }
+        line.push(' ');
+        line.push_str(&literal);
+    }
+    writeln!(out, "{} 0", line)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: &str) -> (usize, usize, ParseErrorKind) {
+        let error = parse(input).unwrap_err();
+        (error.line, error.column, error.kind)
+    }
+
+    #[test]
+    fn parses_clauses() {
+        let input = "c comment\np cnf 3 3\n1 -2 0\n2\n  3 0\nx1 -3 0\n%\n0\n";
+        let instance = parse(input).unwrap();
+        let (a, b, c) = (Var::new(0), Var::new(1), Var::new(2));
+        let clauses: Vec<(Operator, Vec<Literal>)> =
+            instance.clauses.iter().map(|clause| (clause.operator.clone(), clause.literals.clone())).collect();
+        assert_eq!(clauses, vec![
+            (Operator::OR, vec![a.pos(), b.neg()]),
+            (Operator::OR, vec![b.pos(), c.pos()]),
+            (Operator::XOR, vec![a.pos(), c.neg()])
+        ]);
+        assert_eq!(instance.vars.name(c), "3");
+    }
+
+    #[test]
+    fn variables_are_the_ones_used() {
+        let instance = parse("p cnf 4000000000 1\n1 -2 0\n").unwrap();
+        assert_eq!(instance.vars.len(), 2);
+        assert_eq!(instance.clauses.len(), 1);
+    }
+
+    #[test]
+    fn clause_count_is_checked_when_strict() {
+        let input = "p cnf 2 3\n1 -2 0\n2 0\n";
+        assert_eq!(parse(input).unwrap().clauses.len(), 2);
+        let strict = ParseOptions { strict: true };
+        let error = parse_with(input, &strict).unwrap_err();
+        assert_eq!((error.line, error.column), (3, 4));
+        assert_eq!(error.kind, ParseErrorKind::ClauseCountMismatch { expected: 3, found: 2 });
+        assert!(parse_with("p cnf 2 2\n1 -2 0\n2 0\n", &strict).is_ok());
+    }
+
+    #[test]
+    fn errors_are_positioned() {
+        assert_eq!(error("c\n1 2 0\n"), (2, 1, ParseErrorKind::MissingHeader));
+        assert_eq!(error("c\n"), (1, 2, ParseErrorKind::MissingHeader));
+        assert_eq!(error("p cnf 2 1\n  p cnf 2 1\n"), (2, 3, ParseErrorKind::DuplicateHeader));
+        assert_eq!(error("p dnf 2 1\n"), (1, 3, ParseErrorKind::InvalidHeader(String::from("p dnf 2 1"))));
+        assert_eq!(error("p cnf two 1\n"), (1, 7, ParseErrorKind::InvalidHeader(String::from("p cnf two 1"))));
+        assert_eq!(error("p cnf 2 -1\n"), (1, 9, ParseErrorKind::InvalidHeader(String::from("p cnf 2 -1"))));
+        assert_eq!(error("p\n"), (1, 1, ParseErrorKind::InvalidHeader(String::from("p"))));
+        assert_eq!(error("p cnf 2 1\n1 -a 0\n"), (2, 3, ParseErrorKind::InvalidLiteral(String::from("-a"))));
+        assert_eq!(error("p cnf 2 1\n1 -3 0\n"), (2, 3, ParseErrorKind::VariableOutOfRange { variable: 3, variables: 2 }));
+        assert_eq!(error("p cnf 2 1\n1 -2\n"), (2, 5, ParseErrorKind::UnterminatedClause));
+        assert_eq!(error("p cnf 2 2\n1\nx2 0\n"), (3, 1, ParseErrorKind::UnterminatedClause));
+        assert_eq!(error("p cnf 2 1\nx1 2\n"), (2, 5, ParseErrorKind::UnterminatedClause));
+        let duplicate = |name: &str| ParseErrorKind::DuplicateName(String::from(name));
+        assert_eq!(error("c var 1 a\n c var 2  a\np cnf 2 1\n1 2 0\n"), (2, 11, duplicate("a")));
+        assert_eq!(error("c var 1 2\np cnf 2 1\n1 2 0\n"), (1, 9, duplicate("2")));
+    }
+
+    fn names(instance: &SatInstance) -> Vec<&str> {
+        instance.vars.vars().map(|var| instance.vars.name(var)).collect()
+    }
+
+    fn round_trip(instance: &SatInstance) -> SatInstance {
+        let mut out = Vec::new();
+        write(instance, &mut out).unwrap();
+        parse(&String::from_utf8(out).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn names_survive_a_round_trip() {
+        for vars in [vec!["b", "1"], vec!["2", "1", "x"], vec!["3", "2", "1"], vec!["1", "2"], vec!["a b", "c"]] {
+            let mut instance = SatInstance::new();
+            let literals: Vec<Literal> = vars.iter().map(|name| instance.var(name).pos()).collect();
+            instance.add_clause(Clause::or(literals));
+            assert_eq!(names(&round_trip(&instance)), vars);
+        }
+
+        // Variables of the lowering can neither take nor give up names
+        let mut instance = SatInstance::new();
+        let vars: Vec<String> = (10..20).map(|number| number.to_string()).collect();
+        let literals: Vec<Literal> = vars.iter().map(|name| instance.var(name).pos()).collect();
+        instance.add_clause(Clause::new(Operator::XOR, literals));
+        let read = round_trip(&instance);
+        assert!(read.vars.len() > 10);
+        assert_eq!(names(&read)[..10], vars);
+    }
+
+    #[test]
+    fn named_variables_are_kept() {
+        let instance = parse("c var 1 a\nc var 3 c\nc var 9 z\np cnf 4 1\n-1 0\n").unwrap();
+        assert_eq!(names(&instance), vec!["a", "2", "c"]);
+    }
+}
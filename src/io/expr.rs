@@ -0,0 +1,489 @@
+/*
+Infix Boolean expression reader
+
+    # comment
+    (a | b) & (c & !b);
+    a -> ite(b, c, !d)
+
+Operators from tightest to loosest binding:
+
+    !  ~         not
+    &  &&        and
+    ^            xor
+    |  ||        or
+    ->           implies, right associative
+    <->          if and only if
+
+`true` and `false` are constants and `ite(c, a, b)` is if-then-else.
+Names are made of letters, digits, `_` and `.`. Formulas separated
+by `;` must all hold. Spaces are allowed inside `->` and `<->` so
+that text produced by `stringify!` parses, see the `sat!` macro.
+*/
+use std::error::Error;
+use std::fmt;
+
+use crate::expression::Formula;
+use crate::formula::{SatInstance, VarTable};
+use crate::tseitin::Encoding;
+
+/// What was wrong with the input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    InvalidCharacter(char),
+    /// Incomplete `->` or `<->`.
+    InvalidOperator(String),
+    UnexpectedToken { found: String, expected: &'static str },
+    UnexpectedEnd { expected: &'static str }
+}
+
+/// Malformed input at 1-based `line` and `column`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::InvalidCharacter(c) =>
+                write!(f, "invalid character '{}'", c),
+            ParseErrorKind::InvalidOperator(operator) =>
+                write!(f, "invalid operator '{}', expected '->' or '<->'", operator),
+            ParseErrorKind::UnexpectedToken { found, expected } =>
+                write!(f, "unexpected '{}', expected {}", found, expected),
+            ParseErrorKind::UnexpectedEnd { expected } =>
+                write!(f, "unexpected end of input, expected {}", expected)
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl Error for ParseError {}
+
+impl ParseError {
+    /// The offending line of `input` with a caret under the error column.
+    pub fn snippet(&self, input: &str) -> String {
+        let line = input.lines().nth(self.line - 1).unwrap_or("");
+        format!("{}\n{}^", line, " ".repeat(self.column - 1))
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Name(String),
+    Not,
+    And,
+    Xor,
+    Or,
+    Implies,
+    Iff,
+    Open,
+    Close,
+    Comma,
+    Semicolon
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    line: usize,
+    column: usize
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+/*
+Characters of the input with their 1-based line and column
+*/
+struct Chars<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize
+}
+
+impl<'a> Chars<'a> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    /*
+    Consumes `expected` after optional whitespace, for the rest of `->` and `<->`
+    */
+    fn expect(&mut self, expected: char, text: &mut String) -> bool {
+        self.skip_spaces();
+        if self.peek() == Some(expected) {
+            self.next();
+            text.push(expected);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut chars = Chars { chars: input.chars().peekable(), line: 1, column: 1 };
+    let mut tokens = Vec::new();
+
+    loop {
+        chars.skip_spaces();
+        let (line, column) = (chars.line, chars.column);
+        let c = match chars.next() {
+            Some(c) => c,
+            None => return Ok(tokens)
+        };
+        let error = |kind| ParseError { line, column, kind };
+        let mut text = c.to_string();
+
+        let kind = match c {
+            '#' => {
+                while chars.peek().is_some_and(|c| c != '\n') {
+                    chars.next();
+                }
+                continue;
+            },
+            '!' | '~' => TokenKind::Not,
+            '^' => TokenKind::Xor,
+            '(' => TokenKind::Open,
+            ')' => TokenKind::Close,
+            ',' => TokenKind::Comma,
+            ';' => TokenKind::Semicolon,
+            '&' | '|' => {
+                if chars.peek() == Some(c) {
+                    chars.next();
+                    text.push(c);
+                }
+                if c == '&' { TokenKind::And } else { TokenKind::Or }
+            },
+            '-' => {
+                if !chars.expect('>', &mut text) {
+                    return Err(error(ParseErrorKind::InvalidOperator(text)))
+                }
+                TokenKind::Implies
+            },
+            '<' => {
+                if !(chars.expect('-', &mut text) && chars.expect('>', &mut text)) {
+                    return Err(error(ParseErrorKind::InvalidOperator(text)))
+                }
+                TokenKind::Iff
+            },
+            c if is_name_char(c) => {
+                while let Some(c) = chars.peek().filter(|&c| is_name_char(c)) {
+                    chars.next();
+                    text.push(c);
+                }
+                TokenKind::Name(text.clone())
+            },
+            c => return Err(error(ParseErrorKind::InvalidCharacter(c)))
+        };
+        tokens.push(Token { kind, text, line, column });
+    }
+}
+
+
+/*
+Recursive descent parser, one method per precedence level
+*/
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    end: (usize, usize),
+    vars: &'a mut VarTable
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.position).map(|token| &token.kind)
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek() == Some(kind) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ParseError {
+        match self.tokens.get(self.position) {
+            Some(token) => ParseError {
+                line: token.line,
+                column: token.column,
+                kind: ParseErrorKind::UnexpectedToken { found: token.text.clone(), expected }
+            },
+            None => ParseError {
+                line: self.end.0,
+                column: self.end.1,
+                kind: ParseErrorKind::UnexpectedEnd { expected }
+            }
+        }
+    }
+
+    fn expect(&mut self, kind: &TokenKind, expected: &'static str) -> Result<(), ParseError> {
+        if self.eat(kind) {
+            Ok(())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn formulas(&mut self) -> Result<Formula, ParseError> {
+        let mut formulas = Vec::new();
+        while self.peek().is_some() {
+            if self.eat(&TokenKind::Semicolon) {
+                continue;
+            }
+            formulas.push(self.iff()?);
+            if self.peek().is_some() && !self.eat(&TokenKind::Semicolon) {
+                return Err(self.unexpected("an operator or ';'"))
+            }
+        }
+        Ok(match formulas.len() {
+            1 => formulas.pop().unwrap(),
+            _ => Formula::and(formulas)
+        })
+    }
+
+    fn iff(&mut self) -> Result<Formula, ParseError> {
+        let mut formula = self.implies()?;
+        while self.eat(&TokenKind::Iff) {
+            formula = Formula::iff(formula, self.implies()?);
+        }
+        Ok(formula)
+    }
+
+    fn implies(&mut self) -> Result<Formula, ParseError> {
+        let formula = self.or()?;
+        if self.eat(&TokenKind::Implies) {
+            return Ok(Formula::implies(formula, self.implies()?))
+        }
+        Ok(formula)
+    }
+
+    fn or(&mut self) -> Result<Formula, ParseError> {
+        let mut formulas = vec![self.xor()?];
+        while self.eat(&TokenKind::Or) {
+            formulas.push(self.xor()?);
+        }
+        Ok(if formulas.len() == 1 { formulas.pop().unwrap() } else { Formula::or(formulas) })
+    }
+
+    fn xor(&mut self) -> Result<Formula, ParseError> {
+        let mut formula = self.and()?;
+        while self.eat(&TokenKind::Xor) {
+            formula = Formula::xor(formula, self.and()?);
+        }
+        Ok(formula)
+    }
+
+    fn and(&mut self) -> Result<Formula, ParseError> {
+        let mut formulas = vec![self.not()?];
+        while self.eat(&TokenKind::And) {
+            formulas.push(self.not()?);
+        }
+        Ok(if formulas.len() == 1 { formulas.pop().unwrap() } else { Formula::and(formulas) })
+    }
+
+    fn not(&mut self) -> Result<Formula, ParseError> {
+        if self.eat(&TokenKind::Not) {
+            return Ok(Formula::not(self.not()?))
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Formula, ParseError> {
+        let expected = "a name, '!' or '('";
+        match self.peek().cloned() {
+            Some(TokenKind::Open) => {
+                self.position += 1;
+                let formula = self.iff()?;
+                self.expect(&TokenKind::Close, "')'")?;
+                Ok(formula)
+            },
+            Some(TokenKind::Name(name)) => {
+                self.position += 1;
+                match name.as_str() {
+                    "true" => Ok(Formula::Const(true)),
+                    "false" => Ok(Formula::Const(false)),
+                    "ite" if self.peek() == Some(&TokenKind::Open) => {
+                        self.position += 1;
+                        let condition = self.iff()?;
+                        self.expect(&TokenKind::Comma, "','")?;
+                        let then = self.iff()?;
+                        self.expect(&TokenKind::Comma, "','")?;
+                        let otherwise = self.iff()?;
+                        self.expect(&TokenKind::Close, "')'")?;
+                        Ok(Formula::ite(condition, then, otherwise))
+                    },
+                    name => Ok(Formula::var(self.vars.intern(name)))
+                }
+            },
+            _ => Err(self.unexpected(expected))
+        }
+    }
+}
+
+/// Parses `input` into a formula, interning its names into `vars`.
+pub fn parse(input: &str, vars: &mut VarTable) -> Result<Formula, ParseError> {
+    let tokens = tokenize(input)?;
+    let lines = input.split('\n').count();
+    let last = input.rsplit('\n').next().unwrap_or("");
+    let end = (lines, last.chars().count() + 1);
+    Parser { tokens, position: 0, end, vars }.formulas()
+}
+
+/// Parses `input` into an instance, formulas that are not already
+/// clauses are lowered with the Plaisted-Greenbaum encoding.
+///
+/// ```
+/// use solver::io::expr;
+///
+/// let instance = expr::parse_instance("(a | b) & (c & !b)").unwrap();
+/// assert_eq!(instance.clauses.len(), 3);
+///
+/// let error = expr::parse_instance("a & | b").unwrap_err();
+/// assert_eq!(error.to_string(), "1:5: unexpected '|', expected a name, '!' or '('");
+/// ```
+pub fn parse_instance(input: &str) -> Result<SatInstance, ParseError> {
+    let mut vars = VarTable::new();
+    let formula = parse(input, &mut vars)?;
+    Ok(SatInstance::from_formula(&formula, vars, Encoding::PlaistedGreenbaum))
+}
+
+/// Builds a `SatInstance` from an infix expression, written either
+/// as tokens or as a string literal, panicking when it is malformed.
+///
+/// ```
+/// use solver::{sat, SolveResult};
+///
+/// let instance = sat!((a | b) & (c & !b));
+/// assert!(matches!(instance.solve(), SolveResult::Satisfiable(_)));
+///
+/// let instance = sat!("(a <-> !b) & a & b");
+/// assert!(matches!(instance.solve(), SolveResult::Unsatisfiable));
+/// ```
+#[macro_export]
+macro_rules! sat {
+    ($input:literal) => {
+        $crate::io::expr::parse_instance_or_panic(concat!($input))
+    };
+    ($($tokens:tt)+) => {
+        $crate::io::expr::parse_instance_or_panic(stringify!($($tokens)+))
+    };
+}
+
+#[doc(hidden)]
+pub fn parse_instance_or_panic(input: &str) -> SatInstance {
+    parse_instance(input).unwrap_or_else(|error| {
+        panic!("invalid expression: {}\n{}", error, error.snippet(input))
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::SolveResult;
+
+    fn display(input: &str) -> String {
+        let mut vars = VarTable::new();
+        parse(input, &mut vars).unwrap().display(&vars)
+    }
+
+    fn error(input: &str) -> (usize, usize, ParseErrorKind) {
+        let error = parse(input, &mut VarTable::new()).unwrap_err();
+        (error.line, error.column, error.kind)
+    }
+
+    fn unexpected(found: &str, expected: &'static str) -> ParseErrorKind {
+        ParseErrorKind::UnexpectedToken { found: found.to_string(), expected }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(display("a | b & c"), "(a | (b & c))");
+        assert_eq!(display("a & b ^ c"), "((a & b) ^ c)");
+        assert_eq!(display("a ^ b | c ^ d"), "((a ^ b) | (c ^ d))");
+        assert_eq!(display("a ^ b ^ c"), "((a ^ b) ^ c)");
+        assert_eq!(display("!a & ~b"), "(!a & !b)");
+        assert_eq!(display("a && b || !!c"), "((a & b) | !!c)");
+        assert_eq!(display("a | b -> c"), "((a | b) -> c)");
+        assert_eq!(display("a -> b -> c"), "(a -> (b -> c))");
+        assert_eq!(display("a <-> b <-> c"), "((a <-> b) <-> c)");
+        assert_eq!(display("a -> b <-> c | d"), "((a -> b) <-> (c | d))");
+        assert_eq!(display("a - > b < - > c"), "((a -> b) <-> c)");
+    }
+
+    #[test]
+    fn parentheses_and_atoms() {
+        assert_eq!(display("(a | b) & c"), "((a | b) & c)");
+        assert_eq!(display("!(a & (b -> c))"), "!(a & (b -> c))");
+        assert_eq!(display("((a))"), "a");
+        assert_eq!(display("ite(a | b, c, !d) ^ ite"), "(ite((a | b), c, !d) ^ ite)");
+        assert_eq!(display("true | false & x_1.2"), "(true | (false & x_1.2))");
+        assert_eq!(display("a; b # c\n;c;"), "(a & b & c)");
+    }
+
+    #[test]
+    fn errors_are_positioned() {
+        let operand = "a name, '!' or '('";
+        assert_eq!(error("a & | b"), (1, 5, unexpected("|", operand)));
+        assert_eq!(error("a b"), (1, 3, unexpected("b", "an operator or ';'")));
+        assert_eq!(error("(a | b) )"), (1, 9, unexpected(")", "an operator or ';'")));
+        assert_eq!(error("ite(a, b)"), (1, 9, unexpected(")", "','")));
+        assert_eq!(error("(a | b"), (1, 7, ParseErrorKind::UnexpectedEnd { expected: "')'" }));
+        assert_eq!(error("a &\n"), (2, 1, ParseErrorKind::UnexpectedEnd { expected: operand }));
+        assert_eq!(error("a\n  & $b"), (2, 5, ParseErrorKind::InvalidCharacter('$')));
+        assert_eq!(error("a - b"), (1, 3, ParseErrorKind::InvalidOperator(String::from("-"))));
+        assert_eq!(error("a <- b"), (1, 3, ParseErrorKind::InvalidOperator(String::from("<-"))));
+
+        let input = "a &\n  b | ) c";
+        let error = parse(input, &mut VarTable::new()).unwrap_err();
+        assert_eq!(error.snippet(input), "  b | ) c\n      ^");
+    }
+
+    #[test]
+    fn sat_macro() {
+        let instance = sat!(a -> b <-> !c);
+        let vars = &instance.vars;
+        let names: Vec<&str> = vars.vars().filter(|&var| !vars.is_internal(var)).map(|var| vars.name(var)).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert!(matches!(sat!((a | b) & !a & !b).solve(), SolveResult::Unsatisfiable));
+        assert!(matches!(sat!("a ^ b; a").solve(), SolveResult::Satisfiable(_)));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid expression: 1:5")]
+    fn sat_macro_panics_on_errors() {
+        sat!("a & | b");
+    }
+}
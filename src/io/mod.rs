@@ -0,0 +1,7 @@
+/*
+Reading and writing instances, results and proofs
+*/
+pub mod dimacs;
+pub mod expr;
+pub mod opb;
+pub mod proof;
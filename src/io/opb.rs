@@ -0,0 +1,466 @@
+/*
+OPB reader and writer, the pseudo-Boolean competition format
+
+    * #variable= 3 #constraint= 2
+    min: +1 x1 +1 x2 ;
+    +3 x1 +2 x2 -1 x3 >= 2 ;
+    +1 x1 +1 ~x3 = 1 ;
+
+Every constraint is a sum of coefficient and literal terms, a relation
+(>=, <= or =) and an integer bound, terminated by ;. Literals are
+variable names, ~ negates. Lines starting with * are comments. The
+objective function of optimization instances is read and ignored,
+only satisfiability is decided.
+
+Variables x1 .. xn of the header are added in order, so xi is Var i - 1.
+As for DIMACS, the writer numbers variables and records other names
+in "* var <number> <name>" comment lines, which the reader restores.
+*/
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::cnf::{Cnf, CnfOptions};
+use crate::formula::{Literal, Operator, SatInstance, Var, VarTable};
+use crate::pb::{Comparison, PbConstraint};
+
+/// What was wrong with the input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    InvalidCoefficient(String),
+    InvalidLiteral(String),
+    /// Coefficient at the end of the sum.
+    MissingLiteral,
+    /// Product of several literals in one term.
+    NonLinearTerm,
+    MissingRelation,
+    InvalidBound(String),
+    /// Constraint whose normal form does not fit in 64-bit integers.
+    Overflow,
+    UnterminatedConstraint
+}
+
+/// Malformed input at 1-based `line` and `column`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::InvalidCoefficient(token) =>
+                write!(f, "invalid coefficient '{}'", token),
+            ParseErrorKind::InvalidLiteral(token) =>
+                write!(f, "invalid literal '{}'", token),
+            ParseErrorKind::MissingLiteral =>
+                write!(f, "coefficient without a literal"),
+            ParseErrorKind::NonLinearTerm =>
+                write!(f, "products of literals are not supported"),
+            ParseErrorKind::MissingRelation =>
+                write!(f, "constraint without '>=', '<=' or '='"),
+            ParseErrorKind::InvalidBound(token) =>
+                write!(f, "invalid bound '{}'", token),
+            ParseErrorKind::Overflow =>
+                write!(f, "coefficients or bound too large"),
+            ParseErrorKind::UnterminatedConstraint =>
+                write!(f, "last constraint is not terminated by ';'")
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl Error for ParseError {}
+
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize
+}
+
+/*
+Splits the input into tokens, relations and ; stand on their own
+even without surrounding whitespace
+*/
+fn tokens(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        if line.trim_start().starts_with('*') {
+            continue;
+        }
+        let mut start: Option<(usize, usize)> = None;
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let mut k = 0;
+        while k < chars.len() {
+            let (offset, c) = chars[k];
+            let symbol = match c {
+                ';' | '=' => Some(1),
+                '>' | '<' if chars.get(k + 1).map(|&(_, c)| c) == Some('=') => Some(2),
+                _ => None
+            };
+            if c.is_whitespace() || symbol.is_some() {
+                if let Some((column, token_offset)) = start.take() {
+                    tokens.push(Token { text: &line[token_offset..offset], line: index + 1, column: column + 1 });
+                }
+            }
+            if let Some(length) = symbol {
+                let end = chars.get(k + length).map_or(line.len(), |&(end, _)| end);
+                tokens.push(Token { text: &line[offset..end], line: index + 1, column: k + 1 });
+                k += length;
+                continue;
+            }
+            if !c.is_whitespace() && start.is_none() {
+                start = Some((k, offset));
+            }
+            k += 1;
+        }
+        if let Some((column, token_offset)) = start {
+            tokens.push(Token { text: &line[token_offset..], line: index + 1, column: column + 1 });
+        }
+    }
+    tokens
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn parse_header(input: &str) -> Option<u64> {
+    let line = input.lines().next()?;
+    let rest = line.trim_start().strip_prefix('*')?;
+    let mut fields = rest.split_whitespace();
+    while let Some(field) = fields.next() {
+        if field == "#variable=" {
+            return fields.next()?.parse().ok()
+        }
+    }
+    None
+}
+
+fn parse_name_comment(line: &str) -> Option<(u64, String)> {
+    let rest = line.trim_start().strip_prefix("* var ")?.trim_start();
+    let split = rest.find(char::is_whitespace)?;
+    let number = rest[..split].parse().ok()?;
+    let name = rest[split..].trim();
+    if name.is_empty() {
+        return None
+    }
+    Some((number, name.to_string()))
+}
+
+/// Parses OPB into an instance of pseudo-Boolean constraints.
+pub fn parse(input: &str) -> Result<SatInstance, ParseError> {
+    let mut instance = SatInstance::new();
+    for number in 1..=parse_header(input).unwrap_or(0) {
+        instance.var(&format!("x{}", number));
+    }
+
+    let tokens = tokens(input);
+    let mut k = 0;
+    while k < tokens.len() {
+        let start = &tokens[k];
+        let error = |token: &Token, kind| ParseError { line: token.line, column: token.column, kind };
+        let end = tokens[k..]
+            .iter()
+            .position(|token| token.text == ";")
+            .map(|position| k + position)
+            .ok_or_else(|| error(start, ParseErrorKind::UnterminatedConstraint))?;
+        let statement = &tokens[k..end];
+        k = end + 1;
+        if statement.first().is_some_and(|token| token.text == "min:" || token.text == "max:") {
+            continue;
+        }
+
+        let relation = statement
+            .iter()
+            .position(|token| matches!(token.text, ">=" | "<=" | "="))
+            .ok_or_else(|| error(start, ParseErrorKind::MissingRelation))?;
+        let comparison = match statement[relation].text {
+            ">=" => Comparison::AtLeast,
+            "<=" => Comparison::AtMost,
+            _ => Comparison::Equal
+        };
+        let bound = match &statement[relation + 1..] {
+            [bound] => bound
+                .text
+                .parse()
+                .map_err(|_| error(bound, ParseErrorKind::InvalidBound(bound.text.to_string())))?,
+            [] => return Err(error(&statement[relation], ParseErrorKind::InvalidBound(String::new()))),
+            [_, extra, ..] => return Err(error(extra, ParseErrorKind::InvalidBound(extra.text.to_string())))
+        };
+
+        let mut terms = Vec::new();
+        let mut position = 0;
+        let sum = &statement[..relation];
+        while position < sum.len() {
+            let token = &sum[position];
+            // The coefficient may be left out when it is 1
+            let coefficient = if is_name(token.text.trim_start_matches('~')) {
+                1
+            } else {
+                position += 1;
+                token
+                    .text
+                    .parse()
+                    .map_err(|_| error(token, ParseErrorKind::InvalidCoefficient(token.text.to_string())))?
+            };
+            let token = sum
+                .get(position)
+                .ok_or_else(|| error(&sum[position - 1], ParseErrorKind::MissingLiteral))?;
+            let (negated, name) = match token.text.strip_prefix('~') {
+                Some(name) => (true, name),
+                None => (false, token.text)
+            };
+            if !is_name(name) {
+                return Err(error(token, ParseErrorKind::InvalidLiteral(token.text.to_string())))
+            }
+            position += 1;
+            if sum.get(position).is_some_and(|next| is_name(next.text.trim_start_matches('~'))) {
+                return Err(error(&sum[position], ParseErrorKind::NonLinearTerm))
+            }
+            terms.push((coefficient, Literal::new(instance.var(name), negated)));
+        }
+        instance
+            .add_pb_constraint(PbConstraint::new(terms, comparison, bound))
+            .map_err(|_| error(start, ParseErrorKind::Overflow))?;
+    }
+
+    let names: HashMap<u64, String> = input.lines().filter_map(parse_name_comment).collect();
+    for (number, name) in names {
+        if let Some(var) = instance.vars.lookup(&format!("x{}", number)) {
+            instance.vars.rename(var, &name);
+        }
+    }
+    Ok(instance)
+}
+
+
+fn opb_literal(literal: Literal) -> String {
+    let number = literal.var().index() + 1;
+    if literal.is_negated() {
+        format!("~x{}", number)
+    } else {
+        format!("x{}", number)
+    }
+}
+
+fn constraint_line(terms: &[(i64, Literal)], relation: &str, bound: i64) -> String {
+    let mut line = String::new();
+    for &(coefficient, literal) in terms {
+        line.push_str(&format!("{:+} {} ", coefficient, opb_literal(literal)));
+    }
+    format!("{}{} {} ;", line, relation, bound)
+}
+
+/// Writes the instance in OPB. OR, AND and cardinality clauses
+/// become linear constraints, other clauses are lowered with
+/// `options` as `SatInstance::to_cnf_with` does.
+pub fn write<W: Write>(instance: &SatInstance, options: &CnfOptions, out: &mut W) -> io::Result<()> {
+    let unit = |literals: &[Literal]| -> Vec<(i64, Literal)> { literals.iter().map(|&l| (1, l)).collect() };
+    let negative = |literals: &[Literal]| -> Vec<(i64, Literal)> { literals.iter().map(|&l| (-1, l)).collect() };
+
+    let mut lines = Vec::new();
+    let mut cnf = Cnf::new(instance.used_vars());
+    for clause in &instance.clauses {
+        let literals = &clause.literals;
+        match clause.operator {
+            Operator::OR => lines.push(constraint_line(&unit(literals), ">=", 1)),
+            Operator::AND => {
+                for literal in literals {
+                    lines.push(constraint_line(&unit(&[*literal]), ">=", 1));
+                }
+            },
+            Operator::AtLeast(k) => lines.push(constraint_line(&unit(literals), ">=", k as i64)),
+            Operator::AtMost(k) => lines.push(constraint_line(&negative(literals), ">=", -(k as i64))),
+            Operator::Exactly(k) => lines.push(constraint_line(&unit(literals), "=", k as i64)),
+            _ => cnf.add_clause(clause, options)
+        }
+    }
+    for clause in &cnf.clauses {
+        lines.push(constraint_line(&unit(clause), ">=", 1));
+    }
+    for constraint in &instance.pb_constraints {
+        let line = match constraint.comparison {
+            Comparison::AtLeast => constraint_line(&constraint.terms, ">=", constraint.bound),
+            Comparison::Equal => constraint_line(&constraint.terms, "=", constraint.bound),
+            Comparison::AtMost => {
+                let negated: Vec<(i64, Literal)> = constraint.terms.iter().map(|&(c, l)| (-c, l)).collect();
+                constraint_line(&negated, ">=", -constraint.bound)
+            }
+        };
+        lines.push(line);
+    }
+
+    writeln!(out, "* #variable= {} #constraint= {}", cnf.vars, lines.len())?;
+    write_names(&instance.vars, out)?;
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+fn write_names<W: Write>(vars: &VarTable, out: &mut W) -> io::Result<()> {
+    let numbered = |var: Var| vars.name(var) == format!("x{}", var.index() + 1);
+    if vars.vars().all(numbered) {
+        return Ok(())
+    }
+    for var in vars.vars() {
+        writeln!(out, "* var {} {}", var.index() + 1, vars.name(var))?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assignment::{InstanceState, Truth};
+    use crate::formula::Clause;
+
+    fn error(input: &str) -> (usize, usize, ParseErrorKind) {
+        let error = parse(input).unwrap_err();
+        (error.line, error.column, error.kind)
+    }
+
+    fn written(instance: &SatInstance) -> String {
+        let mut out = Vec::new();
+        write(instance, &CnfOptions::default(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn names(instance: &SatInstance) -> Vec<String> {
+        instance.vars.vars().map(|var| instance.vars.name(var).to_string()).collect()
+    }
+
+    /*
+    Instance with named variables a, b and c, negative coefficients and
+    every relation
+    */
+    fn instance() -> SatInstance {
+        let mut instance = SatInstance::new();
+        let (a, b, c) = (instance.var("a"), instance.var("b"), instance.var("c"));
+        instance.add_clause(Clause::or(vec![a.pos(), b.neg()]));
+        instance.add_clause(Clause::new(Operator::AtMost(1), vec![a.pos(), b.pos(), c.neg()]));
+        instance.add_clause(Clause::new(Operator::Exactly(2), vec![a.pos(), b.neg(), c.pos()]));
+        instance.add_pb_constraint(PbConstraint::new(vec![(3, a.pos()), (-2, b.pos())], Comparison::AtMost, 1)).unwrap();
+        instance.add_pb_constraint(PbConstraint::new(vec![(2, a.neg()), (-1, c.neg())], Comparison::Equal, 0)).unwrap();
+        instance.add_pb_constraint(PbConstraint::new(vec![(-5, c.pos())], Comparison::AtLeast, -5)).unwrap();
+        instance
+    }
+
+    #[test]
+    fn parses_constraints() {
+        let input = "* #variable= 4 #constraint= 3\n\
+                     * comment\n\
+                     min: +1 x1 -2 x2 ;\n\
+                     +3 x1 -2 ~x2 >= -1 ;\n\
+                     x3 +2 x1<=2;-1 x2\n  -1 x3 = -1 ;\n";
+        let instance = parse(input).unwrap();
+        assert_eq!(names(&instance), vec!["x1", "x2", "x3", "x4"]);
+        let x = |number: usize| Var::new(number - 1);
+        assert_eq!(instance.pb_constraints, vec![
+            PbConstraint::new(vec![(3, x(1).pos()), (-2, x(2).neg())], Comparison::AtLeast, -1),
+            PbConstraint::new(vec![(1, x(3).pos()), (2, x(1).pos())], Comparison::AtMost, 2),
+            PbConstraint::new(vec![(-1, x(2).pos()), (-1, x(3).pos())], Comparison::Equal, -1)
+        ]);
+        assert!(instance.clauses.is_empty());
+
+        // Without a header variables are added as they appear
+        let instance = parse("+1 y -1 x >= 0 ;\n").unwrap();
+        assert_eq!(names(&instance), vec!["y", "x"]);
+    }
+
+    #[test]
+    fn negative_coefficients_are_evaluated() {
+        let instance = parse("+3 x1 -2 x2 >= 1 ;\n-1 x1 +1 ~x2 <= -1 ;\n").unwrap();
+        for values in [[false, false], [false, true], [true, false], [true, true]] {
+            let state = InstanceState::from_values(&values);
+            let holds = |constraint: &PbConstraint| constraint.evaluate(&state) == Truth::True;
+            let first = 3 * values[0] as i64 - 2 * values[1] as i64 >= 1;
+            let second = -(values[0] as i64) + !values[1] as i64 <= -1;
+            assert_eq!(holds(&instance.pb_constraints[0]), first, "{:?}", values);
+            assert_eq!(holds(&instance.pb_constraints[1]), second, "{:?}", values);
+        }
+    }
+
+    #[test]
+    fn errors_are_positioned() {
+        assert_eq!(error("+1 x1 +a x2 >= 1 ;"), (1, 7, ParseErrorKind::InvalidCoefficient(String::from("+a"))));
+        assert_eq!(error("+1 x1\n+1 ~2 >= 1 ;"), (2, 4, ParseErrorKind::InvalidLiteral(String::from("~2"))));
+        assert_eq!(error("+1 x1 +2 >= 1 ;"), (1, 7, ParseErrorKind::MissingLiteral));
+        assert_eq!(error("+1 x1 x2 >= 1 ;"), (1, 7, ParseErrorKind::NonLinearTerm));
+        assert_eq!(error("+1 x1 >= 1 ;\n  +1 x2 ;"), (2, 3, ParseErrorKind::MissingRelation));
+        assert_eq!(error("+1 x1 >= one ;"), (1, 10, ParseErrorKind::InvalidBound(String::from("one"))));
+        assert_eq!(error("+1 x1 >= ;"), (1, 7, ParseErrorKind::InvalidBound(String::new())));
+        assert_eq!(error("+1 x1 = 1 2 ;"), (1, 11, ParseErrorKind::InvalidBound(String::from("2"))));
+        assert_eq!(error("+1 x1 >= 1 ;\n* c ;\n +1 x2 >= 1\n"), (3, 2, ParseErrorKind::UnterminatedConstraint));
+    }
+
+    #[test]
+    fn writes_constraints() {
+        let expected = "* #variable= 3 #constraint= 6\n\
+                        * var 1 a\n* var 2 b\n* var 3 c\n\
+                        +1 x1 +1 ~x2 >= 1 ;\n\
+                        -1 x1 -1 x2 -1 ~x3 >= -1 ;\n\
+                        +1 x1 +1 ~x2 +1 x3 = 2 ;\n\
+                        -3 x1 +2 x2 >= -1 ;\n\
+                        +2 ~x1 -1 ~x3 = 0 ;\n\
+                        -5 x3 >= -5 ;\n";
+        assert_eq!(written(&instance()), expected);
+
+        // Numbered variables need no name comments
+        let instance = parse("+1 x1 -1 x2 >= 0 ;\n").unwrap();
+        assert_eq!(written(&instance), "* #variable= 2 #constraint= 1\n+1 x1 -1 x2 >= 0 ;\n");
+    }
+
+    #[test]
+    fn instances_survive_a_round_trip() {
+        let instance = instance();
+        let read = parse(&written(&instance)).unwrap();
+        assert_eq!(names(&read), vec!["a", "b", "c"]);
+        assert_eq!(read.pb_constraints.len(), 6);
+        for values in 0..8 {
+            let state = InstanceState::from_values(&[values & 1 != 0, values & 2 != 0, values & 4 != 0]);
+            assert_eq!(read.evaluate(&state), instance.evaluate(&state), "{:?}", state);
+        }
+        assert_eq!(written(&read), written(&instance));
+
+        // Variables of the lowering follow, and some of their values extend each model
+        let mut instance = SatInstance::new();
+        let literals: Vec<Literal> = ["p", "q", "r"].iter().map(|name| instance.var(name).pos()).collect();
+        instance.add_clause(Clause::xor(literals));
+        let read = parse(&written(&instance)).unwrap();
+        assert_eq!(names(&read)[..3], ["p", "q", "r"]);
+        let extra = read.vars.len() - 3;
+        for values in 0..8usize {
+            let values: Vec<bool> = (0..3).map(|k| values >> k & 1 != 0).collect();
+            let extended = (0..1usize << extra).any(|aux| {
+                let aux = (0..extra).map(|k| aux >> k & 1 != 0);
+                let values: Vec<bool> = values.iter().copied().chain(aux).collect();
+                read.evaluate(&InstanceState::from_values(&values)) == Truth::True
+            });
+            assert_eq!(extended, instance.evaluate(&InstanceState::from_values(&values)) == Truth::True, "{:?}", values);
+        }
+    }
+
+    #[test]
+    fn overflowing_constraints_are_positioned_errors() {
+        assert_eq!(error("+1 x1 >= 1 ;\n  -9223372036854775808 x1 <= 0 ;\n"), (2, 3, ParseErrorKind::Overflow));
+        assert_eq!(error("+1 x1 <= -9223372036854775808 ;\n"), (1, 1, ParseErrorKind::Overflow));
+        assert_eq!(error("-1 x1 >= 9223372036854775807 ;\n"), (1, 1, ParseErrorKind::Overflow));
+        assert_eq!(error("+9223372036854775807 x1 +1 x2 >= 1 ;\n"), (1, 1, ParseErrorKind::Overflow));
+        let instance = parse("-9223372036854775807 x1 <= -9223372036854775807 ;\n").unwrap();
+        assert_eq!(instance.pb_constraints.len(), 1);
+    }
+}
@@ -0,0 +1,338 @@
+/*
+DRAT and LRAT proof reader, LRAT writer
+
+    DRAT text      1 -2 0                    d 1 -2 0
+    DRAT binary    a <literals> 0            d <literals> 0
+    LRAT           <id> 1 -2 0 <hints> 0     <id> d <ids> 0
+
+Literals are numbered as in DIMACS, see `solver::ProofFormat` for the
+binary encoding. Lines starting with c are comments. An LRAT deletion
+starts with the id of the last clause added, which the reader ignores.
+*/
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::formula::{Literal, Var};
+use crate::proof::{DratStep, LratStep};
+use super::dimacs::{dimacs_literal, tokens};
+
+/// What was wrong with the input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    InvalidLiteral(String),
+    InvalidId(String),
+    InvalidHint(String),
+    /// Binary step that starts with neither a nor d.
+    InvalidStep(u8),
+    UnterminatedStep
+}
+
+/// Malformed input at 1-based `line` and `column`, in binary
+/// proofs the step and its byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::InvalidLiteral(token) =>
+                write!(f, "invalid literal '{}'", token),
+            ParseErrorKind::InvalidId(token) =>
+                write!(f, "invalid clause id '{}'", token),
+            ParseErrorKind::InvalidHint(token) =>
+                write!(f, "invalid hint '{}'", token),
+            ParseErrorKind::InvalidStep(byte) =>
+                write!(f, "invalid step byte 0x{:02x}, expected 'a' or 'd'", byte),
+            ParseErrorKind::UnterminatedStep =>
+                write!(f, "last step is not terminated by 0")
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl Error for ParseError {}
+
+
+/*
+Literal of a nonzero DIMACS number
+*/
+fn literal(value: i64) -> Option<Literal> {
+    let number = value.unsigned_abs();
+    if number == 0 || number > u32::MAX as u64 / 2 {
+        return None
+    }
+    Some(Literal::new(Var::new(number as usize - 1), value < 0))
+}
+
+/*
+Tokens of the lines that are not comments with their line numbers
+*/
+fn proof_tokens(input: &str) -> impl Iterator<Item = (usize, usize, &str)> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim_start().starts_with('c'))
+        .flat_map(|(index, line)| tokens(line).into_iter().map(move |(column, token)| (index + 1, column, token)))
+}
+
+/// Parses a DRAT proof, binary when it has a zero byte or starts
+/// with a, as no text proof does.
+pub fn parse_drat(input: &[u8]) -> Result<Vec<DratStep>, ParseError> {
+    if input.first() == Some(&b'a') || input.contains(&0) {
+        return parse_binary_drat(input)
+    }
+    let input = String::from_utf8_lossy(input);
+    let mut steps = Vec::new();
+    let mut literals = Vec::new();
+    let mut deletion = false;
+    let mut end = (1, 1);
+    for (line, column, token) in proof_tokens(&input) {
+        end = (line, column);
+        if token == "d" && literals.is_empty() && !deletion {
+            deletion = true;
+            continue;
+        }
+        let error = |kind| ParseError { line, column, kind };
+        let value: i64 = token
+            .parse()
+            .map_err(|_| error(ParseErrorKind::InvalidLiteral(token.to_string())))?;
+        if value != 0 {
+            literals.push(literal(value).ok_or_else(|| error(ParseErrorKind::InvalidLiteral(token.to_string())))?);
+            continue;
+        }
+        let literals = std::mem::take(&mut literals);
+        steps.push(if std::mem::take(&mut deletion) { DratStep::Delete(literals) } else { DratStep::Add(literals) });
+    }
+    if deletion || !literals.is_empty() {
+        let (line, column) = end;
+        return Err(ParseError { line, column, kind: ParseErrorKind::UnterminatedStep })
+    }
+    Ok(steps)
+}
+
+fn parse_binary_drat(input: &[u8]) -> Result<Vec<DratStep>, ParseError> {
+    let mut steps = Vec::new();
+    let mut bytes = input.iter().copied();
+    while let Some(kind) = bytes.next() {
+        let line = steps.len() + 1;
+        let mut column = 1;
+        let error = |column, kind| ParseError { line, column, kind };
+        let deletion = match kind {
+            b'a' => false,
+            b'd' => true,
+            other => return Err(error(column, ParseErrorKind::InvalidStep(other)))
+        };
+        let mut literals = Vec::new();
+        loop {
+            let start = column + 1;
+            let mut code: u64 = 0;
+            let mut shift = 0;
+            loop {
+                let byte = bytes.next().ok_or_else(|| error(column, ParseErrorKind::UnterminatedStep))?;
+                column += 1;
+                if shift > 56 {
+                    return Err(error(start, ParseErrorKind::InvalidLiteral(format!("{:x}..", code))))
+                }
+                code |= ((byte & 0x7f) as u64) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            if code == 0 {
+                break;
+            }
+            let value = if code & 1 == 1 { -((code >> 1) as i64) } else { (code >> 1) as i64 };
+            let literal = literal(value).ok_or_else(|| error(start, ParseErrorKind::InvalidLiteral(value.to_string())))?;
+            literals.push(literal);
+        }
+        steps.push(if deletion { DratStep::Delete(literals) } else { DratStep::Add(literals) });
+    }
+    Ok(steps)
+}
+
+/// Parses an LRAT proof.
+pub fn parse_lrat(input: &str) -> Result<Vec<LratStep>, ParseError> {
+    // Parts of a step: id, d or literals, then hints
+    enum Part {
+        Id,
+        Deleted(Vec<u64>),
+        Literals(u64, Vec<Literal>),
+        Hints(u64, Vec<Literal>, Vec<i64>)
+    }
+
+    let mut steps = Vec::new();
+    let mut part = Part::Id;
+    let mut end = (1, 1);
+    for (line, column, token) in proof_tokens(input) {
+        end = (line, column);
+        let error = |kind| ParseError { line, column, kind };
+        let number = |kind: fn(String) -> ParseErrorKind| -> Result<i64, ParseError> {
+            token.parse().map_err(|_| error(kind(token.to_string())))
+        };
+        part = match part {
+            Part::Id => match number(ParseErrorKind::InvalidId)? {
+                id if id > 0 => Part::Literals(id as u64, Vec::new()),
+                _ => return Err(error(ParseErrorKind::InvalidId(token.to_string())))
+            },
+            Part::Literals(_, literals) if token == "d" && literals.is_empty() => Part::Deleted(Vec::new()),
+            Part::Deleted(mut ids) => match number(ParseErrorKind::InvalidId)? {
+                0 => {
+                    steps.push(LratStep::Delete(ids));
+                    Part::Id
+                },
+                id if id > 0 => {
+                    ids.push(id as u64);
+                    Part::Deleted(ids)
+                },
+                _ => return Err(error(ParseErrorKind::InvalidId(token.to_string())))
+            },
+            Part::Literals(id, mut literals) => match number(ParseErrorKind::InvalidLiteral)? {
+                0 => Part::Hints(id, literals, Vec::new()),
+                value => {
+                    let literal = literal(value).ok_or_else(|| error(ParseErrorKind::InvalidLiteral(token.to_string())))?;
+                    literals.push(literal);
+                    Part::Literals(id, literals)
+                }
+            },
+            Part::Hints(id, literals, mut hints) => match number(ParseErrorKind::InvalidHint)? {
+                0 => {
+                    steps.push(LratStep::Add { id, literals, hints });
+                    Part::Id
+                },
+                hint => {
+                    hints.push(hint);
+                    Part::Hints(id, literals, hints)
+                }
+            }
+        };
+    }
+    if !matches!(part, Part::Id) {
+        let (line, column) = end;
+        return Err(ParseError { line, column, kind: ParseErrorKind::UnterminatedStep })
+    }
+    Ok(steps)
+}
+
+/// Writes an LRAT proof, deletions before the first lemma start
+/// with the id before it.
+pub fn write_lrat<W: Write>(steps: &[LratStep], out: &mut W) -> io::Result<()> {
+    let first = steps.iter().find_map(|step| match step {
+        LratStep::Add { id, .. } => Some(*id),
+        LratStep::Delete(_) => None
+    });
+    let mut last = first.map_or(0, |id| id.saturating_sub(1));
+    for step in steps {
+        match step {
+            LratStep::Add { id, literals, hints } => {
+                write!(out, "{} ", id)?;
+                for &literal in literals {
+                    write!(out, "{} ", dimacs_literal(literal))?;
+                }
+                write!(out, "0 ")?;
+                for hint in hints {
+                    write!(out, "{} ", hint)?;
+                }
+                writeln!(out, "0")?;
+                last = *id;
+            },
+            LratStep::Delete(ids) => {
+                write!(out, "{} d ", last)?;
+                for id in ids {
+                    write!(out, "{} ", id)?;
+                }
+                writeln!(out, "0")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drat_error(input: &[u8]) -> (usize, usize, ParseErrorKind) {
+        let error = parse_drat(input).unwrap_err();
+        (error.line, error.column, error.kind)
+    }
+
+    fn lrat_error(input: &str) -> (usize, usize, ParseErrorKind) {
+        let error = parse_lrat(input).unwrap_err();
+        (error.line, error.column, error.kind)
+    }
+
+    #[test]
+    fn text_and_binary_drat_agree() {
+        let x = |number: usize| Var::new(number - 1);
+        let expected = vec![
+            DratStep::Add(vec![x(1).pos(), x(2).neg()]),
+            DratStep::Delete(vec![x(1).pos(), x(2).neg()]),
+            DratStep::Add(vec![x(64).neg()]),
+            DratStep::Add(Vec::new())
+        ];
+        let text = parse_drat(b"c comment\n1 -2 0\nd 1  -2 0\n  -64\n 0\n0\n").unwrap();
+        assert_eq!(text, expected);
+        let binary = parse_drat(b"a\x02\x05\x00d\x02\x05\x00a\x81\x01\x00a\x00").unwrap();
+        assert_eq!(binary, expected);
+        assert_eq!(parse_drat(b"d\x02\x00").unwrap(), vec![DratStep::Delete(vec![x(1).pos()])]);
+        assert_eq!(parse_drat(b"").unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn drat_errors_are_positioned() {
+        assert_eq!(drat_error(b"1 x 0\n"), (1, 3, ParseErrorKind::InvalidLiteral(String::from("x"))));
+        assert_eq!(drat_error(b"0\n 4294967295 0\n"), (2, 2, ParseErrorKind::InvalidLiteral(String::from("4294967295"))));
+        assert_eq!(drat_error(b"1 -2 0\nd 3\n"), (2, 3, ParseErrorKind::UnterminatedStep));
+        assert_eq!(drat_error(b"1 -2 0\nd\n"), (2, 1, ParseErrorKind::UnterminatedStep));
+
+        assert_eq!(drat_error(b"a\x02\x00b\x00"), (2, 1, ParseErrorKind::InvalidStep(b'b')));
+        assert_eq!(drat_error(b"a\x02"), (1, 2, ParseErrorKind::UnterminatedStep));
+        assert_eq!(drat_error(b"a\x00a\x04\x01\x00"), (2, 3, ParseErrorKind::InvalidLiteral(String::from("0"))));
+        let overlong = [b'a', 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0x00];
+        assert!(matches!(drat_error(&overlong), (1, 2, ParseErrorKind::InvalidLiteral(_))));
+    }
+
+    #[test]
+    fn lrat_survives_a_round_trip() {
+        let x = |number: usize| Var::new(number - 1);
+        let text = "5 1 -2 0 1 -3 4 0\n5 d 1 2 0\n6 0 5 -1 2 0\n";
+        let steps = parse_lrat(&format!("c comment\n{}", text)).unwrap();
+        assert_eq!(steps, vec![
+            LratStep::Add { id: 5, literals: vec![x(1).pos(), x(2).neg()], hints: vec![1, -3, 4] },
+            LratStep::Delete(vec![1, 2]),
+            LratStep::Add { id: 6, literals: Vec::new(), hints: vec![5, -1, 2] }
+        ]);
+        let mut out = Vec::new();
+        write_lrat(&steps, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), text);
+
+        // Deletions before the first lemma start with the id before it
+        let steps = vec![LratStep::Delete(vec![3]), LratStep::Add { id: 4, literals: Vec::new(), hints: vec![1, 2] }];
+        let mut out = Vec::new();
+        write_lrat(&steps, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text, "3 d 3 0\n4 0 1 2 0\n");
+        assert_eq!(parse_lrat(&text).unwrap(), steps);
+    }
+
+    #[test]
+    fn lrat_errors_are_positioned() {
+        assert_eq!(lrat_error("0 1 0 0\n"), (1, 1, ParseErrorKind::InvalidId(String::from("0"))));
+        assert_eq!(lrat_error("1 d -2 0\n"), (1, 5, ParseErrorKind::InvalidId(String::from("-2"))));
+        assert_eq!(lrat_error("1 a 0 0\n"), (1, 3, ParseErrorKind::InvalidLiteral(String::from("a"))));
+        assert_eq!(lrat_error("1 1 0 0\n2 1 0 x 0\n"), (2, 7, ParseErrorKind::InvalidHint(String::from("x"))));
+        assert_eq!(lrat_error("1 1 0\n 2\n"), (2, 2, ParseErrorKind::UnterminatedStep));
+    }
+}
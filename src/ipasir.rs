@@ -0,0 +1,189 @@
+/*
+IPASIR interface for C and C++ programs, declared in include/ipasir.h
+which cbindgen generates from this file (see cbindgen.toml).
+
+Solver handles are boxed `Ipasir` values over an IncrementalSolver.
+Literals are DIMACS numbers, variable n being Var n - 1, which the
+solver names after their numbers as it first sees them. Clauses are
+collected until the 0 that ends them and assumptions until the next
+solve, after which they are dropped as IPASIR requires.
+*/
+use std::os::raw::{c_char, c_int, c_void};
+
+use crate::assignment::InstanceState;
+use crate::formula::{Clause, Literal, Var};
+use crate::solver::{IncrementalSolver, SolveResult};
+
+struct Ipasir {
+    solver: IncrementalSolver,
+    clause: Vec<Literal>,
+    assumptions: Vec<Literal>,
+    model: Option<InstanceState>
+}
+
+fn literal(lit: i32) -> Literal {
+    Literal::new(Var::new(lit.unsigned_abs() as usize - 1), lit < 0)
+}
+
+fn dimacs(literal: Literal) -> i32 {
+    let number = literal.var().index() as i32 + 1;
+    if literal.is_negated() { -number } else { number }
+}
+
+/*
+The solver behind a handle from `ipasir_init`
+*/
+unsafe fn handle<'a>(solver: *mut c_void) -> &'a mut Ipasir {
+    &mut *(solver as *mut Ipasir)
+}
+
+/// Name and version of the solver.
+#[no_mangle]
+pub extern "C" fn ipasir_signature() -> *const c_char {
+    concat!(env!("CARGO_PKG_NAME"), "-", env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// New solver without clauses, to be freed with `ipasir_release`.
+#[no_mangle]
+pub extern "C" fn ipasir_init() -> *mut c_void {
+    let solver = Ipasir {
+        solver: IncrementalSolver::new(),
+        clause: Vec::new(),
+        assumptions: Vec::new(),
+        model: None
+    };
+    Box::into_raw(Box::new(solver)) as *mut c_void
+}
+
+/// Frees the solver.
+///
+/// # Safety
+/// `solver` comes from `ipasir_init` and is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_release(solver: *mut c_void) {
+    drop(Box::from_raw(solver as *mut Ipasir));
+}
+
+/// Adds a literal to the clause being built, 0 adds the clause.
+///
+/// # Safety
+/// `solver` comes from `ipasir_init`.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_add(solver: *mut c_void, lit_or_zero: i32) {
+    let solver = handle(solver);
+    solver.model = None;
+    if lit_or_zero != 0 {
+        solver.clause.push(literal(lit_or_zero));
+        return
+    }
+    let clause = Clause::or(std::mem::take(&mut solver.clause));
+    solver.solver.add_clause(&clause);
+}
+
+/// Assumes `lit` for the next solve only, 0 is ignored.
+///
+/// # Safety
+/// `solver` comes from `ipasir_init`.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_assume(solver: *mut c_void, lit: i32) {
+    if lit == 0 {
+        return
+    }
+    let solver = handle(solver);
+    solver.model = None;
+    solver.assumptions.push(literal(lit));
+}
+
+/// Solves under the assumptions, returns 10 when satisfiable, 20 when
+/// unsatisfiable and 0 when the terminate callback stopped the search.
+///
+/// # Safety
+/// `solver` comes from `ipasir_init`.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_solve(solver: *mut c_void) -> c_int {
+    let solver = handle(solver);
+    let assumptions = std::mem::take(&mut solver.assumptions);
+    match solver.solver.solve_with_assumptions(&assumptions) {
+        SolveResult::Satisfiable(model) => {
+            solver.model = Some(model);
+            10
+        },
+        SolveResult::Unsatisfiable => 20,
+        SolveResult::Unknown => 0
+    }
+}
+
+/// Value of `lit` in the model of the last solve, `lit` when true,
+/// `-lit` when false and 0 when `lit` is 0 or the solver never saw its
+/// variable.
+///
+/// # Safety
+/// `solver` comes from `ipasir_init` and its last solve returned 10.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_val(solver: *mut c_void, lit: i32) -> i32 {
+    if lit == 0 {
+        return 0
+    }
+    let solver = handle(solver);
+    let value = solver.model.as_ref().and_then(|model| model.literal_value(literal(lit)));
+    match value {
+        Some(true) => lit,
+        Some(false) => -lit,
+        None => 0
+    }
+}
+
+/// 1 when the assumption `lit` took part in making the last solve
+/// unsatisfiable, 0 otherwise, including when `lit` is 0.
+///
+/// # Safety
+/// `solver` comes from `ipasir_init` and its last solve returned 20.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_failed(solver: *mut c_void, lit: i32) -> c_int {
+    if lit == 0 {
+        return 0
+    }
+    handle(solver).solver.failed(literal(lit)) as c_int
+}
+
+/// Has the solver call `terminate(data)` during search and give up
+/// when it returns nonzero. A null `terminate` removes the callback.
+///
+/// # Safety
+/// `solver` comes from `ipasir_init` and `terminate` can be called with
+/// `data` as long as it is set.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_set_terminate(
+    solver: *mut c_void,
+    data: *mut c_void,
+    terminate: Option<extern "C" fn(*mut c_void) -> c_int>
+) {
+    let callback = terminate.map(|terminate| Box::new(move || terminate(data) != 0) as Box<dyn FnMut() -> bool>);
+    handle(solver).solver.set_terminate(callback);
+}
+
+/// Passes every learned clause of at most `max_length` literals to
+/// `learn(data, clause)` as 0 terminated literals, valid during the
+/// call. A null `learn` removes the callback.
+///
+/// # Safety
+/// `solver` comes from `ipasir_init` and `learn` can be called with
+/// `data` as long as it is set.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_set_learn(
+    solver: *mut c_void,
+    data: *mut c_void,
+    max_length: c_int,
+    learn: Option<extern "C" fn(*mut c_void, *mut i32)>
+) {
+    let callback = learn.map(|learn| {
+        let mut buffer = Vec::new();
+        Box::new(move |clause: &[Literal]| {
+            buffer.clear();
+            buffer.extend(clause.iter().map(|&literal| dimacs(literal)));
+            buffer.push(0);
+            learn(data, buffer.as_mut_ptr());
+        }) as Box<dyn FnMut(&[Literal])>
+    });
+    handle(solver).solver.set_learn(max_length.max(0) as usize, callback);
+}
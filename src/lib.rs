@@ -0,0 +1,42 @@
+//! SAT solver for instances built from `OR`, `AND`, `XOR` and other clauses
+//! or general formulas lowered into them.
+//!
+//! ```
+//! use solver::{SatInstance, SolveResult};
+//!
+//! let instance = SatInstance::builder()
+//!     .or(&["a", "b"])
+//!     .and(&["c", "!b"])
+//!     .build();
+//!
+//! match instance.solve() {
+//!     SolveResult::Satisfiable(model) => {
+//!         let a = instance.vars.lookup("a").unwrap();
+//!         assert_eq!(model.value(a), Some(true));
+//!     },
+//!     _ => unreachable!()
+//! }
+//! ```
+pub mod analysis;
+pub mod assignment;
+pub mod cnf;
+pub mod expression;
+pub mod formula;
+pub mod io;
+mod ipasir;
+pub mod mcs;
+mod mus;
+pub mod pb;
+pub mod proof;
+pub mod solver;
+pub mod tseitin;
+
+pub use analysis::{Analysis, VarOccurrences};
+pub use assignment::{InstanceState, Truth};
+pub use cnf::{CardinalityEncoding, Cnf, CnfOptions, PbEncoding};
+pub use expression::Formula;
+pub use formula::{Clause, Literal, Operator, SatInstance, SatInstanceBuilder, Var, VarTable};
+pub use mcs::{CorrectionSets, McsAlgorithm};
+pub use pb::{Comparison, PbConstraint};
+pub use solver::{IncrementalSolver, ProofFormat, SolveOptions, SolveResult, Stats};
+pub use tseitin::Encoding;
@@ -1,246 +1,417 @@
 /*
-SAT instance is built from N clauses
-
-Clauses can either have AND or OR operator
-and N literals.
-
-Literal is either positive or negative and has name
+Command-line front end, see USAGE
 */
-use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+use std::process;
+use std::time::{Duration, Instant};
+
+use solver::io::{dimacs, expr, opb, proof};
+use solver::proof::{Proof, Verdict};
+use solver::{
+    CardinalityEncoding, CnfOptions, Encoding, Formula, ProofFormat, SatInstance, SolveOptions, SolveResult, Stats, Truth,
+    VarTable
+};
+
+const USAGE: &str = "\
+Usage: solver [options] <file|->
+
+Reads an instance from <file>, or standard input when it is -,
+solves it and prints the result in SAT competition format.
+
+Exit status is 10 when satisfiable, 20 when unsatisfiable
+and 0 when the result is unknown.
+
+Options:
+  -f, --format <format>     input format: dimacs (default), expr or opb
+  -t, --time-limit <secs>   give up after <secs> seconds
+  -s, --seed <n>            random seed for variable ordering
+  -v, --verbose             print statistics as comment lines, repeat for more
+      --dpll                use plain DPLL search instead of CDCL
+      --core                when unsatisfiable, print a subset of the
+                            clauses that is unsatisfiable as comment lines
+      --mus                 like --core, with a subset that is satisfiable
+                            once any of its clauses is removed, without
+                            a time limit
+      --proof <file>        write a DRAT proof to <file>, which refutes the
+                            CNF of --export when unsatisfiable
+      --proof-format <fmt>  text (default) or binary
+      --check <file>        check the DRAT proof in <file> against the CNF
+                            of --export instead of solving
+      --check-lrat <file>   same for an LRAT proof
+      --trim <file>         with --check or --check-lrat, write the lemmas
+                            the refutation uses as LRAT proof to <file>
+      --export              print the instance as DIMACS CNF instead of solving
+      --encoding <encoding> CNF encoding of cardinality clauses for --export
+                            and --proof:
+                            pairwise, sequential (default), totalizer,
+                            network or commander
+  -h, --help                print this help
+";
+
+#[derive(Debug, Clone, PartialEq)]
+enum InputFormat {
+    Dimacs,
+    Expr,
+    Opb
+}
 
-#[derive(Debug, Eq, Clone)]
-struct Literal {
-    negated: bool,
-    name: String
+#[derive(Debug, Clone)]
+struct Args {
+    input: String,
+    format: InputFormat,
+    options: SolveOptions,
+    verbosity: usize,
+    dpll: bool,
+    core: bool,
+    mus: bool,
+    proof: Option<String>,
+    proof_format: ProofFormat,
+    check: Option<(String, bool)>,
+    trim: Option<String>,
+    export: bool,
+    cnf: CnfOptions
 }
 
-impl Literal {
-    fn same_name_as(&self, other: &Self) -> bool {
-        self.name == other.name
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut input = None;
+    let mut format = InputFormat::Dimacs;
+    let mut options = SolveOptions::default();
+    let mut verbosity = 0;
+    let mut dpll = false;
+    let mut core = false;
+    let mut mus = false;
+    let mut proof = None;
+    let mut proof_format = ProofFormat::Text;
+    let mut check = None;
+    let mut trim = None;
+    let mut export = false;
+    let mut cnf = CnfOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // Accept both --option value and --option=value
+        let (name, inline_value) = match arg.find('=') {
+            Some(split) if arg.starts_with("--") => (&arg[..split], Some(arg[split + 1..].to_string())),
+            _ => (arg.as_str(), None)
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or(format!("missing value for {}", name))
+        };
+
+        match name {
+            "-h" | "--help" => return Err(String::new()),
+            "-f" | "--format" => {
+                format = match value()?.as_str() {
+                    "dimacs" | "cnf" => InputFormat::Dimacs,
+                    "expr" => InputFormat::Expr,
+                    "opb" => InputFormat::Opb,
+                    other => return Err(format!("unknown format '{}'", other))
+                }
+            },
+            "-t" | "--time-limit" => {
+                let value = value()?;
+                let limit = value
+                    .parse()
+                    .ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .ok_or_else(|| format!("invalid time limit '{}'", value))?;
+                options.time_limit = Some(limit);
+            },
+            "-s" | "--seed" => {
+                let value = value()?;
+                options.seed = value.parse().map_err(|_| format!("invalid seed '{}'", value))?;
+            },
+            "-v" | "--verbose" => verbosity += 1,
+            "-vv" => verbosity += 2,
+            "--dpll" => dpll = true,
+            "--core" => core = true,
+            "--mus" => {
+                core = true;
+                mus = true;
+            },
+            "--proof" => proof = Some(value()?),
+            "--proof-format" => {
+                proof_format = match value()?.as_str() {
+                    "text" => ProofFormat::Text,
+                    "binary" => ProofFormat::Binary,
+                    other => return Err(format!("unknown proof format '{}'", other))
+                }
+            },
+            "--check" => check = Some((value()?, false)),
+            "--check-lrat" => check = Some((value()?, true)),
+            "--trim" => trim = Some(value()?),
+            "--export" => export = true,
+            "--encoding" => {
+                cnf.cardinality = match value()?.as_str() {
+                    "pairwise" => CardinalityEncoding::Pairwise,
+                    "sequential" => CardinalityEncoding::SequentialCounter,
+                    "totalizer" => CardinalityEncoding::Totalizer,
+                    "network" => CardinalityEncoding::CardinalityNetwork,
+                    "commander" => CardinalityEncoding::Commander,
+                    other => return Err(format!("unknown encoding '{}'", other))
+                }
+            },
+            "-" => input = Some(arg.clone()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ => {
+                if input.is_some() {
+                    return Err(format!("unexpected argument '{}'", arg))
+                }
+                input = Some(arg.clone());
+            }
+        }
     }
 
-    fn inverse_of(&self, other: &Self) -> bool {
-        self.same_name_as(other) && self.negated != other.negated
+    let input = input.ok_or_else(|| String::from("missing input file"))?;
+    if dpll && core {
+        return Err(String::from("--core and --mus need the CDCL solver"))
     }
-}
-
-impl PartialEq for Literal {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.negated == other.negated
+    // Shrinking the core to a minimal one is not bounded in time
+    if mus && options.time_limit.is_some() {
+        return Err(String::from("--mus cannot be combined with --time-limit"))
     }
-}
-
-impl Ord for Literal {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let ord = self.name.cmp(&other.name);
-        if ord == Ordering::Equal {
-            return self.negated.cmp(&other.negated);
-        }
-        return ord;
+    if proof.is_some() && (dpll || core) {
+        return Err(String::from("--proof needs the CDCL solver without --core or --mus"))
+    }
+    if trim.is_some() && check.is_none() {
+        return Err(String::from("--trim needs --check or --check-lrat"))
     }
+    Ok(Args { input, format, options, verbosity, dpll, core, mus, proof, proof_format, check, trim, export, cnf })
 }
 
-impl PartialOrd for Literal {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+fn read_input(path: &str) -> io::Result<String> {
+    let mut input = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut input)?;
+    } else {
+        input = std::fs::read_to_string(path)?;
     }
+    Ok(input)
 }
 
-
-
-#[derive(Debug, Clone)]
-enum Operator {
-    OR,
-    AND
+/*
+Maps the numbers of the value lines back to variable names, unless
+the variables are already named after their numbers as in DIMACS or OPB
+*/
+fn write_names<W: Write>(vars: &VarTable, out: &mut W) -> io::Result<()> {
+    let numeric = vars.vars().all(|var| {
+        let number = (var.index() + 1).to_string();
+        let name = vars.name(var);
+        name == number || name.strip_prefix('x') == Some(number.as_str())
+    });
+    if numeric {
+        return Ok(())
+    }
+    for var in vars.vars().filter(|&var| !vars.is_internal(var)) {
+        writeln!(out, "c var {} {}", var.index() + 1, vars.name(var))?;
+    }
+    Ok(())
 }
 
-impl PartialEq for Operator {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (&Operator::OR, &Operator::OR) => true,
-            (&Operator::AND, &Operator::AND) => true,
-            _ => false
+/*
+One line per clause of the core with its 1-based position in the input
+*/
+fn write_core<W: Write>(instance: &SatInstance, core: &[usize], out: &mut W) -> io::Result<()> {
+    writeln!(out, "c core of {} clauses", core.len())?;
+    for &index in core {
+        let clause = &instance.clauses[index];
+        match &clause.label {
+            Some(label) => writeln!(out, "c core {} {}: {}", index + 1, label, clause.display(&instance.vars))?,
+            None => writeln!(out, "c core {} {}", index + 1, clause.display(&instance.vars))?
         }
     }
+    Ok(())
 }
 
-
-#[derive(Debug, Clone)]
-struct Clause {
-    operator: Operator,
-    literals: Vec<Literal>
-}
-
-
-impl Clause {
-    fn satisfied_by(self: Self, state: &InstanceState) -> bool {
-        // Collect states for this clause
-        let clause_literal_states: Vec<Option<bool>> =
-            self.literals.into_iter().map(|clause_literal| {
-                let state: Option<LiteralState> = state.states.clone()
-                    .into_iter()
-                    .find(|state| {
-                        match state {
-                            LiteralState {
-                                literal: state_literal,
-                                value: _
-                            } => clause_literal.same_name_as(state_literal)
-                        }
-                    });
-                match state {
-                    Some(LiteralState {
-                        literal: _,
-                        value
-                    }) => {
-                        match (value, clause_literal.negated) {
-                            (Some(state_bool), true) => Some(!state_bool),
-                            (Some(state_bool), false) => Some(state_bool),
-                            (None, _) => None
-                        }
-                    },
-                    _ => None
-                }
-            }).collect();
-
-        // State has all required literals
-        let needed_literals_set = clause_literal_states.clone()
-            .into_iter()
-            .all(|v| { 
-                match v {
-                    Some(_) => true,
-                    _ => false
-                }
-            });
-        
-        if !needed_literals_set {
-            return false
-        }
-
-        match self.operator {
-            Operator::OR => {
-                clause_literal_states
-                    .into_iter()
-                    .any(|v| {
-                        match v {
-                            Some(true) => true,
-                            _ => false
-                        }
-                    })
-            },
-            Operator::AND => {
-                clause_literal_states
-                    .into_iter()
-                    .all(|v| {
-                        match v {
-                            Some(true) => true,
-                            _ => false
-                        }
-                    })
+/*
+Checks the proof in `path` and writes the trimmed proof, returns
+the exit status, 0 when the proof is valid
+*/
+fn check_proof<W: Write>(instance: &SatInstance, args: &Args, path: &str, lrat: bool, out: &mut W) -> io::Result<i32> {
+    let input = std::fs::read(path)?;
+    let parsed = if lrat {
+        proof::parse_lrat(&String::from_utf8_lossy(&input)).map(Proof::Lrat)
+    } else {
+        proof::parse_drat(&input).map(Proof::Drat)
+    };
+    let parsed = parsed.unwrap_or_else(|error| {
+        eprintln!("solver: {}:{}", path, error);
+        process::exit(1)
+    });
+
+    let check = instance.check_proof(&args.cnf, &parsed, args.trim.is_some());
+    if let (Some(trim), Some(steps)) = (&args.trim, &check.lrat) {
+        let mut file = BufWriter::new(File::create(trim)?);
+        proof::write_lrat(steps, &mut file)?;
+        file.flush()?;
+    }
+    match &check.verdict {
+        Verdict::Valid => {
+            writeln!(out, "c core of {} clauses and {} lemmas", check.core.len(), check.lemmas)?;
+            writeln!(out, "s VERIFIED")?;
+            return Ok(0)
+        },
+        Verdict::Incomplete => writeln!(out, "c the proof does not derive the empty clause")?,
+        Verdict::Failed { step, literals } => {
+            write!(out, "c step {} fails: ", step + 1)?;
+            for literal in literals {
+                let number = literal.var().index() + 1;
+                write!(out, "{}{} ", if literal.is_negated() { "-" } else { "" }, number)?;
             }
+            writeln!(out, "0")?;
         }
     }
+    writeln!(out, "s NOT VERIFIED")?;
+    Ok(1)
 }
 
-
-#[derive(Debug, Clone)]
-struct SatInstance {
-    clauses: Vec<Clause>
-}
-
-impl SatInstance {
-    fn inspect(self: Self) -> Vec<Literal> {
-        let mut literals = self.clauses
-            .into_iter()
-            .flat_map(|c| c.literals)
-            .collect::<Vec<Literal>>();
-        literals.sort();
-        literals.dedup_by(|a, b| a.inverse_of(b));
-        return literals
+/*
+Solves or exports the instance, or checks a proof of it, writing the
+results to `out`. Returns the exit status.
+*/
+fn run<W: Write>(instance: &SatInstance, formula: Option<&Formula>, args: &Args, start: Instant, out: &mut W) -> io::Result<i32> {
+    if args.export {
+        dimacs::write_with(instance, &args.cnf, out)?;
+        return Ok(0)
     }
-
-    fn satisfied_by(self: Self, state: &InstanceState) -> bool {
-        self.clauses.into_iter().all(|c| c.satisfied_by(&state))
+    if let Some((path, lrat)) = &args.check {
+        return check_proof(instance, args, path, *lrat, out)
     }
-}
 
-
-#[derive(Debug, Clone)]
-struct LiteralState {
-    literal: Literal,
-    value: Option<bool>
-}
-
-impl PartialEq for LiteralState {
-    fn eq(&self, other: &Self) -> bool {
-        self.literal == other.literal
-            && self.value == other.value
+    if args.verbosity > 0 {
+        let analysis = instance.analyze();
+        writeln!(out, "c variables: {}", instance.vars.len())?;
+        writeln!(out, "c unused variables: {}", analysis.unused.len())?;
+        writeln!(out, "c pure literals: {}", analysis.pure_literals.len())?;
+        writeln!(out, "c clauses: {}", instance.clauses.len())?;
+        if args.verbosity > 1 {
+            for (length, count) in &analysis.clause_lengths {
+                writeln!(out, "c clauses of length {}: {}", length, count)?;
+            }
+        }
+        writeln!(out, "c parse time: {:.3}s", start.elapsed().as_secs_f64())?;
     }
-}
 
-#[derive(Debug, Clone)]
-struct InstanceState {
-    states: Vec<LiteralState>
-}
+    let (result, core, stats) = if args.dpll {
+        (instance.solve_dpll_with(&args.options), None, Stats::default())
+    } else if args.core {
+        instance.solve_with_core(&args.options)
+    } else if let Some(path) = &args.proof {
+        let solved = File::create(path).and_then(|file| {
+            let proof = Box::new(BufWriter::new(file));
+            instance.solve_with_proof(&args.options, &args.cnf, proof, args.proof_format)
+        });
+        let (result, stats) = solved.unwrap_or_else(|error| {
+            eprintln!("solver: {}: {}", path, error);
+            process::exit(1)
+        });
+        (result, None, stats)
+    } else {
+        let (result, stats) = instance.solve_with(&args.options);
+        (result, None, stats)
+    };
 
+    if args.verbosity > 0 {
+        writeln!(out, "c decisions: {}", stats.decisions)?;
+        writeln!(out, "c propagations: {}", stats.propagations)?;
+        writeln!(out, "c conflicts: {}", stats.conflicts)?;
+        writeln!(out, "c restarts: {}", stats.restarts)?;
+        writeln!(out, "c learnt clauses: {}", stats.learnt_clauses)?;
+        writeln!(out, "c deleted clauses: {}", stats.deleted_clauses)?;
+        writeln!(out, "c total time: {:.3}s", start.elapsed().as_secs_f64())?;
+    }
 
-fn main() {
-    // (a or b) and (c or (not b)) -> true
-    // solution a = true, b = true/false, c = true
-    let instance = SatInstance {
-        clauses: vec![
-            Clause {
-                operator: Operator::OR,
-                literals: vec![
-                    Literal {
-                        name: String::from("a"),
-                        negated: false
-                    },
-                    Literal {
-                        name: String::from("b"),
-                        negated: false
-                    }
-                ]
-            },
-            Clause {
-                operator: Operator::AND,
-                literals: vec![
-                    Literal {
-                        name: String::from("c"),
-                        negated: false
-                    },
-                    Literal {
-                        name: String::from("b"),
-                        negated: true
-                    }
-                ]
+    let code = match result {
+        SolveResult::Satisfiable(state) => {
+            if args.verbosity > 1 {
+                let verified = match formula {
+                    Some(formula) => formula.evaluate(&state) == Truth::True,
+                    None => instance.satisfied_by(&state)
+                };
+                writeln!(out, "c model verified: {}", verified)?;
             }
-        ]
+            write_names(&instance.vars, out)?;
+            dimacs::write_model(&state, out)?;
+            10
+        },
+        SolveResult::Unsatisfiable => {
+            // The core of the solve call is only shrunk once it is known to exist
+            let core = if args.mus { instance.minimal_unsat_subset() } else { core };
+            if let Some(core) = core {
+                write_core(instance, &core, out)?;
+            }
+            writeln!(out, "s UNSATISFIABLE")?;
+            20
+        },
+        SolveResult::Unknown => {
+            writeln!(out, "s UNKNOWN")?;
+            0
+        }
     };
+    Ok(code)
+}
 
-    //println!("{:#?}", instance);
-
-    let literals = instance.clone().inspect();
-
-    //println!("{:#?}", literals);
-
-    let state = InstanceState {
-        states: vec![
-            LiteralState {
-                literal: literals[0].clone(),
-                value: Some(true)
-            },
-            LiteralState {
-                literal: literals[1].clone(),
-                value: Some(false)
-            },
-            LiteralState {
-                literal: literals[2].clone(),
-                value: Some(true)
-            },
-        ]
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args = parse_args(&args).unwrap_or_else(|error| {
+        if error.is_empty() {
+            // Help cut short by a closed pipe is no error either
+            let _ = io::stdout().write_all(USAGE.as_bytes());
+            process::exit(0)
+        }
+        eprintln!("solver: {}\n\n{}", error, USAGE);
+        process::exit(1)
+    });
+
+    let start = Instant::now();
+    let input = read_input(&args.input).unwrap_or_else(|error| {
+        eprintln!("solver: {}: {}", args.input, error);
+        process::exit(1)
+    });
+    // Expressions are kept to check models against, their instance has internal variables
+    let (instance, formula) = match args.format {
+        InputFormat::Dimacs => {
+            let instance = dimacs::parse(&input).unwrap_or_else(|error| {
+                eprintln!("solver: {}:{}", args.input, error);
+                process::exit(1)
+            });
+            (instance, None)
+        },
+        InputFormat::Opb => {
+            let instance = opb::parse(&input).unwrap_or_else(|error| {
+                eprintln!("solver: {}:{}", args.input, error);
+                process::exit(1)
+            });
+            (instance, None)
+        },
+        InputFormat::Expr => {
+            let mut vars = VarTable::new();
+            let formula = expr::parse(&input, &mut vars).unwrap_or_else(|error| {
+                eprintln!("solver: {}:{}\n{}", args.input, error, error.snippet(&input));
+                process::exit(1)
+            });
+            let instance = SatInstance::from_formula(&formula, vars, Encoding::PlaistedGreenbaum);
+            (instance, Some(formula))
+        }
     };
 
-    //println!("{:#?}", state);
-
-    println!("{:#?}", instance.satisfied_by(&state));
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let code = run(&instance, formula.as_ref(), &args, start, &mut out).and_then(|code| {
+        out.flush()?;
+        Ok(code)
+    });
+    match code {
+        Ok(code) => process::exit(code),
+        // The reader has gone, as in solver input.cnf | head -1
+        Err(error) if error.kind() == ErrorKind::BrokenPipe => process::exit(0),
+        Err(error) => {
+            eprintln!("solver: {}", error);
+            process::exit(1)
+        }
+    }
 }
@@ -0,0 +1,241 @@
+/*
+Minimal correction sets
+
+Removing the clauses of a correction set makes the instance satisfiable,
+a minimal one has no proper subset that does. Its complement is a
+maximal satisfiable subset S, which is grown from the clauses a model
+satisfies, U being the others:
+
+    LinearSearch   try the clauses of U one at a time with S
+    Cld            ask for a model of S that satisfies some clause of U,
+                   the clause D, until there is none
+
+and every model moves all the clauses of U it satisfies into S. At the
+end U is a minimal correction set. Clauses only hold while their
+selector is true (see `SatInstance::load`), so the clause "some
+selector of U is true" blocks U and its supersets from being found
+again, and enumeration ends once that leaves no model at all.
+*/
+use crate::formula::{Literal, SatInstance, Var};
+use crate::solver::cdcl::{Solver, Status};
+
+/// How `CorrectionSets` grows a satisfiable subset to a maximal one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum McsAlgorithm {
+    /// One solver call per clause outside the subset.
+    LinearSearch,
+    /// Clause D search, one call per model that satisfies more clauses.
+    #[default]
+    Cld
+}
+
+/// Iterator over the minimal correction sets of an instance as indices
+/// of `SatInstance::clauses`, see `SatInstance::correction_sets`.
+pub struct CorrectionSets<'a> {
+    instance: &'a SatInstance,
+    solver: Solver,
+    selectors: Vec<Literal>,
+    vars: usize,
+    algorithm: McsAlgorithm
+}
+
+impl SatInstance {
+    /// Minimal sets of clauses whose removal makes the instance
+    /// satisfiable, found one by one with the Cld algorithm. A satisfiable
+    /// instance has only the empty one, pseudo-Boolean constraints are
+    /// never removed and there are none when they are unsatisfiable.
+    ///
+    /// ```
+    /// use solver::SatInstance;
+    ///
+    /// let instance = SatInstance::builder()
+    ///     .or(&["a"])
+    ///     .or(&["!a"])
+    ///     .or(&["b"])
+    ///     .build();
+    /// let sets: Vec<Vec<usize>> = instance.correction_sets().collect();
+    /// assert_eq!(sets.len(), 2);
+    /// assert!(sets.contains(&vec![0]) && sets.contains(&vec![1]));
+    /// ```
+    pub fn correction_sets(&self) -> CorrectionSets<'_> {
+        self.correction_sets_with(McsAlgorithm::default())
+    }
+
+    /// Minimal correction sets found with `algorithm`.
+    pub fn correction_sets_with(&self, algorithm: McsAlgorithm) -> CorrectionSets<'_> {
+        let mut solver = Solver::new();
+        let (vars, selectors) = self.load(&mut solver, true);
+        CorrectionSets { instance: self, solver, selectors, vars, algorithm }
+    }
+}
+
+impl CorrectionSets<'_> {
+    /*
+    Marks the clauses that the last model satisfies
+    */
+    fn update(&self, satisfied: &mut [bool]) {
+        let model = self.solver.model();
+        for (satisfied, clause) in satisfied.iter_mut().zip(&self.instance.clauses) {
+            *satisfied |= clause.satisfied_by(model);
+        }
+    }
+
+    fn assumptions(&self, satisfied: &[bool]) -> Vec<Literal> {
+        (0..self.selectors.len())
+            .filter(|&clause| satisfied[clause])
+            .map(|clause| self.selectors[clause])
+            .collect()
+    }
+
+    fn linear_search(&mut self, satisfied: &mut [bool]) {
+        for clause in 0..satisfied.len() {
+            if satisfied[clause] {
+                continue;
+            }
+            let mut assumptions = self.assumptions(satisfied);
+            assumptions.push(self.selectors[clause]);
+            if self.solver.solve_with_assumptions(&assumptions) == Status::Satisfiable {
+                self.update(satisfied);
+            }
+        }
+    }
+
+    fn cld(&mut self, satisfied: &mut [bool]) {
+        loop {
+            let outside: Vec<usize> = (0..satisfied.len()).filter(|&clause| !satisfied[clause]).collect();
+            if outside.is_empty() {
+                return
+            }
+            // D only holds while a fresh activation literal is assumed
+            let activation = Var::new(self.vars).pos();
+            self.vars += 1;
+            let mut d = vec![!activation];
+            d.extend(outside.iter().map(|&clause| self.selectors[clause]));
+            self.solver.add_clause(&d);
+
+            let mut assumptions = self.assumptions(satisfied);
+            assumptions.push(activation);
+            let status = self.solver.solve_with_assumptions(&assumptions);
+            if status == Status::Satisfiable {
+                self.update(satisfied);
+            }
+            // Retracts D along with the learned clauses that needed it
+            self.solver.add_clause(&[!activation]);
+            self.solver.simplify();
+            if status != Status::Satisfiable {
+                return
+            }
+        }
+    }
+}
+
+impl Iterator for CorrectionSets<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        if self.solver.solve() != Status::Satisfiable {
+            return None
+        }
+        let mut satisfied = vec![false; self.instance.clauses.len()];
+        self.update(&mut satisfied);
+        match self.algorithm {
+            McsAlgorithm::LinearSearch => self.linear_search(&mut satisfied),
+            McsAlgorithm::Cld => self.cld(&mut satisfied)
+        }
+
+        let set: Vec<usize> = (0..satisfied.len()).filter(|&clause| !satisfied[clause]).collect();
+        let blocking: Vec<Literal> = set.iter().map(|&clause| self.selectors[clause]).collect();
+        self.solver.add_clause(&blocking);
+        Some(set)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assignment::InstanceState;
+    use crate::formula::{Clause, Operator};
+    use crate::pb::{Comparison, PbConstraint};
+
+    const VARS: usize = 5;
+
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn literals(&mut self, len: usize) -> Vec<Literal> {
+            (0..len).map(|_| Literal::new(Var::new(self.below(VARS)), self.below(2) == 1)).collect()
+        }
+
+        fn instance(&mut self, clauses: usize) -> SatInstance {
+            let mut instance = SatInstance::new();
+            for var in 0..VARS {
+                instance.var(&format!("x{}", var));
+            }
+            for _ in 0..clauses {
+                let operator = match self.below(6) {
+                    0 => Operator::XOR,
+                    1 => Operator::Exactly(1),
+                    2 => Operator::NAND,
+                    _ => Operator::OR
+                };
+                let length = 1 + self.below(3);
+                instance.add_clause(Clause::new(operator, self.literals(length)));
+            }
+            if self.below(3) == 0 {
+                let terms = self.literals(3).into_iter().map(|literal| (1 + self.below(2) as i64, literal)).collect();
+                instance.add_pb_constraint(PbConstraint::new(terms, Comparison::AtMost, 2)).unwrap();
+            }
+            instance
+        }
+    }
+
+    /*
+    Complements of the maximal sets of clauses that some assignment
+    satisfying the pseudo-Boolean constraints satisfies
+    */
+    fn brute_force(instance: &SatInstance) -> Vec<Vec<usize>> {
+        let mut satisfiable: Vec<Vec<bool>> = Vec::new();
+        for bits in 0..1u32 << VARS {
+            let values: Vec<bool> = (0..VARS).map(|var| bits >> var & 1 == 1).collect();
+            let state = InstanceState::from_values(&values);
+            if instance.pb_constraints.iter().all(|constraint| constraint.satisfied_by(&state)) {
+                satisfiable.push(instance.clauses.iter().map(|clause| clause.satisfied_by(&state)).collect());
+            }
+        }
+        let within = |a: &[bool], b: &[bool]| a.iter().zip(b).all(|(&a, &b)| !a || b);
+        let mut sets: Vec<Vec<usize>> = satisfiable
+            .iter()
+            .filter(|subset| !satisfiable.iter().any(|other| other != *subset && within(subset, other)))
+            .map(|subset| (0..subset.len()).filter(|&clause| !subset[clause]).collect())
+            .collect();
+        sets.sort();
+        sets.dedup();
+        sets
+    }
+
+    #[test]
+    fn algorithms_find_every_correction_set() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let mut several = 0;
+        for _ in 0..300 {
+            let clauses = 2 + random.below(10);
+            let instance = random.instance(clauses);
+            let expected = brute_force(&instance);
+            several += (expected.len() > 1) as usize;
+            for algorithm in [McsAlgorithm::LinearSearch, McsAlgorithm::Cld] {
+                let mut sets: Vec<Vec<usize>> = instance.correction_sets_with(algorithm).collect();
+                sets.sort();
+                assert_eq!(sets, expected, "{:?} {:?}", algorithm, instance);
+            }
+        }
+        assert!(several > 100, "{}", several);
+    }
+}
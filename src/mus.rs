@@ -0,0 +1,417 @@
+/*
+Minimal unsatisfiable subsets
+
+Clauses are removed in units, single clauses or the groups of clauses
+sharing a label. One solver holds every clause behind a selector (see
+`SatInstance::load`) and each check assumes the selectors of the units
+still in the working set, starting from an unsatisfiable core.
+
+    unsatisfiable without unit u   drop u, and every unit outside the
+                                   core of that call (refinement)
+    satisfiable without unit u     u is necessary
+
+The model of a satisfiable check falsifies u and nothing else of the
+working set. Flipping one variable of a falsified clause of u can give
+a model that falsifies exactly one other unit, which is then necessary
+as well without a solver call, and flipping goes on from there (model
+rotation). Once every unit of the working set is necessary, removing
+any of them makes it satisfiable.
+*/
+use std::collections::HashMap;
+
+use crate::assignment::Truth;
+use crate::formula::{Literal, SatInstance, Var};
+use crate::solver::cdcl::{Solver, Status};
+
+impl SatInstance {
+    /// Indices of a minimal unsatisfiable subset of `clauses`: together
+    /// with the pseudo-Boolean constraints, which always take part, the
+    /// clauses are unsatisfiable, but removing any one of them makes them
+    /// satisfiable. None when the instance is satisfiable.
+    ///
+    /// ```
+    /// use solver::SatInstance;
+    ///
+    /// let instance = SatInstance::builder()
+    ///     .or(&["a", "b"])
+    ///     .and(&["!a"])
+    ///     .or(&["!b", "c"])
+    ///     .and(&["!b"])
+    ///     .and(&["!c"])
+    ///     .build();
+    /// assert_eq!(instance.minimal_unsat_subset(), Some(vec![0, 1, 3]));
+    /// ```
+    pub fn minimal_unsat_subset(&self) -> Option<Vec<usize>> {
+        let units = (0..self.clauses.len()).map(|index| vec![index]).collect();
+        Extractor::new(self, units).run()
+    }
+
+    /// Like `minimal_unsat_subset`, with all clauses sharing a label kept
+    /// or removed together. Unlabelled clauses always take part and are
+    /// not listed, the result holds the indices of every clause of the
+    /// groups in the subset.
+    pub fn minimal_unsat_groups(&self) -> Option<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut labels: HashMap<&str, usize> = HashMap::new();
+        for (index, clause) in self.clauses.iter().enumerate() {
+            if let Some(label) = &clause.label {
+                let group = *labels.entry(label).or_insert(groups.len());
+                if group == groups.len() {
+                    groups.push(Vec::new());
+                }
+                groups[group].push(index);
+            }
+        }
+        Extractor::new(self, groups).run()
+    }
+}
+
+
+fn truth(values: &[bool]) -> impl Fn(Literal) -> Truth + '_ {
+    move |literal| Truth::from(Some(values[literal.var().index()] != literal.is_negated()))
+}
+
+/*
+Step of model rotation: the only false unit, the variable flipped
+to get there and the variables of the unit to flip next
+*/
+struct Frame {
+    unit: usize,
+    flipped: Option<usize>,
+    vars: Vec<usize>,
+    next: usize
+}
+
+struct Extractor<'a> {
+    instance: &'a SatInstance,
+    solver: Solver,
+    selectors: Vec<Literal>,
+    // Clause of every selector variable
+    selected: Vec<Option<usize>>,
+    units: Vec<Vec<usize>>,
+    // Unit of every clause, None for clauses that always take part
+    unit_of: Vec<Option<usize>>,
+    clause_occurrences: Vec<Vec<usize>>,
+    pb_occurrences: Vec<Vec<usize>>,
+    working: Vec<bool>,
+    necessary: Vec<bool>
+}
+
+impl<'a> Extractor<'a> {
+    fn new(instance: &'a SatInstance, units: Vec<Vec<usize>>) -> Extractor<'a> {
+        let mut solver = Solver::new();
+        let (vars, selectors) = instance.load(&mut solver, true);
+
+        let mut selected = vec![None; vars];
+        for (clause, selector) in selectors.iter().enumerate() {
+            selected[selector.var().index()] = Some(clause);
+        }
+        let mut unit_of = vec![None; instance.clauses.len()];
+        for (unit, clauses) in units.iter().enumerate() {
+            for &clause in clauses {
+                unit_of[clause] = Some(unit);
+            }
+        }
+        let mut clause_occurrences = vec![Vec::new(); vars];
+        for (index, clause) in instance.clauses.iter().enumerate() {
+            for literal in &clause.literals {
+                let occurrences: &mut Vec<usize> = &mut clause_occurrences[literal.var().index()];
+                if occurrences.last() != Some(&index) {
+                    occurrences.push(index);
+                }
+            }
+        }
+        let mut pb_occurrences = vec![Vec::new(); vars];
+        for (index, constraint) in instance.pb_constraints.iter().enumerate() {
+            for &(_, literal) in &constraint.terms {
+                let occurrences: &mut Vec<usize> = &mut pb_occurrences[literal.var().index()];
+                if occurrences.last() != Some(&index) {
+                    occurrences.push(index);
+                }
+            }
+        }
+
+        let count = units.len();
+        Extractor {
+            instance,
+            solver,
+            selectors,
+            selected,
+            units,
+            unit_of,
+            clause_occurrences,
+            pb_occurrences,
+            working: vec![true; count],
+            necessary: vec![false; count]
+        }
+    }
+
+    fn run(mut self) -> Option<Vec<usize>> {
+        if self.solve(None) == Status::Satisfiable {
+            return None
+        }
+        self.refine();
+        while let Some(unit) = (0..self.units.len()).find(|&u| self.working[u] && !self.necessary[u]) {
+            if self.solve(Some(unit)) == Status::Satisfiable {
+                self.necessary[unit] = true;
+                let model = self.solver.model();
+                let mut values: Vec<bool> = (0..self.clause_occurrences.len())
+                    .map(|index| model.value(Var::new(index)) == Some(true))
+                    .collect();
+                self.rotate(unit, &mut values);
+            } else {
+                self.working[unit] = false;
+                self.refine();
+            }
+        }
+
+        let mut clauses: Vec<usize> = (0..self.units.len())
+            .filter(|&unit| self.working[unit])
+            .flat_map(|unit| self.units[unit].iter().copied())
+            .collect();
+        clauses.sort_unstable();
+        Some(clauses)
+    }
+
+    /*
+    Solves with the clauses of the working set except those of `skip`
+    */
+    fn solve(&mut self, skip: Option<usize>) -> Status {
+        let assumptions: Vec<Literal> = (0..self.selectors.len())
+            .filter(|&clause| match self.unit_of[clause] {
+                Some(unit) => self.working[unit] && Some(unit) != skip,
+                None => true
+            })
+            .map(|clause| self.selectors[clause])
+            .collect();
+        self.solver.solve_with_assumptions(&assumptions)
+    }
+
+    /*
+    Keeps the units of the core of the last unsatisfiable call
+    */
+    fn refine(&mut self) {
+        let mut in_core = vec![false; self.units.len()];
+        for literal in self.solver.failed_assumptions() {
+            let clause = self.selected[literal.var().index()];
+            if let Some(unit) = clause.and_then(|clause| self.unit_of[clause]) {
+                in_core[unit] = true;
+            }
+        }
+        for (working, in_core) in self.working.iter_mut().zip(in_core) {
+            *working &= in_core;
+        }
+    }
+
+    fn falsified(&self, clause: usize, values: &[bool]) -> bool {
+        self.instance.clauses[clause].evaluate_with(truth(values)) == Truth::False
+    }
+
+    /*
+    Variables of the clauses of `unit` that `values` makes false
+    */
+    fn flip_candidates(&self, unit: usize, values: &[bool]) -> Vec<usize> {
+        let mut vars: Vec<usize> = self.units[unit]
+            .iter()
+            .filter(|&&clause| self.falsified(clause, values))
+            .flat_map(|&clause| self.instance.clauses[clause].literals.iter().map(|l| l.var().index()))
+            .collect();
+        vars.sort_unstable();
+        vars.dedup();
+        vars
+    }
+
+    /*
+    The one unit of the working set that `values` makes false after
+    flipping `var`, which made `unit` the only false one before
+    */
+    fn only_falsified(&self, unit: usize, var: usize, values: &[bool]) -> Option<usize> {
+        if self.units[unit].iter().any(|&clause| self.falsified(clause, values)) {
+            return None
+        }
+        let constraints = &self.instance.pb_constraints;
+        if self.pb_occurrences[var].iter().any(|&c| constraints[c].evaluate_with(truth(values)) == Truth::False) {
+            return None
+        }
+        let mut falsified = None;
+        for &clause in &self.clause_occurrences[var] {
+            let other = match self.unit_of[clause] {
+                Some(other) if !self.working[other] => continue,
+                other => other
+            };
+            if !self.falsified(clause, values) {
+                continue;
+            }
+            match (other, falsified) {
+                (None, _) => return None,
+                (Some(other), Some(found)) if other != found => return None,
+                (Some(other), _) => falsified = Some(other)
+            }
+        }
+        falsified
+    }
+
+    /*
+    Marks the units found necessary by recursive model rotation from
+    `unit`, restores `values` when done
+    */
+    fn rotate(&mut self, unit: usize, values: &mut [bool]) {
+        let vars = self.flip_candidates(unit, values);
+        let mut stack = vec![Frame { unit, flipped: None, vars, next: 0 }];
+        while let Some(frame) = stack.last_mut() {
+            if frame.next == frame.vars.len() {
+                if let Some(var) = frame.flipped {
+                    values[var] = !values[var];
+                }
+                stack.pop();
+                continue;
+            }
+            let (unit, var) = (frame.unit, frame.vars[frame.next]);
+            frame.next += 1;
+
+            values[var] = !values[var];
+            match self.only_falsified(unit, var, values) {
+                Some(other) if !self.necessary[other] => {
+                    self.necessary[other] = true;
+                    let vars = self.flip_candidates(other, values);
+                    stack.push(Frame { unit: other, flipped: Some(var), vars, next: 0 });
+                },
+                _ => values[var] = !values[var]
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assignment::InstanceState;
+    use crate::formula::{Clause, Operator};
+    use crate::pb::{Comparison, PbConstraint};
+
+    const VARS: usize = 6;
+
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn literals(&mut self, len: usize) -> Vec<Literal> {
+            (0..len).map(|_| Literal::new(Var::new(self.below(VARS)), self.below(2) == 1)).collect()
+        }
+
+        /*
+        Mostly short disjunctions, with some clauses the solver handles
+        natively or lowers and now and then a pseudo-Boolean constraint
+        */
+        fn instance(&mut self, clauses: usize) -> SatInstance {
+            let mut instance = SatInstance::new();
+            for var in 0..VARS {
+                instance.var(&format!("x{}", var));
+            }
+            for _ in 0..clauses {
+                let operator = match self.below(8) {
+                    0 => Operator::XOR,
+                    1 => Operator::AtMost(1),
+                    2 => Operator::IMPLIES,
+                    _ => Operator::OR
+                };
+                let length = 1 + self.below(3);
+                instance.add_clause(Clause::new(operator, self.literals(length)));
+            }
+            if self.below(2) == 0 {
+                let terms = self.literals(4).into_iter().map(|literal| (1 + self.below(3) as i64, literal)).collect();
+                instance.add_pb_constraint(PbConstraint::new(terms, Comparison::AtLeast, 3)).unwrap();
+            }
+            instance
+        }
+    }
+
+    fn satisfiable(instance: &SatInstance, clauses: &[usize]) -> bool {
+        (0..1u32 << VARS).any(|bits| {
+            let values: Vec<bool> = (0..VARS).map(|var| bits >> var & 1 == 1).collect();
+            let state = InstanceState::from_values(&values);
+            clauses.iter().all(|&clause| instance.clauses[clause].satisfied_by(&state))
+                && instance.pb_constraints.iter().all(|constraint| constraint.satisfied_by(&state))
+        })
+    }
+
+    fn without(clauses: &[usize], removed: &[usize]) -> Vec<usize> {
+        clauses.iter().copied().filter(|clause| !removed.contains(clause)).collect()
+    }
+
+    #[test]
+    fn subsets_are_minimal() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let mut unsatisfiable = 0;
+        for _ in 0..300 {
+            let clauses = 4 + random.below(16);
+            let instance = random.instance(clauses);
+            let all: Vec<usize> = (0..instance.clauses.len()).collect();
+            let subset = match instance.minimal_unsat_subset() {
+                Some(subset) => subset,
+                None => {
+                    assert!(satisfiable(&instance, &all), "{:?}", instance);
+                    continue;
+                }
+            };
+            unsatisfiable += 1;
+            assert!(!satisfiable(&instance, &subset), "{:?} {:?}", instance, subset);
+            for &clause in &subset {
+                assert!(satisfiable(&instance, &without(&subset, &[clause])), "{:?} {:?} {}", instance, subset, clause);
+            }
+        }
+        assert!(unsatisfiable > 50, "{}", unsatisfiable);
+    }
+
+    #[test]
+    fn groups_are_minimal() {
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        let mut unsatisfiable = 0;
+        for _ in 0..300 {
+            let clauses = 4 + random.below(16);
+            let mut instance = random.instance(clauses);
+            for clause in &mut instance.clauses {
+                if random.below(4) != 0 {
+                    clause.label = Some(format!("g{}", random.below(4)));
+                }
+            }
+            let group = |label: &Option<String>| -> Vec<usize> {
+                (0..instance.clauses.len()).filter(|&clause| &instance.clauses[clause].label == label).collect()
+            };
+            let always = group(&None);
+            let all: Vec<usize> = (0..instance.clauses.len()).collect();
+            let subset = match instance.minimal_unsat_groups() {
+                Some(subset) => subset,
+                None => {
+                    assert!(satisfiable(&instance, &all), "{:?}", instance);
+                    continue;
+                }
+            };
+            unsatisfiable += 1;
+
+            // Whole groups, unlabelled clauses left out but taking part
+            let mut labels: Vec<&Option<String>> = subset.iter().map(|&clause| &instance.clauses[clause].label).collect();
+            labels.sort();
+            labels.dedup();
+            assert!(labels.iter().all(|label| label.is_some()));
+            let mut grouped: Vec<usize> = labels.iter().flat_map(|label| group(label)).collect();
+            grouped.sort_unstable();
+            assert_eq!(grouped, subset);
+
+            let taking_part: Vec<usize> = always.iter().chain(&subset).copied().collect();
+            assert!(!satisfiable(&instance, &taking_part), "{:?} {:?}", instance, subset);
+            for label in labels {
+                let rest = without(&taking_part, &group(label));
+                assert!(satisfiable(&instance, &rest), "{:?} {:?} {:?}", instance, subset, label);
+            }
+        }
+        assert!(unsatisfiable > 50, "{}", unsatisfiable);
+    }
+}
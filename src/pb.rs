@@ -0,0 +1,240 @@
+/*
+Pseudo-Boolean linear constraints
+
+    3 a + 2 b - c >= 2
+
+A literal counts as 1 when true and 0 when false. Every constraint
+normalizes to "sum of positive weights times literals >= bound":
+a term with a negative coefficient -w becomes w times the negated
+literal with w added to the bound, since -w l = w !l - w, and <=
+is >= with all signs flipped.
+
+Normal forms keep their bound and the sum of their weights within
+i64, constraints that would leave it cannot be normalized and are
+rejected by `SatInstance::add_pb_constraint`.
+*/
+use std::error::Error;
+use std::fmt;
+
+use crate::assignment::{InstanceState, Truth};
+use crate::formula::Literal;
+
+/// Relation between the weighted sum and the bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    AtLeast,
+    AtMost,
+    Equal
+}
+
+/// Linear constraint `sum of coefficient * literal <comparison> bound`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PbConstraint {
+    pub terms: Vec<(i64, Literal)>,
+    pub comparison: Comparison,
+    pub bound: i64
+}
+
+/// Constraint `sum of weight * literal >= bound` with positive weights.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Normalized {
+    pub terms: Vec<(u64, Literal)>,
+    pub bound: i64
+}
+
+/// A constraint whose normal form does not fit in i64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overflow;
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "normalized constraint does not fit in 64-bit integers")
+    }
+}
+
+impl Error for Overflow {}
+
+impl Normalized {
+    fn new(terms: &[(i64, Literal)], bound: i64) -> Result<Normalized, Overflow> {
+        let mut normalized = Normalized { terms: Vec::with_capacity(terms.len()), bound };
+        let mut total: i64 = 0;
+        for &(coefficient, literal) in terms {
+            if coefficient < 0 {
+                normalized.terms.push((coefficient.unsigned_abs(), !literal));
+                normalized.bound = normalized.bound.checked_sub(coefficient).ok_or(Overflow)?;
+            } else if coefficient > 0 {
+                normalized.terms.push((coefficient as u64, literal));
+            }
+            total = total.checked_add(coefficient.checked_abs().ok_or(Overflow)?).ok_or(Overflow)?;
+        }
+        Ok(normalized)
+    }
+
+    /// Sum of all weights.
+    pub fn total(&self) -> u64 {
+        self.terms.iter().map(|&(weight, _)| weight).sum()
+    }
+
+    /// Kleene evaluation from the least and greatest sum `state` allows.
+    pub fn evaluate(&self, state: &InstanceState) -> Truth {
+        self.evaluate_with(&|literal| state.literal_truth(literal))
+    }
+
+    fn evaluate_with<F: Fn(Literal) -> Truth>(&self, truth: &F) -> Truth {
+        let (mut least, mut greatest) = (0, 0);
+        for &(weight, literal) in &self.terms {
+            match truth(literal) {
+                Truth::True => {
+                    least += weight;
+                    greatest += weight;
+                },
+                Truth::False => {},
+                Truth::Unknown => greatest += weight
+            }
+        }
+        if least as i64 >= self.bound {
+            Truth::True
+        } else if (greatest as i64) < self.bound {
+            Truth::False
+        } else {
+            Truth::Unknown
+        }
+    }
+}
+
+impl PbConstraint {
+    pub fn new(terms: Vec<(i64, Literal)>, comparison: Comparison, bound: i64) -> PbConstraint {
+        PbConstraint { terms, comparison, bound }
+    }
+
+    /// Equivalent constraints in normal form, two for Equal.
+    /// Fails when a bound or the sum of the weights leaves i64.
+    pub fn normalize(&self) -> Result<Vec<Normalized>, Overflow> {
+        let negated = || -> Result<Normalized, Overflow> {
+            let terms = self.terms
+                .iter()
+                .map(|&(coefficient, literal)| Some((coefficient.checked_neg()?, literal)))
+                .collect::<Option<Vec<(i64, Literal)>>>()
+                .ok_or(Overflow)?;
+            Normalized::new(&terms, self.bound.checked_neg().ok_or(Overflow)?)
+        };
+        match self.comparison {
+            Comparison::AtLeast => Ok(vec![Normalized::new(&self.terms, self.bound)?]),
+            Comparison::AtMost => Ok(vec![negated()?]),
+            Comparison::Equal => Ok(vec![Normalized::new(&self.terms, self.bound)?, negated()?])
+        }
+    }
+
+    /*
+    Normal form of a constraint of an instance, which
+    `SatInstance::add_pb_constraint` checked
+    */
+    pub(crate) fn normalized(&self) -> Vec<Normalized> {
+        self.normalize().expect("constraints are checked by SatInstance::add_pb_constraint")
+    }
+
+    /// Kleene evaluation under a possibly partial assignment.
+    pub fn evaluate(&self, state: &InstanceState) -> Truth {
+        self.evaluate_with(|literal| state.literal_truth(literal))
+    }
+
+    /*
+    Sums in i128 from the terms as given, so constraints that cannot be
+    normalized are evaluated as well
+    */
+    pub(crate) fn evaluate_with<F: Fn(Literal) -> Truth>(&self, truth: F) -> Truth {
+        let (mut least, mut greatest) = (0i128, 0i128);
+        for &(coefficient, literal) in &self.terms {
+            let coefficient = coefficient as i128;
+            match truth(literal) {
+                Truth::True => {
+                    least += coefficient;
+                    greatest += coefficient;
+                },
+                Truth::False => {},
+                Truth::Unknown => {
+                    least += coefficient.min(0);
+                    greatest += coefficient.max(0);
+                }
+            }
+        }
+        let bound = self.bound as i128;
+        let (holds, fails) = match self.comparison {
+            Comparison::AtLeast => (least >= bound, greatest < bound),
+            Comparison::AtMost => (greatest <= bound, least > bound),
+            Comparison::Equal => (least == bound && greatest == bound, least > bound || greatest < bound)
+        };
+        if holds {
+            Truth::True
+        } else if fails {
+            Truth::False
+        } else {
+            Truth::Unknown
+        }
+    }
+
+    pub fn satisfied_by(&self, state: &InstanceState) -> bool {
+        self.evaluate(state) == Truth::True
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::{SatInstance, Var};
+    use crate::solver::{IncrementalSolver, SolveResult};
+
+    #[test]
+    fn overflowing_normal_forms_are_rejected() {
+        let (x, y) = (Var::new(0).pos(), Var::new(1).pos());
+        let overflowing = [
+            PbConstraint::new(vec![(i64::MIN, x)], Comparison::AtMost, 0),
+            PbConstraint::new(vec![(1, x)], Comparison::AtMost, i64::MIN),
+            PbConstraint::new(vec![(-1, x)], Comparison::AtLeast, i64::MAX),
+            PbConstraint::new(vec![(1, x)], Comparison::Equal, i64::MIN),
+            PbConstraint::new(vec![(i64::MAX, x), (1, y)], Comparison::AtLeast, 1),
+            PbConstraint::new(vec![(i64::MIN, x)], Comparison::AtLeast, 0)
+        ];
+        for constraint in overflowing {
+            assert_eq!(constraint.normalize(), Err(Overflow), "{:?}", constraint);
+            let mut instance = SatInstance::new();
+            assert_eq!(instance.add_pb_constraint(constraint), Err(Overflow));
+            assert!(instance.pb_constraints.is_empty());
+        }
+
+        let largest = PbConstraint::new(vec![(-i64::MAX, x)], Comparison::AtMost, -i64::MAX);
+        let normalized = Normalized { terms: vec![(i64::MAX as u64, x)], bound: i64::MAX };
+        assert_eq!(largest.normalize(), Ok(vec![normalized]));
+    }
+
+    #[test]
+    fn overflowing_constraints_are_evaluated() {
+        let (x, y) = (Var::new(0), Var::new(1));
+        let constraint = PbConstraint::new(vec![(i64::MAX, x.pos()), (i64::MAX, y.pos())], Comparison::AtLeast, i64::MAX);
+        let state = |values: &[bool]| InstanceState::from_values(values);
+        assert_eq!(constraint.evaluate(&state(&[true, false])), Truth::True);
+        assert_eq!(constraint.evaluate(&state(&[false, false])), Truth::False);
+        assert_eq!(constraint.evaluate(&InstanceState::new(2)), Truth::Unknown);
+
+        let constraint = PbConstraint::new(vec![(i64::MIN, x.pos())], Comparison::Equal, i64::MIN);
+        assert_eq!(constraint.evaluate(&state(&[true])), Truth::True);
+        assert_eq!(constraint.evaluate(&state(&[false])), Truth::False);
+    }
+
+    #[test]
+    fn largest_weights_are_solved_in_scopes() {
+        let mut solver = IncrementalSolver::new();
+        let (x, y) = (solver.var("x"), solver.var("y"));
+        solver.push();
+        let constraint = PbConstraint::new(vec![(i64::MAX - 1, x.pos()), (1, y.neg())], Comparison::AtLeast, i64::MAX);
+        assert_eq!(solver.add_pb_constraint(&constraint), Ok(true));
+        match solver.solve() {
+            SolveResult::Satisfiable(model) => assert_eq!((model.value(x), model.value(y)), (Some(true), Some(false))),
+            result => panic!("{:?}", result)
+        }
+        let overflowing = PbConstraint::new(vec![(i64::MAX, x.pos()), (1, y.pos())], Comparison::AtLeast, 1);
+        assert_eq!(solver.add_pb_constraint(&overflowing), Err(Overflow));
+        assert!(solver.pop());
+    }
+}
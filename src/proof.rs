@@ -0,0 +1,865 @@
+/*
+Checking DRAT and LRAT proofs of unsatisfiability
+
+A proof adds lemmas to the CNF of the instance and deletes clauses,
+until it adds the empty clause. A lemma C holds when it is
+
+    RUP   assigning the negation of C makes unit propagation over the
+          clauses present conflict
+    RAT   for every clause D with the negation of the first literal p
+          of C, the resolvent C | D \ {!p} is RUP
+
+DRAT proofs are checked backwards: the clauses are first added and
+deleted up to the empty clause, then the lemmas are removed again from
+last to first and only those that the check of a later lemma used are
+checked (core-first). Unit propagation goes through the clauses already
+used before any others, so the checks keep reusing the same clauses.
+Each check starts from no assignment, which lets the clauses keep their
+two watched literals across checks while being removed and added back.
+
+LRAT proofs give the unit clauses of each check as hints, numbered as
+the clauses of the CNF are from 1 and lemmas by their own ids, and are
+checked forwards, RAT steps listing every clause D as its negated id
+followed by the hints of that resolvent.
+
+Either check can give a trimmed LRAT proof of the lemmas used, with
+each clause deleted after its last use.
+*/
+use std::collections::HashMap;
+
+use crate::cnf::{Cnf, CnfOptions};
+use crate::formula::{Literal, SatInstance, Var};
+
+/// Step of a DRAT proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DratStep {
+    Add(Vec<Literal>),
+    Delete(Vec<Literal>)
+}
+
+/// Step of an LRAT proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LratStep {
+    /// Lemma with its id and hints, negative hints start the hints
+    /// of the resolvent with that clause.
+    Add { id: u64, literals: Vec<Literal>, hints: Vec<i64> },
+    /// Deletes the clauses with these ids.
+    Delete(Vec<u64>)
+}
+
+/// Proof of unsatisfiability.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Proof {
+    Drat(Vec<DratStep>),
+    Lrat(Vec<LratStep>)
+}
+
+/// Outcome of a proof check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Valid,
+    /// The proof never derives the empty clause.
+    Incomplete,
+    /// The earliest lemma that does not hold, at index `step`
+    /// of the proof.
+    Failed { step: usize, literals: Vec<Literal> }
+}
+
+/// Verdict of a proof check with what the refutation used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofCheck {
+    pub verdict: Verdict,
+    /// Indices of the clauses of the CNF used, empty unless valid.
+    pub core: Vec<usize>,
+    /// Number of lemmas used.
+    pub lemmas: usize,
+    /// Trimmed proof when asked for and valid.
+    pub lrat: Option<Vec<LratStep>>
+}
+
+impl ProofCheck {
+    pub fn is_valid(&self) -> bool {
+        self.verdict == Verdict::Valid
+    }
+}
+
+impl SatInstance {
+    /// Checks that `proof` refutes the CNF of the instance under
+    /// `options`, numbered as `io::dimacs::write_with` writes it.
+    /// With `trim`, a valid proof also comes with a trimmed LRAT proof.
+    ///
+    /// ```
+    /// use solver::proof::{DratStep, Proof};
+    /// use solver::{CnfOptions, Literal, SatInstance};
+    ///
+    /// let instance = SatInstance::builder()
+    ///     .or(&["a", "b"])
+    ///     .or(&["a", "!b"])
+    ///     .or(&["!a", "b"])
+    ///     .or(&["!a", "!b"])
+    ///     .build();
+    /// let a = instance.vars.lookup("a").unwrap();
+    /// let proof = Proof::Drat(vec![DratStep::Add(vec![Literal::pos(a)]), DratStep::Add(vec![])]);
+    /// let check = instance.check_proof(&CnfOptions::default(), &proof, false);
+    /// assert!(check.is_valid());
+    /// assert_eq!(check.core, vec![0, 1, 2, 3]);
+    /// ```
+    pub fn check_proof(&self, options: &CnfOptions, proof: &Proof, trim: bool) -> ProofCheck {
+        check(&self.to_cnf_with(options), proof, trim)
+    }
+}
+
+/// Checks that `proof` refutes `cnf`, see `SatInstance::check_proof`.
+pub fn check(cnf: &Cnf, proof: &Proof, trim: bool) -> ProofCheck {
+    let (verdict, clauses, derivations) = match proof {
+        Proof::Drat(steps) => Checker::new(cnf).check_drat(steps),
+        Proof::Lrat(steps) => check_lrat(cnf, steps)
+    };
+    if verdict != Verdict::Valid {
+        return ProofCheck { verdict, core: Vec::new(), lemmas: 0, lrat: None }
+    }
+
+    let needed = needed(clauses, &derivations);
+    let core = (0..cnf.clauses.len()).filter(|&clause| needed[clause]).collect();
+    let lemmas = derivations.iter().filter(|d| needed[d.clause]).count();
+    let lrat = if trim { Some(trimmed(cnf.clauses.len(), &needed, &derivations)) } else { None };
+    ProofCheck { verdict, core, lemmas, lrat }
+}
+
+
+/*
+Hints of a checked lemma as indices of the clause table: the unit
+clauses of its RUP check, or failing that the clauses D of the RAT
+check with the unit clauses of each resolvent
+*/
+#[derive(Debug, Clone, Default)]
+struct Derivation {
+    clause: usize,
+    literals: Vec<Literal>,
+    rup: Vec<usize>,
+    rat: Vec<(usize, Vec<usize>)>
+}
+
+/*
+Marks the clauses the last derivation, the empty clause, depends on.
+The clauses D of RAT checks only matter when needed anyway.
+*/
+fn needed(clauses: usize, derivations: &[Derivation]) -> Vec<bool> {
+    let mut needed = vec![false; clauses];
+    if let Some(last) = derivations.last() {
+        needed[last.clause] = true;
+    }
+    for derivation in derivations.iter().rev() {
+        if !needed[derivation.clause] {
+            continue;
+        }
+        for &clause in derivation.rup.iter().chain(derivation.rat.iter().flat_map(|(_, hints)| hints)) {
+            needed[clause] = true;
+        }
+    }
+    needed
+}
+
+/*
+LRAT proof of the needed derivations, the clauses of the CNF keep
+their numbers and lemmas are numbered after them in order. Clauses are
+deleted after their last use, those of the CNF never used first.
+*/
+fn trimmed(originals: usize, needed: &[bool], derivations: &[Derivation]) -> Vec<LratStep> {
+    let derivations: Vec<&Derivation> = derivations.iter().filter(|d| needed[d.clause]).collect();
+    let mut ids: HashMap<usize, u64> = (0..originals).map(|clause| (clause, clause as u64 + 1)).collect();
+    let mut last_use = vec![None; needed.len()];
+    for (position, derivation) in derivations.iter().enumerate() {
+        let rat = derivation.rat.iter().filter(|(clause, _)| needed[*clause]);
+        let used = derivation.rup.iter().chain(rat.flat_map(|(clause, hints)| std::iter::once(clause).chain(hints)));
+        for &clause in used {
+            last_use[clause] = Some(position);
+        }
+    }
+
+    let mut steps = Vec::new();
+    let unused: Vec<u64> = (0..originals).filter(|&c| last_use[c].is_none()).map(|c| c as u64 + 1).collect();
+    if !unused.is_empty() {
+        steps.push(LratStep::Delete(unused));
+    }
+    let mut deleted_after: Vec<Vec<usize>> = vec![Vec::new(); derivations.len()];
+    for (clause, position) in last_use.iter().enumerate() {
+        if let Some(position) = *position {
+            deleted_after[position].push(clause);
+        }
+    }
+    for (position, derivation) in derivations.iter().enumerate() {
+        let id = (originals + position) as u64 + 1;
+        ids.insert(derivation.clause, id);
+        let mut hints: Vec<i64> = derivation.rup.iter().map(|clause| ids[clause] as i64).collect();
+        for (clause, units) in derivation.rat.iter().filter(|(clause, _)| needed[*clause]) {
+            hints.push(-(ids[clause] as i64));
+            hints.extend(units.iter().map(|unit| ids[unit] as i64));
+        }
+        steps.push(LratStep::Add { id, literals: derivation.literals.clone(), hints });
+        if position + 1 < derivations.len() && !deleted_after[position].is_empty() {
+            steps.push(LratStep::Delete(deleted_after[position].iter().map(|clause| ids[clause]).collect()));
+        }
+    }
+    steps
+}
+
+/*
+Literals without repetitions, in their order
+*/
+fn distinct(literals: &[Literal]) -> Vec<Literal> {
+    let mut distinct: Vec<Literal> = Vec::with_capacity(literals.len());
+    for &literal in literals {
+        if !distinct.contains(&literal) {
+            distinct.push(literal);
+        }
+    }
+    distinct
+}
+
+fn key(literals: &[Literal]) -> Vec<Literal> {
+    let mut key = distinct(literals);
+    key.sort_unstable();
+    key
+}
+
+fn is_tautology(literals: &[Literal]) -> bool {
+    let key = key(literals);
+    key.windows(2).any(|pair| pair[0].inverse_of(pair[1]))
+}
+
+#[derive(Debug, Clone)]
+struct ClauseData {
+    literals: Vec<Literal>,
+    active: bool,
+    core: bool
+}
+
+/*
+Clauses of a DRAT check with watched literals, the first two literals
+of clauses of two or more
+*/
+struct Checker {
+    clauses: Vec<ClauseData>,
+    watches: Vec<Vec<usize>>,
+    units: Vec<usize>,
+    empty: Vec<usize>,
+    values: Vec<Option<bool>>,
+    reasons: Vec<Option<usize>>,
+    positions: Vec<usize>,
+    trail: Vec<Literal>
+}
+
+impl Checker {
+    fn new(cnf: &Cnf) -> Checker {
+        let mut checker = Checker {
+            clauses: Vec::new(),
+            watches: Vec::new(),
+            units: Vec::new(),
+            empty: Vec::new(),
+            values: Vec::new(),
+            reasons: Vec::new(),
+            positions: Vec::new(),
+            trail: Vec::new()
+        };
+        checker.ensure_vars(cnf.vars);
+        for clause in &cnf.clauses {
+            checker.insert(clause);
+        }
+        checker
+    }
+
+    fn ensure_vars(&mut self, vars: usize) {
+        if self.values.len() < vars {
+            self.values.resize(vars, None);
+            self.reasons.resize(vars, None);
+            self.positions.resize(vars, 0);
+            self.watches.resize(2 * vars, Vec::new());
+        }
+    }
+
+    fn insert(&mut self, literals: &[Literal]) -> usize {
+        let literals = distinct(literals);
+        if let Some(max_var) = literals.iter().map(|l| l.var().index()).max() {
+            self.ensure_vars(max_var + 1);
+        }
+        let clause = self.clauses.len();
+        match literals.len() {
+            0 => self.empty.push(clause),
+            1 => self.units.push(clause),
+            _ => {
+                self.watches[literals[0].code()].push(clause);
+                self.watches[literals[1].code()].push(clause);
+            }
+        }
+        self.clauses.push(ClauseData { literals, active: true, core: false });
+        clause
+    }
+
+    fn value(&self, literal: Literal) -> Option<bool> {
+        self.values[literal.var().index()].map(|value| value != literal.is_negated())
+    }
+
+    fn assign(&mut self, literal: Literal, reason: Option<usize>) {
+        let var = literal.var().index();
+        self.values[var] = Some(!literal.is_negated());
+        self.reasons[var] = reason;
+        self.positions[var] = self.trail.len();
+        self.trail.push(literal);
+    }
+
+    fn reset(&mut self) {
+        for literal in self.trail.drain(..) {
+            self.values[literal.var().index()] = None;
+        }
+    }
+
+    /*
+    Propagates `literal` through the active clauses watching its
+    negation that are core or not, returns a conflicting clause
+    */
+    fn propagate_literal(&mut self, literal: Literal, core: bool) -> Option<usize> {
+        let false_literal = !literal;
+        let mut watchers = std::mem::take(&mut self.watches[false_literal.code()]);
+        let mut kept = 0;
+        let mut conflict = None;
+        let mut index = 0;
+        while index < watchers.len() {
+            let clause = watchers[index];
+            index += 1;
+            watchers[kept] = clause;
+            kept += 1;
+            let data = &self.clauses[clause];
+            if !data.active || data.core != core || conflict.is_some() {
+                continue;
+            }
+
+            let data = &mut self.clauses[clause];
+            if data.literals[0] == false_literal {
+                data.literals.swap(0, 1);
+            }
+            let first = data.literals[0];
+            if self.values[first.var().index()].map(|v| v != first.is_negated()) == Some(true) {
+                continue;
+            }
+            let values = &self.values;
+            let replacement = (2..data.literals.len()).find(|&k| {
+                let other = data.literals[k];
+                values[other.var().index()].map(|v| v != other.is_negated()) != Some(false)
+            });
+            match replacement {
+                Some(k) => {
+                    data.literals.swap(1, k);
+                    let watch = data.literals[1];
+                    self.watches[watch.code()].push(clause);
+                    kept -= 1;
+                },
+                None if self.value(first) == Some(false) => conflict = Some(clause),
+                None => self.assign(first, Some(clause))
+            }
+        }
+        watchers.truncate(kept);
+        self.watches[false_literal.code()] = watchers;
+        conflict
+    }
+
+    /*
+    Propagates the trail from `start`, through the core clauses first
+    and through the others one literal at a time
+    */
+    fn propagate(&mut self, start: usize) -> Option<usize> {
+        let (mut core_head, mut head) = (start, start);
+        loop {
+            while core_head < self.trail.len() {
+                let literal = self.trail[core_head];
+                core_head += 1;
+                if let Some(conflict) = self.propagate_literal(literal, true) {
+                    return Some(conflict)
+                }
+            }
+            if head == self.trail.len() {
+                return None
+            }
+            let literal = self.trail[head];
+            head += 1;
+            if let Some(conflict) = self.propagate_literal(literal, false) {
+                return Some(conflict)
+            }
+        }
+    }
+
+    /*
+    Unit clauses in propagation order ending with the conflicting
+    clause, None when the literals all false give no conflict
+    */
+    fn rup(&mut self, falsified: &[Literal]) -> Option<Vec<usize>> {
+        let chain = self.conflict(falsified).map(|conflict| self.chain(conflict));
+        self.reset();
+        chain
+    }
+
+    fn conflict(&mut self, falsified: &[Literal]) -> Option<usize> {
+        if let Some(&clause) = self.empty.iter().find(|&&c| self.clauses[c].active) {
+            return Some(clause)
+        }
+        for &literal in falsified {
+            match self.value(literal) {
+                Some(true) => return Some(usize::MAX),
+                Some(false) => {},
+                None => self.assign(!literal, None)
+            }
+        }
+        let units: Vec<usize> = self.units.iter().copied().filter(|&c| self.clauses[c].active).collect();
+        for core in [true, false] {
+            for &clause in &units {
+                if self.clauses[clause].core != core {
+                    continue;
+                }
+                let literal = self.clauses[clause].literals[0];
+                match self.value(literal) {
+                    Some(true) => {},
+                    Some(false) => return Some(clause),
+                    None => self.assign(literal, Some(clause))
+                }
+            }
+        }
+        self.propagate(0)
+    }
+
+    /*
+    Marks the reasons of the conflict as core, usize::MAX being a
+    tautology that needs none
+    */
+    fn chain(&mut self, conflict: usize) -> Vec<usize> {
+        if conflict == usize::MAX {
+            return Vec::new()
+        }
+        let mut used = vec![conflict];
+        let mut seen = vec![false; self.values.len()];
+        let mut index = 0;
+        while index < used.len() {
+            let clause = used[index];
+            index += 1;
+            for literal in &self.clauses[clause].literals {
+                let var = literal.var().index();
+                if seen[var] || self.values[var].is_none() {
+                    continue;
+                }
+                seen[var] = true;
+                if let Some(reason) = self.reasons[var] {
+                    if reason != clause {
+                        used.push(reason);
+                    }
+                }
+            }
+        }
+        let mut reasons = used.split_off(1);
+        reasons.sort_by_key(|&clause| self.positions[self.clauses[clause].literals[0].var().index()]);
+        reasons.push(conflict);
+        for &clause in &reasons {
+            self.clauses[clause].core = true;
+        }
+        reasons
+    }
+}
+
+impl Checker {
+    /*
+    Adds and deletes clauses up to the empty clause, or adds it after
+    the last step, then checks the lemmas used from last to first
+    */
+    fn check_drat(mut self, steps: &[DratStep]) -> (Verdict, usize, Vec<Derivation>) {
+        let mut present: HashMap<Vec<Literal>, Vec<usize>> = HashMap::new();
+        for (clause, data) in self.clauses.iter().enumerate() {
+            present.entry(key(&data.literals)).or_default().push(clause);
+        }
+        // Clause each step adds or deletes
+        let mut changes: Vec<Option<usize>> = Vec::with_capacity(steps.len() + 1);
+        for step in steps {
+            match step {
+                DratStep::Add(literals) => {
+                    let clause = self.insert(literals);
+                    present.entry(key(literals)).or_default().push(clause);
+                    changes.push(Some(clause));
+                    if literals.is_empty() {
+                        break;
+                    }
+                },
+                DratStep::Delete(literals) => {
+                    let clause = present.get_mut(&key(literals)).and_then(Vec::pop);
+                    if let Some(clause) = clause {
+                        self.clauses[clause].active = false;
+                    }
+                    changes.push(clause);
+                }
+            }
+        }
+        if changes.len() == steps.len() && !matches!(steps.last(), Some(DratStep::Add(literals)) if literals.is_empty()) {
+            changes.push(Some(self.insert(&[])));
+        }
+
+        let last = changes.len() - 1;
+        if let Some(clause) = changes[last] {
+            self.clauses[clause].core = true;
+        }
+        let mut derivations = Vec::new();
+        let mut failed = None;
+        for (step, &clause) in changes.iter().enumerate().rev() {
+            let (clause, literals) = match (clause, steps.get(step)) {
+                (Some(clause), Some(DratStep::Add(literals))) => (clause, distinct(literals)),
+                (Some(clause), None) => (clause, Vec::new()),
+                (Some(clause), Some(DratStep::Delete(_))) => {
+                    self.clauses[clause].active = true;
+                    continue;
+                },
+                (None, _) => continue
+            };
+            self.clauses[clause].active = false;
+            if !self.clauses[clause].core {
+                continue;
+            }
+            match self.derive(clause, literals) {
+                Some(derivation) => derivations.push(derivation),
+                None => failed = Some(step)
+            }
+        }
+        derivations.reverse();
+
+        let verdict = match failed {
+            None => Verdict::Valid,
+            Some(step) => match &steps.get(step) {
+                Some(DratStep::Add(literals)) => Verdict::Failed { step, literals: literals.clone() },
+                _ => Verdict::Incomplete
+            }
+        };
+        (verdict, self.clauses.len(), derivations)
+    }
+
+    fn derive(&mut self, clause: usize, literals: Vec<Literal>) -> Option<Derivation> {
+        if let Some(rup) = self.rup(&literals) {
+            return Some(Derivation { clause, literals, rup, rat: Vec::new() })
+        }
+        let pivot = *literals.first()?;
+        let candidates: Vec<usize> = (0..self.clauses.len())
+            .filter(|&c| self.clauses[c].active && self.clauses[c].literals.contains(&!pivot))
+            .collect();
+        let mut rat = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            let mut resolvent = literals.clone();
+            resolvent.extend(self.clauses[candidate].literals.iter().filter(|&&l| l != !pivot));
+            rat.push((candidate, self.rup(&resolvent)?));
+        }
+        Some(Derivation { clause, literals, rup: Vec::new(), rat })
+    }
+}
+
+/*
+Assignment of an LRAT check, undone down to a trail length
+*/
+#[derive(Debug, Default)]
+struct Assignment {
+    values: Vec<Option<bool>>,
+    trail: Vec<Var>
+}
+
+impl Assignment {
+    fn value(&self, literal: Literal) -> Option<bool> {
+        let value = self.values.get(literal.var().index()).copied().flatten();
+        value.map(|value| value != literal.is_negated())
+    }
+
+    /*
+    Makes `literal` false, false when it is already true
+    */
+    fn falsify(&mut self, literal: Literal) -> bool {
+        match self.value(literal) {
+            Some(value) => !value,
+            None => {
+                let var = literal.var();
+                if self.values.len() <= var.index() {
+                    self.values.resize(var.index() + 1, None);
+                }
+                self.values[var.index()] = Some(literal.is_negated());
+                self.trail.push(var);
+                true
+            }
+        }
+    }
+
+    fn undo(&mut self, length: usize) {
+        for var in self.trail.drain(length..) {
+            self.values[var.index()] = None;
+        }
+    }
+
+    /*
+    Whether the hints end in a conflict, each one being unit before,
+    None when one is neither
+    */
+    fn units(&mut self, clauses: &[Vec<Literal>], hints: &[usize]) -> Option<bool> {
+        for &hint in hints {
+            let mut open = clauses[hint].iter().filter(|&&l| self.value(l) != Some(false));
+            match (open.next(), open.next()) {
+                (None, _) => return Some(true),
+                (Some(&unit), None) if self.value(unit).is_none() => {
+                    self.falsify(!unit);
+                },
+                _ => return None
+            }
+        }
+        Some(false)
+    }
+}
+
+/*
+Checks the lemmas of an LRAT proof in order up to the empty clause
+*/
+fn check_lrat(cnf: &Cnf, steps: &[LratStep]) -> (Verdict, usize, Vec<Derivation>) {
+    let mut clauses: Vec<Vec<Literal>> = cnf.clauses.iter().map(|clause| distinct(clause)).collect();
+    let mut ids: HashMap<u64, usize> = (0..clauses.len()).map(|clause| (clause as u64 + 1, clause)).collect();
+    let mut assignment = Assignment::default();
+    let mut derivations = Vec::new();
+    for (step, lrat_step) in steps.iter().enumerate() {
+        let (id, literals, hints) = match lrat_step {
+            LratStep::Add { id, literals, hints } => (*id, literals, hints),
+            LratStep::Delete(deleted) => {
+                for id in deleted {
+                    ids.remove(id);
+                }
+                continue;
+            }
+        };
+        let clause = clauses.len();
+        let derivation = if ids.contains_key(&id) {
+            None
+        } else {
+            derive_lrat(&clauses, &ids, &mut assignment, clause, distinct(literals), hints)
+        };
+        assignment.undo(0);
+        match derivation {
+            Some(derivation) => derivations.push(derivation),
+            None => return (Verdict::Failed { step, literals: literals.clone() }, clauses.len(), Vec::new())
+        }
+        clauses.push(distinct(literals));
+        ids.insert(id, clause);
+        if literals.is_empty() {
+            return (Verdict::Valid, clauses.len(), derivations)
+        }
+    }
+    (Verdict::Incomplete, clauses.len(), Vec::new())
+}
+
+fn derive_lrat(
+    clauses: &[Vec<Literal>],
+    ids: &HashMap<u64, usize>,
+    assignment: &mut Assignment,
+    clause: usize,
+    literals: Vec<Literal>,
+    hints: &[i64]
+) -> Option<Derivation> {
+    let mut derivation = Derivation { clause, literals, rup: Vec::new(), rat: Vec::new() };
+    for &hint in hints {
+        let id = ids.get(&hint.unsigned_abs()).copied()?;
+        match derivation.rat.last_mut() {
+            _ if hint < 0 => derivation.rat.push((id, Vec::new())),
+            Some((_, units)) => units.push(id),
+            None => derivation.rup.push(id)
+        }
+    }
+    if derivation.literals.iter().any(|&literal| !assignment.falsify(literal)) {
+        derivation.rup.clear();
+        derivation.rat.clear();
+        return Some(derivation)
+    }
+    if assignment.units(clauses, &derivation.rup)? {
+        derivation.rat.clear();
+        return Some(derivation)
+    }
+
+    let pivot = *derivation.literals.first()?;
+    let groups: HashMap<usize, &Vec<usize>> = derivation.rat.iter().map(|(id, units)| (*id, units)).collect();
+    let length = assignment.trail.len();
+    for &candidate in ids.values().filter(|&&c| clauses[c].contains(&!pivot)) {
+        let mut resolvent = derivation.literals.clone();
+        resolvent.extend(clauses[candidate].iter().filter(|&&l| l != !pivot));
+        if is_tautology(&resolvent) {
+            continue;
+        }
+        let units = groups.get(&candidate)?;
+        let satisfied = clauses[candidate].iter().filter(|&&l| l != !pivot).any(|&l| !assignment.falsify(l));
+        let conflict = satisfied || assignment.units(clauses, units)?;
+        assignment.undo(length);
+        if !conflict {
+            return None
+        }
+    }
+    Some(derivation)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::formula::{Clause, Operator};
+    use crate::io::proof::{parse_drat, parse_lrat, write_lrat};
+    use crate::solver::{ProofFormat, SolveOptions, SolveResult};
+
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    fn pigeonhole(pigeons: usize) -> SatInstance {
+        let holes = pigeons - 1;
+        let mut instance = SatInstance::new();
+        let vars: Vec<Vec<Var>> = (0..pigeons)
+            .map(|pigeon| (0..holes).map(|hole| instance.var(&format!("p{}h{}", pigeon, hole))).collect())
+            .collect();
+        for pigeon in &vars {
+            instance.add_clause(Clause::or(pigeon.iter().map(|var| var.pos()).collect()));
+        }
+        for hole in 0..holes {
+            instance.add_clause(Clause::new(Operator::AtMost(1), vars.iter().map(|pigeon| pigeon[hole].pos()).collect()));
+        }
+        instance
+    }
+
+    /*
+    Pigeonhole formulas and random 3-SAT past the threshold, which is
+    unsatisfiable for most seeds
+    */
+    fn refuted() -> Vec<SatInstance> {
+        let mut instances: Vec<SatInstance> = (3..6).map(pigeonhole).collect();
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        while instances.len() < 10 {
+            let mut instance = SatInstance::new();
+            for _ in 0..70 {
+                let literals = (0..3).map(|_| Literal::new(Var::new(random.below(12)), random.below(2) == 1)).collect();
+                instance.add_clause(Clause::or(literals));
+            }
+            if matches!(instance.solve_dpll(), SolveResult::Unsatisfiable) {
+                instances.push(instance);
+            }
+        }
+        instances
+    }
+
+    fn drat(instance: &SatInstance, format: ProofFormat) -> Vec<DratStep> {
+        let out = Shared::default();
+        let proof = Box::new(out.clone());
+        instance.solve_with_proof(&SolveOptions::default(), &CnfOptions::default(), proof, format).unwrap();
+        let steps = parse_drat(&out.0.borrow()).unwrap();
+        steps
+    }
+
+    fn lrat_text(steps: &[LratStep]) -> String {
+        let mut out = Vec::new();
+        write_lrat(steps, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn generated_proofs_are_valid_in_both_formats() {
+        for instance in refuted() {
+            for format in [ProofFormat::Text, ProofFormat::Binary] {
+                let check = instance.check_proof(&CnfOptions::default(), &Proof::Drat(drat(&instance, format)), false);
+                assert!(check.is_valid(), "{:?} {:?}", format, check.verdict);
+                assert!(!check.core.is_empty());
+                assert_eq!(check.lrat, None);
+            }
+        }
+    }
+
+    #[test]
+    fn trimmed_proofs_survive_a_round_trip() {
+        for instance in refuted() {
+            let options = CnfOptions::default();
+            let check = instance.check_proof(&options, &Proof::Drat(drat(&instance, ProofFormat::Text)), true);
+            let lrat = check.lrat.unwrap();
+            assert_eq!(lrat.iter().filter(|step| matches!(step, LratStep::Add { .. })).count(), check.lemmas);
+
+            let read = parse_lrat(&lrat_text(&lrat)).unwrap();
+            assert_eq!(read, lrat);
+            let lrat_check = instance.check_proof(&options, &Proof::Lrat(read), true);
+            assert!(lrat_check.is_valid(), "{:?}", lrat_check.verdict);
+            assert_eq!(lrat_check.core, check.core);
+            assert_eq!(lrat_check.lemmas, check.lemmas);
+            assert_eq!(lrat_check.lrat, Some(lrat));
+        }
+    }
+
+    #[test]
+    fn corrupted_proofs_are_rejected() {
+        let instance = SatInstance::builder()
+            .or(&["a", "b"])
+            .or(&["a", "!b"])
+            .or(&["!a", "b"])
+            .or(&["!a", "!b"])
+            .build();
+        let a = instance.vars.lookup("a").unwrap();
+        let (b, not_b) = (instance.clauses[0].literals.clone(), instance.clauses[1].literals.clone());
+        let check = |steps: Vec<DratStep>| instance.check_proof(&CnfOptions::default(), &Proof::Drat(steps), false).verdict;
+        assert_eq!(check(vec![DratStep::Add(vec![a.pos()])]), Verdict::Valid);
+        assert_eq!(check(Vec::new()), Verdict::Incomplete);
+        assert_eq!(check(vec![DratStep::Add(Vec::new())]), Verdict::Failed { step: 0, literals: Vec::new() });
+        let deleted = vec![DratStep::Delete(b), DratStep::Delete(not_b), DratStep::Add(vec![a.pos()]), DratStep::Add(Vec::new())];
+        assert_eq!(check(deleted), Verdict::Failed { step: 2, literals: vec![a.pos()] });
+
+        // Pigeonhole formulas have no units, so the empty clause needs the lemmas before it
+        let instance = pigeonhole(4);
+        let options = CnfOptions::default();
+        let steps = drat(&instance, ProofFormat::Binary);
+        let last = steps.len() - 1;
+        let check = instance.check_proof(&options, &Proof::Drat(steps[last..].to_vec()), false);
+        assert_eq!(check.verdict, Verdict::Failed { step: 0, literals: Vec::new() });
+        assert!(check.core.is_empty());
+
+        // Hints that no longer lead to a conflict
+        let lrat = instance.check_proof(&options, &Proof::Drat(steps), true).lrat.unwrap();
+        let (step, hint) = lrat
+            .iter()
+            .enumerate()
+            .find_map(|(step, lrat_step)| match lrat_step {
+                LratStep::Add { hints, .. } => Some((step, hints[0].unsigned_abs())),
+                LratStep::Delete(_) => None
+            })
+            .unwrap();
+        let mut corrupted = lrat.clone();
+        if let LratStep::Add { hints, .. } = &mut corrupted[step] {
+            hints.pop();
+        }
+        let check = instance.check_proof(&options, &Proof::Lrat(corrupted), false);
+        assert!(matches!(check.verdict, Verdict::Failed { step: failed, .. } if failed == step), "{:?}", check.verdict);
+
+        // Lemmas cannot take the id of a clause that is still present
+        let mut corrupted = lrat;
+        if let LratStep::Add { id, .. } = &mut corrupted[step] {
+            *id = hint;
+        }
+        let check = instance.check_proof(&options, &Proof::Lrat(corrupted), false);
+        assert!(matches!(check.verdict, Verdict::Failed { step: failed, .. } if failed == step), "{:?}", check.verdict);
+    }
+}
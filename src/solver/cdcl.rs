@@ -0,0 +1,1420 @@
+/*
+Conflict driven clause learning solver
+
+Variables are numbered 0..n and literals are encoded as
+variable * 2 + sign, so they can index watch lists directly.
+
+Clauses are kept in one arena, original clauses first and learned
+clauses appended after them. Every clause with two or more literals
+is watched by its first two literals; the implied literal of a reason
+clause is always kept in position 0.
+
+Linear constraints "sum of weight * literal >= bound", cardinality
+constraints being the ones with all weights 1, propagate natively by
+counting. Each keeps its slack, the weight of the literals that are
+not false minus the bound, up to date for the literals before the
+propagation queue head. Unassigned literals weighing more than the
+slack are implied, below 0 the constraint is conflicting. Reasons are
+explained on demand as the clause of the literals that were false
+before the implied one on the trail.
+
+XOR constraints are kept as a matrix (see `gauss`) that is eliminated
+whenever clauses and linear constraints have nothing left to propagate
+and some of its variables were assigned since. Its implications and
+conflicts come with their explanation clauses, which are stored until
+the implied literal is unassigned.
+
+With a proof set, every learned clause is written to it when learned
+and again when deleted, see `drat`. Clauses derived from linear and
+XOR constraints would need those constraints in the proof, so proofs
+only cover instances of plain clauses.
+
+Assumptions are decided first, one decision level each. When one of
+them is false at its turn, the reasons of its negation are followed
+back to the assumptions that imply it, which are the failed ones.
+
+Constraints added between solves go in at decision level 0. Learned
+clauses are kept, the constraints they follow from only ever grow,
+except for XOR constraints over switch variables that are removed
+together with the learned clauses mentioning those variables.
+*/
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::assignment::InstanceState;
+use crate::formula::{Literal, Var};
+use super::drat::{Proof, ProofFormat};
+use super::gauss::{Matrix, Propagation};
+
+/// Answer of `Solver::solve`, Unknown when the time limit ran out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Satisfiable,
+    Unsatisfiable,
+    Unknown
+}
+
+/// Search counters, mostly of interest for tuning.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub decisions: u64,
+    pub propagations: u64,
+    pub conflicts: u64,
+    pub restarts: u64,
+    pub learnt_clauses: u64,
+    pub deleted_clauses: u64
+}
+
+
+type ClauseRef = usize;
+
+/*
+Why a literal was implied, or which constraint is conflicting
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reason {
+    Clause(ClauseRef),
+    Linear(usize),
+    Xor
+}
+
+#[derive(Debug, Clone)]
+struct ClauseData {
+    literals: Vec<Literal>,
+    learnt: bool,
+    deleted: bool,
+    activity: f64,
+    lbd: u32
+}
+
+#[derive(Debug, Clone)]
+struct LinearData {
+    terms: Vec<(u64, Literal)>,
+    max_weight: u64,
+    slack: i64
+}
+
+/// Callback telling the solver to give up when it returns true.
+pub type Terminate = Box<dyn FnMut() -> bool>;
+/// Callback receiving learned clauses.
+pub type Learn = Box<dyn FnMut(&[Literal])>;
+
+/*
+Callbacks set by the user, which cannot be shown by Debug
+*/
+#[derive(Default)]
+struct Callbacks {
+    terminate: Option<Terminate>,
+    learn: Option<(usize, Learn)>
+}
+
+impl fmt::Debug for Callbacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Callbacks")
+            .field("terminate", &self.terminate.is_some())
+            .field("learn", &self.learn.as_ref().map(|(max_length, _)| max_length))
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Watcher {
+    clause: ClauseRef,
+    blocker: Literal
+}
+
+
+/*
+Binary max-heap of variables ordered by activity,
+`positions` tracks where each variable sits in `heap`.
+*/
+#[derive(Debug, Clone, Default)]
+struct VarOrder {
+    heap: Vec<usize>,
+    positions: Vec<Option<usize>>
+}
+
+impl VarOrder {
+    fn grow(&mut self, vars: usize) {
+        if self.positions.len() < vars {
+            self.positions.resize(vars, None);
+        }
+    }
+
+    fn contains(&self, var: usize) -> bool {
+        self.positions[var].is_some()
+    }
+
+    fn insert(&mut self, var: usize, activity: &[f64]) {
+        if self.contains(var) {
+            return
+        }
+        self.positions[var] = Some(self.heap.len());
+        self.heap.push(var);
+        self.sift_up(self.heap.len() - 1, activity);
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        if self.heap.is_empty() {
+            return None
+        }
+        let top = self.heap.swap_remove(0);
+        self.positions[top] = None;
+        if !self.heap.is_empty() {
+            self.positions[self.heap[0]] = Some(0);
+            self.sift_down(0, activity);
+        }
+        Some(top)
+    }
+
+    fn bumped(&mut self, var: usize, activity: &[f64]) {
+        if let Some(position) = self.positions[var] {
+            self.sift_up(position, activity);
+        }
+    }
+
+    fn sift_up(&mut self, mut position: usize, activity: &[f64]) {
+        let var = self.heap[position];
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if activity[self.heap[parent]] >= activity[var] {
+                break;
+            }
+            self.heap[position] = self.heap[parent];
+            self.positions[self.heap[position]] = Some(position);
+            position = parent;
+        }
+        self.heap[position] = var;
+        self.positions[var] = Some(position);
+    }
+
+    fn sift_down(&mut self, mut position: usize, activity: &[f64]) {
+        let var = self.heap[position];
+        loop {
+            let left = 2 * position + 1;
+            if left >= self.heap.len() {
+                break;
+            }
+            let right = left + 1;
+            let child = if right < self.heap.len()
+                && activity[self.heap[right]] > activity[self.heap[left]] {
+                right
+            } else {
+                left
+            };
+            if activity[self.heap[child]] <= activity[var] {
+                break;
+            }
+            self.heap[position] = self.heap[child];
+            self.positions[self.heap[position]] = Some(position);
+            position = child;
+        }
+        self.heap[position] = var;
+        self.positions[var] = Some(position);
+    }
+}
+
+
+/*
+Luby restart sequence 1, 1, 2, 1, 1, 2, 4, 1, ...
+*/
+fn luby(mut index: u64) -> u64 {
+    let mut size = 1;
+    let mut sequence = 0;
+    while size < index + 1 {
+        sequence += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != index {
+        size = (size - 1) >> 1;
+        sequence -= 1;
+        index %= size;
+    }
+    1 << sequence
+}
+
+const RESTART_BASE: u64 = 100;
+const VAR_DECAY: f64 = 0.95;
+const CLAUSE_DECAY: f64 = 0.999;
+
+
+/// Clause learning solver over numbered variables.
+///
+/// ```
+/// use solver::solver::cdcl::{Solver, Status};
+/// use solver::Var;
+///
+/// let mut solver = Solver::new();
+/// let (a, b) = (Var::new(0), Var::new(1));
+/// solver.add_clause(&[a.pos(), b.pos()]);
+/// solver.add_clause(&[a.neg()]);
+/// assert_eq!(solver.solve(), Status::Satisfiable);
+/// assert_eq!(solver.model().value(a), Some(false));
+/// assert_eq!(solver.model().value(b), Some(true));
+/// ```
+#[derive(Debug, Default)]
+pub struct Solver {
+    clauses: Vec<ClauseData>,
+    learnts: Vec<ClauseRef>,
+    watches: Vec<Vec<Watcher>>,
+    linear: Vec<LinearData>,
+    occurrences: Vec<Vec<(usize, u64)>>,
+    pub(super) xors: Matrix,
+    xor_reasons: Vec<Vec<Literal>>,
+    xor_conflict: Vec<Literal>,
+    xor_checked: Option<usize>,
+
+    state: InstanceState,
+    levels: Vec<usize>,
+    positions: Vec<usize>,
+    reasons: Vec<Option<Reason>>,
+    trail_lim: Vec<usize>,
+    queue_head: usize,
+
+    activity: Vec<f64>,
+    var_inc: f64,
+    clause_inc: f64,
+    order: VarOrder,
+    phases: Vec<bool>,
+
+    seen: Vec<bool>,
+    assumptions: Vec<Literal>,
+    failed: Vec<Literal>,
+    max_learnts: f64,
+    ok: bool,
+
+    proof: Option<Proof>,
+    callbacks: Callbacks,
+    time_limit: Option<Duration>,
+    deadline: Option<Instant>,
+    random: u64,
+    pub stats: Stats
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Solver {
+            var_inc: 1.0,
+            clause_inc: 1.0,
+            ok: true,
+            ..Default::default()
+        }
+    }
+
+    /// Gives up with Status::Unknown once solving has taken longer than `limit`
+    pub fn set_time_limit(&mut self, limit: Duration) {
+        self.time_limit = Some(limit);
+    }
+
+    /// Seeds the tiny random initial activities that break ties between
+    /// variables, call before adding clauses. Seed 0 keeps input order.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random = seed;
+    }
+
+    /// Asks `terminate` at every conflict whether to give up, solving
+    /// then returns Status::Unknown. None removes the callback.
+    pub fn set_terminate(&mut self, terminate: Option<Terminate>) {
+        self.callbacks.terminate = terminate;
+    }
+
+    /// Passes every learned clause of at most `max_length` literals to
+    /// `learn`. None removes the callback.
+    pub fn set_learn(&mut self, max_length: usize, learn: Option<Learn>) {
+        self.callbacks.learn = learn.map(|learn| (max_length, learn));
+    }
+
+    /// Writes a DRAT proof of the clauses learned and deleted from
+    /// now on to `out`, call before adding clauses.
+    pub fn set_proof(&mut self, out: Box<dyn Write>, format: ProofFormat) {
+        self.proof = Some(Proof::new(out, format));
+    }
+
+    /// Ends the proof, returns the first error writing it.
+    pub fn finish_proof(&mut self) -> io::Result<()> {
+        match self.proof.take() {
+            Some(proof) => proof.finish(),
+            None => Ok(())
+        }
+    }
+
+    /*
+    Marks the clauses as unsatisfiable, which the proof ends with
+    */
+    fn refuted(&mut self) {
+        self.ok = false;
+        if let Some(proof) = &mut self.proof {
+            proof.add(&[]);
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        // xorshift64*
+        self.random ^= self.random >> 12;
+        self.random ^= self.random << 25;
+        self.random ^= self.random >> 27;
+        self.random.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.state.num_vars()
+    }
+
+    fn ensure_vars(&mut self, vars: usize) {
+        if vars <= self.num_vars() {
+            return
+        }
+        let old_vars = self.num_vars();
+        self.state.grow(vars);
+        self.levels.resize(vars, 0);
+        self.positions.resize(vars, 0);
+        self.reasons.resize(vars, None);
+        self.activity.resize(vars, 0.0);
+        self.phases.resize(vars, false);
+        self.seen.resize(vars, false);
+        self.watches.resize(vars * 2, Vec::new());
+        self.occurrences.resize(vars * 2, Vec::new());
+        self.xor_reasons.resize(vars, Vec::new());
+        self.order.grow(vars);
+        if self.random != 0 {
+            for var in old_vars..vars {
+                self.activity[var] = (self.next_random() >> 11) as f64 * 1e-21;
+            }
+        }
+        for var in old_vars..vars {
+            self.order.insert(var, &self.activity);
+        }
+    }
+
+    fn value(&self, lit: Literal) -> Option<bool> {
+        self.state.literal_value(lit)
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    /// False once the constraints are known to be unsatisfiable.
+    pub fn is_ok(&self) -> bool {
+        self.ok
+    }
+
+    /// Removes the clauses, learned ones included, and the linear
+    /// constraints that the literals fixed at level 0 satisfy, such as
+    /// everything that holds only while a literal now false is true.
+    /// Returns false once the constraints are known to be unsatisfiable.
+    pub fn simplify(&mut self) -> bool {
+        if !self.ok {
+            return false
+        }
+        self.backtrack(0);
+        if self.propagate().is_some() {
+            self.refuted();
+            return false
+        }
+        for clause in 0..self.clauses.len() {
+            let data = &self.clauses[clause];
+            if data.deleted || self.locked(clause) || !data.literals.iter().any(|&l| self.value(l) == Some(true)) {
+                continue;
+            }
+            self.delete(clause);
+        }
+        let clauses = &self.clauses;
+        self.learnts.retain(|&clause| !clauses[clause].deleted);
+
+        // The slack of a satisfied constraint covers all its open literals
+        for constraint in 0..self.linear.len() {
+            let data = &self.linear[constraint];
+            let open: u64 = data.terms.iter().filter(|&&(_, l)| self.value(l).is_none()).map(|&(w, _)| w).sum();
+            if data.terms.is_empty() || data.slack < open as i64 {
+                continue;
+            }
+            for (_, literal) in std::mem::take(&mut self.linear[constraint].terms) {
+                self.occurrences[literal.code()].retain(|&(other, _)| other != constraint);
+            }
+        }
+        true
+    }
+
+    /// Removes the XOR constraints over any of `vars` and the learned
+    /// clauses that mention them, which only holds up for variables
+    /// that occur nowhere else, such as switches that turn constraints
+    /// off. Clauses that are reasons at level 0 stay.
+    pub fn remove_xors(&mut self, vars: &[Var]) {
+        if vars.is_empty() {
+            return
+        }
+        self.backtrack(0);
+        let mut removed = vec![false; self.num_vars()];
+        for var in vars {
+            removed[var.index()] = true;
+        }
+        self.xors.remove(|var| removed[var.index()]);
+        self.xor_checked = None;
+        for index in 0..self.learnts.len() {
+            let clause = self.learnts[index];
+            let data = &self.clauses[clause];
+            if data.deleted || self.locked(clause) || !data.literals.iter().any(|l| removed[l.var().index()]) {
+                continue;
+            }
+            self.delete(clause);
+        }
+        let clauses = &self.clauses;
+        self.learnts.retain(|&clause| !clauses[clause].deleted);
+    }
+
+    /// Adds an original clause, before search or between solves, which
+    /// keeps the learned clauses. Returns false once the clause set is
+    /// known to be unsatisfiable.
+    pub fn add_clause(&mut self, literals: &[Literal]) -> bool {
+        if !self.ok {
+            return false
+        }
+        self.backtrack(0);
+        if let Some(max_var) = literals.iter().map(|l| l.var().index()).max() {
+            self.ensure_vars(max_var + 1);
+        }
+
+        let mut literals = literals.to_vec();
+        literals.sort();
+        literals.dedup();
+        // Tautologies are always satisfied, sorting puts x and not x side by side
+        if literals.windows(2).any(|pair| pair[0] == !pair[1]) {
+            return true
+        }
+        if literals.iter().any(|&l| self.value(l) == Some(true)) {
+            return true
+        }
+        literals.retain(|&l| self.value(l).is_none());
+
+        match literals.len() {
+            0 => self.refuted(),
+            1 => {
+                self.enqueue(literals[0], None);
+                if self.propagate().is_some() {
+                    self.refuted();
+                }
+            },
+            _ => {
+                self.attach(literals, false);
+            }
+        }
+        self.ok
+    }
+
+    /// Adds the constraint that at least `k` of `literals` are true,
+    /// before search or between solves as `add_clause`. A literal that
+    /// is listed twice counts twice. Returns false once the constraints are
+    /// known to be unsatisfiable.
+    pub fn add_at_least(&mut self, literals: &[Literal], k: usize) -> bool {
+        let terms: Vec<(u64, Literal)> = literals.iter().map(|&l| (1, l)).collect();
+        self.add_linear(&terms, k as i64)
+    }
+
+    /// Adds the constraint that the weights of the true literals of
+    /// `terms` sum to at least `bound`, before search or between solves.
+    /// Besides one term of weight `bound`, the weights sum to at most i64::MAX.
+    /// Returns false once the constraints are known to be unsatisfiable.
+    pub fn add_linear(&mut self, terms: &[(u64, Literal)], bound: i64) -> bool {
+        if !self.ok {
+            return false
+        }
+        self.backtrack(0);
+        if let Some(max_var) = terms.iter().map(|(_, l)| l.var().index()).max() {
+            self.ensure_vars(max_var + 1);
+        }
+        // Weights above the bound can be lowered to it
+        let saturate = bound.max(0) as u64;
+        let terms: Vec<(u64, Literal)> = terms
+            .iter()
+            .filter(|&&(weight, _)| weight > 0)
+            .map(|&(weight, literal)| (weight.min(saturate), literal))
+            .collect();
+        // In i128 as a selector term of weight bound may take the sum out of i64
+        let weight_of = |value: Option<bool>| -> i128 {
+            terms
+                .iter()
+                .filter(|&&(_, l)| self.value(l) == value)
+                .map(|&(weight, _)| weight as i128)
+                .sum()
+        };
+        if weight_of(Some(true)) >= bound as i128 {
+            return true
+        }
+        let slack = (weight_of(Some(true)) + weight_of(None) - bound as i128) as i64;
+        if slack < 0 {
+            self.refuted();
+            return false
+        }
+
+        let constraint = self.linear.len();
+        for &(weight, literal) in &terms {
+            self.occurrences[literal.code()].push((constraint, weight));
+        }
+        let max_weight = terms.iter().map(|&(weight, _)| weight).max().unwrap_or(0);
+        self.linear.push(LinearData { terms, max_weight, slack });
+        if (slack as u64) < max_weight {
+            self.imply_linear(constraint);
+            if self.propagate().is_some() {
+                self.refuted();
+            }
+        }
+        self.ok
+    }
+
+    /// Adds the constraint that an odd number of `literals` is true
+    /// when `odd`, an even number otherwise, before search or between
+    /// solves. Returns false once the constraints are known to be
+    /// unsatisfiable.
+    pub fn add_xor(&mut self, literals: &[Literal], odd: bool) -> bool {
+        if !self.ok {
+            return false
+        }
+        self.backtrack(0);
+        if let Some(max_var) = literals.iter().map(|l| l.var().index()).max() {
+            self.ensure_vars(max_var + 1);
+        }
+        if !self.xors.add(literals, odd) {
+            self.refuted();
+            return false
+        }
+        self.xor_checked = None;
+        if self.propagate().is_some() {
+            self.refuted();
+        }
+        self.ok
+    }
+
+    /*
+    Enqueues the unassigned literals of a linear constraint
+    that weigh more than its slack
+    */
+    fn imply_linear(&mut self, constraint: usize) {
+        let slack = self.linear[constraint].slack;
+        for k in 0..self.linear[constraint].terms.len() {
+            let (weight, literal) = self.linear[constraint].terms[k];
+            if weight as i64 > slack && self.value(literal).is_none() {
+                self.enqueue(literal, Some(Reason::Linear(constraint)));
+            }
+        }
+    }
+
+    fn attach(&mut self, literals: Vec<Literal>, learnt: bool) -> ClauseRef {
+        let clause = self.clauses.len();
+        self.watches[literals[0].code()].push(Watcher { clause, blocker: literals[1] });
+        self.watches[literals[1].code()].push(Watcher { clause, blocker: literals[0] });
+        self.clauses.push(ClauseData {
+            literals,
+            learnt,
+            deleted: false,
+            activity: 0.0,
+            lbd: 0
+        });
+        if learnt {
+            self.learnts.push(clause);
+        }
+        clause
+    }
+
+    fn enqueue(&mut self, lit: Literal, reason: Option<Reason>) {
+        let var = lit.var().index();
+        self.positions[var] = self.state.trail().len();
+        self.state.assign(lit);
+        self.levels[var] = self.decision_level();
+        self.reasons[var] = reason;
+    }
+
+    /*
+    Counts `false_lit` against the slack of its linear constraints,
+    all of them before looking for conflicts so that backtracking can
+    undo the counts of every literal before the queue head
+    */
+    fn propagate_linear(&mut self, false_lit: Literal) -> Option<Reason> {
+        let constraints = std::mem::take(&mut self.occurrences[false_lit.code()]);
+        for &(constraint, weight) in &constraints {
+            self.linear[constraint].slack -= weight as i64;
+        }
+        let mut conflict = None;
+        for &(constraint, _) in &constraints {
+            let data = &self.linear[constraint];
+            if data.slack < 0 {
+                conflict = Some(Reason::Linear(constraint));
+                break;
+            }
+            if (data.slack as u64) < data.max_weight {
+                self.imply_linear(constraint);
+            }
+        }
+        self.occurrences[false_lit.code()] = constraints;
+        conflict
+    }
+
+    /*
+    Propagates until nothing is left to imply,
+    returns the conflicting constraint
+    */
+    fn propagate(&mut self) -> Option<Reason> {
+        loop {
+            let conflict = self.propagate_queue();
+            if conflict.is_some() {
+                return conflict
+            }
+            // Elimination only finds something new after assignments to its variables
+            let trail = self.state.trail().len();
+            let touched = match self.xor_checked {
+                Some(checked) => self.state.trail()[checked..].iter().any(|l| self.xors.contains(l.var())),
+                None => !self.xors.is_empty()
+            };
+            self.xor_checked = Some(trail);
+            if !touched {
+                return None
+            }
+            let conflict = self.propagate_xor();
+            if conflict.is_some() || self.state.trail().len() == trail {
+                return conflict
+            }
+        }
+    }
+
+    /*
+    Enqueues what elimination of the XOR matrix implies,
+    returns Reason::Xor when it finds a conflict
+    */
+    fn propagate_xor(&mut self) -> Option<Reason> {
+        let state = &self.state;
+        match self.xors.eliminate(|var| state.value(var)) {
+            Propagation::Conflict(clause) => {
+                self.xor_conflict = clause;
+                Some(Reason::Xor)
+            },
+            Propagation::Implied(clauses) => {
+                for clause in clauses {
+                    let var = clause[0].var().index();
+                    self.enqueue(clause[0], Some(Reason::Xor));
+                    self.xor_reasons[var] = clause;
+                }
+                None
+            }
+        }
+    }
+
+    /*
+    Two watched literal unit propagation and linear constraint counting,
+    returns the conflicting constraint
+    */
+    fn propagate_queue(&mut self) -> Option<Reason> {
+        let mut conflict = None;
+        while self.queue_head < self.state.trail().len() && conflict.is_none() {
+            let false_lit = !self.state.trail()[self.queue_head];
+            self.queue_head += 1;
+            self.stats.propagations += 1;
+
+            conflict = self.propagate_linear(false_lit);
+            if conflict.is_some() {
+                break;
+            }
+
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.code()]);
+            let mut kept = 0;
+            let mut i = 0;
+            while i < watchers.len() {
+                let watcher = watchers[i];
+                i += 1;
+                if self.value(watcher.blocker) == Some(true) {
+                    watchers[kept] = watcher;
+                    kept += 1;
+                    continue;
+                }
+                if self.clauses[watcher.clause].deleted {
+                    continue;
+                }
+
+                let literals = &mut self.clauses[watcher.clause].literals;
+                if literals[0] == false_lit {
+                    literals.swap(0, 1);
+                }
+                let first = literals[0];
+                let moved = Watcher { clause: watcher.clause, blocker: first };
+                if first != watcher.blocker && self.value(first) == Some(true) {
+                    watchers[kept] = moved;
+                    kept += 1;
+                    continue;
+                }
+
+                // Look for a new literal to watch
+                let state = &self.state;
+                let literals = &mut self.clauses[watcher.clause].literals;
+                let replacement = (2..literals.len()).find(|&k| {
+                    state.literal_value(literals[k]) != Some(false)
+                });
+                if let Some(k) = replacement {
+                    literals.swap(1, k);
+                    let watch = literals[1];
+                    self.watches[watch.code()].push(moved);
+                    continue;
+                }
+
+                // Clause is unit or conflicting
+                watchers[kept] = moved;
+                kept += 1;
+                if self.value(first) == Some(false) {
+                    conflict = Some(Reason::Clause(watcher.clause));
+                    while i < watchers.len() {
+                        watchers[kept] = watchers[i];
+                        kept += 1;
+                        i += 1;
+                    }
+                } else {
+                    self.enqueue(first, Some(Reason::Clause(watcher.clause)));
+                }
+            }
+            watchers.truncate(kept);
+            self.watches[false_lit.code()] = watchers;
+        }
+        conflict
+    }
+
+    fn bump_var(&mut self, var: usize) {
+        self.activity[var] += self.var_inc;
+        if self.activity[var] > 1e100 {
+            for activity in self.activity.iter_mut() {
+                *activity *= 1e-100;
+            }
+            self.var_inc *= 1e-100;
+        }
+        self.order.bumped(var, &self.activity);
+    }
+
+    fn bump_clause(&mut self, clause: ClauseRef) {
+        self.clauses[clause].activity += self.clause_inc;
+        if self.clauses[clause].activity > 1e20 {
+            for &learnt in &self.learnts {
+                self.clauses[learnt].activity *= 1e-20;
+            }
+            self.clause_inc *= 1e-20;
+        }
+    }
+
+    /*
+    Literals of the clause behind `reason`, all false except
+    `implied` which is put first. Conflicting constraints have no
+    implied literal and give all their false literals.
+    */
+    fn explain(&self, reason: Reason, implied: Option<Literal>, literals: &mut Vec<Literal>) {
+        literals.clear();
+        match reason {
+            Reason::Clause(clause) => literals.extend_from_slice(&self.clauses[clause].literals),
+            Reason::Linear(constraint) => {
+                let before = implied.map_or(usize::MAX, |l| self.positions[l.var().index()]);
+                literals.extend(implied);
+                literals.extend(self.linear[constraint].terms.iter().map(|&(_, l)| l).filter(|&l| {
+                    self.value(l) == Some(false) && self.positions[l.var().index()] < before
+                }));
+            },
+            Reason::Xor => match implied {
+                Some(implied) => literals.extend_from_slice(&self.xor_reasons[implied.var().index()]),
+                None => literals.extend_from_slice(&self.xor_conflict)
+            }
+        }
+    }
+
+    /*
+    First unique implication point conflict analysis.
+    Returns the learned clause with the asserting literal first
+    and the level to backjump to.
+    */
+    fn analyze(&mut self, conflict: Reason) -> (Vec<Literal>, usize) {
+        let mut learnt: Vec<Literal> = vec![Literal::pos(Var::new(0))];
+        let mut pending = 0;
+        let mut implied: Option<Literal> = None;
+        let mut reason = conflict;
+        let mut literals = Vec::new();
+        let mut index = self.state.trail().len();
+
+        loop {
+            if let Reason::Clause(clause) = reason {
+                if self.clauses[clause].learnt {
+                    self.bump_clause(clause);
+                }
+            }
+            self.explain(reason, implied, &mut literals);
+            let skip = if implied.is_some() { 1 } else { 0 };
+            for &lit in &literals[skip..] {
+                let var = lit.var().index();
+                if self.seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                self.bump_var(var);
+                self.seen[var] = true;
+                if self.levels[var] >= self.decision_level() {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+
+            loop {
+                index -= 1;
+                if self.seen[self.state.trail()[index].var().index()] {
+                    break;
+                }
+            }
+            let lit = self.state.trail()[index];
+            implied = Some(lit);
+            self.seen[lit.var().index()] = false;
+            pending -= 1;
+            if pending == 0 {
+                break;
+            }
+            reason = self.reasons[lit.var().index()].unwrap();
+        }
+        learnt[0] = !implied.unwrap();
+
+        self.minimize(&mut learnt);
+
+        // Put the literal with the highest level second, it becomes the other watch
+        let mut backjump = 0;
+        if learnt.len() > 1 {
+            let mut max = 1;
+            for k in 2..learnt.len() {
+                if self.levels[learnt[k].var().index()] > self.levels[learnt[max].var().index()] {
+                    max = k;
+                }
+            }
+            learnt.swap(1, max);
+            backjump = self.levels[learnt[1].var().index()];
+        }
+        (learnt, backjump)
+    }
+
+    /*
+    Recursive learned clause minimization, drops literals
+    that are implied by the other literals of the clause.
+    */
+    fn minimize(&mut self, learnt: &mut Vec<Literal>) {
+        let mut marked: Vec<usize> = learnt.iter().map(|l| l.var().index()).collect();
+        for lit in learnt.iter() {
+            self.seen[lit.var().index()] = true;
+        }
+        let levels: u64 = learnt[1..]
+            .iter()
+            .fold(0, |mask, l| mask | self.level_mask(l.var().index()));
+
+        let mut kept = 1;
+        for k in 1..learnt.len() {
+            let lit = learnt[k];
+            if self.reasons[lit.var().index()].is_none() || !self.redundant(lit, levels, &mut marked) {
+                learnt[kept] = lit;
+                kept += 1;
+            }
+        }
+        learnt.truncate(kept);
+        for var in marked {
+            self.seen[var] = false;
+        }
+    }
+
+    fn level_mask(&self, var: usize) -> u64 {
+        1 << (self.levels[var] & 63)
+    }
+
+    fn redundant(&mut self, lit: Literal, levels: u64, marked: &mut Vec<usize>) -> bool {
+        let mut stack = vec![lit];
+        let mut literals = Vec::new();
+        let top = marked.len();
+        while let Some(lit) = stack.pop() {
+            let reason = self.reasons[lit.var().index()].unwrap();
+            self.explain(reason, Some(!lit), &mut literals);
+            for &other in &literals[1..] {
+                let var = other.var().index();
+                if self.seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                if self.reasons[var].is_some() && self.level_mask(var) & levels != 0 {
+                    self.seen[var] = true;
+                    marked.push(var);
+                    stack.push(other);
+                } else {
+                    for &var in &marked[top..] {
+                        self.seen[var] = false;
+                    }
+                    marked.truncate(top);
+                    return false
+                }
+            }
+        }
+        true
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return
+        }
+        let start = self.trail_lim[level];
+        while self.state.trail().len() > start {
+            // Only literals before the queue head were counted by linear constraints
+            if self.state.trail().len() <= self.queue_head {
+                let false_lit = !self.state.trail()[self.state.trail().len() - 1];
+                for &(constraint, weight) in &self.occurrences[false_lit.code()] {
+                    self.linear[constraint].slack += weight as i64;
+                }
+            }
+            let lit = self.state.pop().unwrap();
+            let var = lit.var().index();
+            self.reasons[var] = None;
+            self.phases[var] = !lit.is_negated();
+            self.order.insert(var, &self.activity);
+        }
+        self.trail_lim.truncate(level);
+        self.queue_head = start;
+        self.xor_checked = self.xor_checked.map(|checked| checked.min(start));
+    }
+
+    fn decide(&mut self) -> Option<Literal> {
+        while let Some(var) = self.order.pop(&self.activity) {
+            if !self.state.is_assigned(Var::new(var)) {
+                return Some(Literal::new(Var::new(var), !self.phases[var]))
+            }
+        }
+        None
+    }
+
+    fn lbd(&self, literals: &[Literal]) -> u32 {
+        let mut levels: Vec<usize> = literals.iter().map(|l| self.levels[l.var().index()]).collect();
+        levels.sort_unstable();
+        levels.dedup();
+        levels.len() as u32
+    }
+
+    fn locked(&self, clause: ClauseRef) -> bool {
+        let first = self.clauses[clause].literals[0];
+        self.reasons[first.var().index()] == Some(Reason::Clause(clause))
+            && self.value(first) == Some(true)
+    }
+
+    /*
+    Removes about half of the learned clauses, keeping
+    clauses with low literal block distance and reasons.
+    */
+    fn reduce_learnts(&mut self) {
+        let mut learnts = std::mem::take(&mut self.learnts);
+        learnts.sort_by(|&a, &b| {
+            let (a, b) = (&self.clauses[a], &self.clauses[b]);
+            b.lbd.cmp(&a.lbd).then(a.activity.partial_cmp(&b.activity).unwrap())
+        });
+        let limit = learnts.len() / 2;
+        let mut kept = Vec::with_capacity(learnts.len());
+        for (k, clause) in learnts.into_iter().enumerate() {
+            let data = &self.clauses[clause];
+            if k < limit && data.lbd > 2 && data.literals.len() > 2 && !self.locked(clause) {
+                self.delete(clause);
+            } else {
+                kept.push(clause);
+            }
+        }
+        self.learnts = kept;
+    }
+
+    fn delete(&mut self, clause: ClauseRef) {
+        if let Some(proof) = &mut self.proof {
+            proof.delete(&self.clauses[clause].literals);
+        }
+        let data = &mut self.clauses[clause];
+        data.deleted = true;
+        data.literals = Vec::new();
+        self.stats.deleted_clauses += 1;
+    }
+
+    /*
+    Searches until a model is found, the clauses are refuted
+    or the conflict budget runs out, which yields None.
+    */
+    fn search(&mut self, conflict_budget: u64) -> Option<Status> {
+        let mut conflicts = 0;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                self.stats.conflicts += 1;
+                conflicts += 1;
+                if self.decision_level() == 0 {
+                    self.refuted();
+                    return Some(Status::Unsatisfiable)
+                }
+                let terminated = self.callbacks.terminate.as_mut().is_some_and(|terminate| terminate());
+                if terminated || matches!(self.deadline, Some(deadline) if Instant::now() >= deadline) {
+                    self.backtrack(0);
+                    return Some(Status::Unknown)
+                }
+
+                let (learnt, backjump) = self.analyze(conflict);
+                if let Some(proof) = &mut self.proof {
+                    proof.add(&learnt);
+                }
+                if let Some((max_length, learn)) = &mut self.callbacks.learn {
+                    if learnt.len() <= *max_length {
+                        learn(&learnt);
+                    }
+                }
+                self.backtrack(backjump);
+                self.stats.learnt_clauses += 1;
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let lbd = self.lbd(&learnt);
+                    let asserting = learnt[0];
+                    let clause = self.attach(learnt, true);
+                    self.clauses[clause].lbd = lbd;
+                    self.bump_clause(clause);
+                    self.enqueue(asserting, Some(Reason::Clause(clause)));
+                }
+
+                self.var_inc /= VAR_DECAY;
+                self.clause_inc /= CLAUSE_DECAY;
+            } else {
+                if conflicts >= conflict_budget {
+                    self.backtrack(0);
+                    return None
+                }
+                if self.learnts.len() as f64 - self.state.trail().len() as f64 >= self.max_learnts {
+                    self.reduce_learnts();
+                    self.max_learnts *= 1.1;
+                }
+
+                let mut next = None;
+                while self.decision_level() < self.assumptions.len() {
+                    let assumption = self.assumptions[self.decision_level()];
+                    match self.value(assumption) {
+                        // Already true, an empty level keeps levels and assumptions aligned
+                        Some(true) => self.trail_lim.push(self.state.trail().len()),
+                        Some(false) => {
+                            self.analyze_final(assumption);
+                            self.backtrack(0);
+                            return Some(Status::Unsatisfiable)
+                        },
+                        None => {
+                            next = Some(assumption);
+                            break;
+                        }
+                    }
+                }
+
+                match next.or_else(|| self.decide()) {
+                    Some(lit) => {
+                        self.stats.decisions += 1;
+                        self.trail_lim.push(self.state.trail().len());
+                        self.enqueue(lit, None);
+                    },
+                    None => return Some(Status::Satisfiable)
+                }
+            }
+        }
+    }
+
+    /*
+    Collects the assumptions that make `assumption` false
+    together with `assumption` itself into `failed`
+    */
+    fn analyze_final(&mut self, assumption: Literal) {
+        self.failed = vec![assumption];
+        if self.decision_level() == 0 {
+            return
+        }
+        let mut literals = Vec::new();
+        self.seen[assumption.var().index()] = true;
+        for index in (self.trail_lim[0]..self.state.trail().len()).rev() {
+            let lit = self.state.trail()[index];
+            let var = lit.var().index();
+            if !self.seen[var] {
+                continue;
+            }
+            self.seen[var] = false;
+            match self.reasons[var] {
+                None => self.failed.push(lit),
+                Some(reason) => {
+                    self.explain(reason, Some(lit), &mut literals);
+                    for &other in &literals[1..] {
+                        if self.levels[other.var().index()] > 0 {
+                            self.seen[other.var().index()] = true;
+                        }
+                    }
+                }
+            }
+        }
+        self.seen[assumption.var().index()] = false;
+    }
+
+    pub fn solve(&mut self) -> Status {
+        self.solve_with_assumptions(&[])
+    }
+
+    /// Solves under the assumption that all of `assumptions` are true,
+    /// which only hold for this call. When that is unsatisfiable,
+    /// `failed_assumptions` tells which of them were needed.
+    pub fn solve_with_assumptions(&mut self, assumptions: &[Literal]) -> Status {
+        self.failed.clear();
+        if !self.ok {
+            return Status::Unsatisfiable
+        }
+        if let Some(max_var) = assumptions.iter().map(|l| l.var().index()).max() {
+            self.ensure_vars(max_var + 1);
+        }
+        self.backtrack(0);
+        self.assumptions = assumptions.to_vec();
+        self.max_learnts = (self.clauses.len() as f64 / 3.0).max(1000.0);
+        // Limits too far off to represent are no limit
+        self.deadline = self.time_limit.and_then(|limit| Instant::now().checked_add(limit));
+        let mut restarts = 0;
+        loop {
+            let budget = luby(restarts) * RESTART_BASE;
+            if let Some(status) = self.search(budget) {
+                return status
+            }
+            restarts += 1;
+            self.stats.restarts += 1;
+        }
+    }
+
+    /// Assumptions of the last solve that are unsatisfiable together
+    /// with the clauses, empty when the clauses alone are.
+    pub fn failed_assumptions(&self) -> &[Literal] {
+        &self.failed
+    }
+
+    /// Model found by the last solve, which assigns every variable
+    /// when it returned Status::Satisfiable.
+    pub fn model(&self) -> &InstanceState {
+        &self.state
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::dpll::dpll;
+
+    /*
+    Constraints of the tests, checked by brute force
+    */
+    enum Constraint {
+        Clause(Vec<Literal>),
+        Linear(Vec<(u64, Literal)>, i64),
+        Xor(Vec<Literal>, bool)
+    }
+
+    impl Constraint {
+        fn holds(&self, values: &[bool]) -> bool {
+            let value = |l: &Literal| values[l.var().index()] != l.is_negated();
+            match self {
+                Constraint::Clause(literals) => literals.iter().any(value),
+                Constraint::Linear(terms, bound) =>
+                    terms.iter().filter(|(_, l)| value(l)).map(|&(w, _)| w as i64).sum::<i64>() >= *bound,
+                Constraint::Xor(literals, odd) => (literals.iter().filter(|l| value(l)).count() % 2 == 1) == *odd
+            }
+        }
+
+        fn add_to(&self, solver: &mut Solver) -> bool {
+            match self {
+                Constraint::Clause(literals) => solver.add_clause(literals),
+                Constraint::Linear(terms, bound) => solver.add_linear(terms, *bound),
+                Constraint::Xor(literals, odd) => solver.add_xor(literals, *odd)
+            }
+        }
+    }
+
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn literal(&mut self, vars: usize) -> Literal {
+            Literal::new(Var::new(self.below(vars)), self.below(2) == 1)
+        }
+
+        fn clause(&mut self, vars: usize, len: usize) -> Vec<Literal> {
+            (0..len).map(|_| self.literal(vars)).collect()
+        }
+    }
+
+    // Variables no constraint mentions yet are left out of the model
+    fn values(solver: &Solver, vars: usize) -> Vec<bool> {
+        (0..vars).map(|var| solver.model().value(Var::new(var)).unwrap_or(false)).collect()
+    }
+
+    fn satisfiable(constraints: &[Constraint], vars: usize, units: &[Literal]) -> bool {
+        (0..1u32 << vars).any(|bits| {
+            let values: Vec<bool> = (0..vars).map(|var| bits >> var & 1 == 1).collect();
+            units.iter().all(|l| values[l.var().index()] != l.is_negated())
+                && constraints.iter().all(|c| c.holds(&values))
+        })
+    }
+
+    fn pigeonhole(pigeons: usize, holes: usize) -> Vec<Vec<Literal>> {
+        let var = |pigeon: usize, hole: usize| Var::new(pigeon * holes + hole);
+        let mut clauses: Vec<Vec<Literal>> = (0..pigeons)
+            .map(|pigeon| (0..holes).map(|hole| var(pigeon, hole).pos()).collect())
+            .collect();
+        for hole in 0..holes {
+            for p in 0..pigeons {
+                for q in p + 1..pigeons {
+                    clauses.push(vec![var(p, hole).neg(), var(q, hole).neg()]);
+                }
+            }
+        }
+        clauses
+    }
+
+    #[test]
+    fn pigeonhole_is_unsatisfiable() {
+        for holes in 1..6 {
+            let mut solver = Solver::new();
+            for clause in pigeonhole(holes + 1, holes) {
+                solver.add_clause(&clause);
+            }
+            assert_eq!(solver.solve(), Status::Unsatisfiable, "{} holes", holes);
+
+            let mut solver = Solver::new();
+            for clause in pigeonhole(holes, holes) {
+                solver.add_clause(&clause);
+            }
+            assert_eq!(solver.solve(), Status::Satisfiable, "{} holes", holes);
+        }
+    }
+
+    #[test]
+    fn random_3sat_agrees_with_dpll() {
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        for round in 0..300 {
+            let vars = 10 + round % 10;
+            let clauses: Vec<Vec<Literal>> = (0..vars * 4 + round % 7).map(|_| random.clause(vars, 3)).collect();
+            let mut solver = Solver::new();
+            solver.set_seed(round as u64);
+            for clause in &clauses {
+                solver.add_clause(clause);
+            }
+            let mut expected = vec![None; vars];
+            let status = solver.solve();
+            assert_eq!(status == Status::Satisfiable, dpll(&clauses, &mut expected, None) == Some(true), "round {}", round);
+            if status == Status::Satisfiable {
+                let values = values(&solver, vars);
+                assert!(clauses.iter().all(|clause| Constraint::Clause(clause.clone()).holds(&values)));
+            }
+        }
+    }
+
+    #[test]
+    fn failed_assumptions() {
+        let (a, b, c) = (Var::new(0), Var::new(1), Var::new(2));
+        let mut solver = Solver::new();
+        solver.add_clause(&[a.neg(), b.neg()]);
+        solver.add_clause(&[b.pos(), c.pos()]);
+        assert_eq!(solver.solve_with_assumptions(&[c.neg(), a.pos()]), Status::Unsatisfiable);
+        let mut failed = solver.failed_assumptions().to_vec();
+        failed.sort();
+        assert_eq!(failed, vec![a.pos(), c.neg()]);
+        assert_eq!(solver.solve_with_assumptions(&[a.pos()]), Status::Satisfiable);
+        assert_eq!(solver.model().value(c), Some(true));
+        assert_eq!(solver.solve(), Status::Satisfiable);
+        assert!(solver.failed_assumptions().is_empty());
+
+        // Failed assumptions are unsatisfiable with the clauses by themselves
+        let mut random = Random(7);
+        for round in 0..200 {
+            let vars = 12;
+            let constraints: Vec<Constraint> = (0..30).map(|_| Constraint::Clause(random.clause(vars, 3))).collect();
+            let assumptions = random.clause(vars, 1 + round % 6);
+            let mut solver = Solver::new();
+            for constraint in &constraints {
+                constraint.add_to(&mut solver);
+            }
+            let status = solver.solve_with_assumptions(&assumptions);
+            assert_eq!(status == Status::Satisfiable, satisfiable(&constraints, vars, &assumptions), "round {}", round);
+            if status == Status::Unsatisfiable {
+                let failed = solver.failed_assumptions();
+                assert!(failed.iter().all(|l| assumptions.contains(l)));
+                assert!(!satisfiable(&constraints, vars, failed), "round {}", round);
+            }
+        }
+    }
+
+    #[test]
+    fn constraints_between_solves() {
+        let mut random = Random(0x2545_f491);
+        for round in 0..200 {
+            let vars = 8;
+            let mut solver = Solver::new();
+            let mut constraints = Vec::new();
+            for _ in 0..12 {
+                let constraint = match random.below(3) {
+                    0 => {
+                        let len = 2 + random.below(2);
+                        Constraint::Clause(random.clause(vars, len))
+                    },
+                    1 => {
+                        let terms: Vec<(u64, Literal)> =
+                            (0..4).map(|_| (1 + random.below(3) as u64, random.literal(vars))).collect();
+                        Constraint::Linear(terms, 2 + random.below(4) as i64)
+                    },
+                    _ => Constraint::Xor(random.clause(vars, 3), random.below(2) == 1)
+                };
+                constraint.add_to(&mut solver);
+                constraints.push(constraint);
+
+                let expected = satisfiable(&constraints, vars, &[]);
+                let status = solver.solve();
+                assert_eq!(status == Status::Satisfiable, expected, "round {}", round);
+                if !expected {
+                    break;
+                }
+                let values = values(&solver, vars);
+                assert!(constraints.iter().all(|c| c.holds(&values)), "round {}", round);
+            }
+        }
+    }
+
+    #[test]
+    fn removed_xors_take_their_learned_clauses() {
+        let mut random = Random(11);
+        let vars = 10;
+        let mut solver = Solver::new();
+        for _ in 0..25 {
+            solver.add_clause(&random.clause(vars, 3));
+        }
+        let mut next = vars;
+        let mut mentioned = 0;
+        for _ in 0..3 {
+            // XOR constraints turned off by switches once the activation literal is false
+            let activation = Var::new(next).pos();
+            let switches: Vec<Var> = (next + 1..next + 7).map(Var::new).collect();
+            next += 7;
+            for &switch in &switches {
+                let mut literals = random.clause(vars, 4);
+                literals.push(switch.pos());
+                solver.add_xor(&literals, random.below(2) == 1);
+                solver.add_clause(&[!activation, switch.neg()]);
+            }
+            solver.solve_with_assumptions(&[activation]);
+            let mentions = |solver: &Solver| {
+                solver
+                    .learnts
+                    .iter()
+                    .filter(|&&clause| solver.clauses[clause].literals.iter().any(|l| switches.contains(&l.var())))
+                    .count()
+            };
+            mentioned += mentions(&solver);
+
+            solver.add_clause(&[!activation]);
+            solver.remove_xors(&switches);
+            solver.simplify();
+            assert!(solver.xors.is_empty());
+            assert_eq!(mentions(&solver), 0);
+            assert!(solver.learnts.iter().all(|&clause| !solver.clauses[clause].literals.contains(&!activation)));
+        }
+        assert!(mentioned > 0, "no learned clause depends on the XOR constraints");
+    }
+}
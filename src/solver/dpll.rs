@@ -0,0 +1,193 @@
+use std::time::Instant;
+
+use crate::formula::Literal;
+
+fn literal_value(literal: &Literal, values: &[Option<bool>]) -> Option<bool> {
+    values[literal.var().index()].map(|value| value != literal.is_negated())
+}
+
+/*
+Unit propagation and pure literal elimination, the variables they
+assign are pushed on `trail`. Returns false when a clause is falsified.
+*/
+fn propagate(clauses: &[Vec<Literal>], values: &mut [Option<bool>], trail: &mut Vec<usize>) -> bool {
+    loop {
+        // Unit propagation
+        let mut changed = false;
+        for clause in clauses {
+            let mut satisfied = false;
+            let mut unassigned = Vec::new();
+            for literal in clause {
+                match literal_value(literal, values) {
+                    Some(true) => {
+                        satisfied = true;
+                        break;
+                    },
+                    Some(false) => {},
+                    None => unassigned.push(*literal)
+                }
+            }
+            if satisfied {
+                continue;
+            }
+            match unassigned.len() {
+                0 => return false,
+                1 => {
+                    let literal: Literal = unassigned[0];
+                    let variable = literal.var().index();
+                    values[variable] = Some(!literal.is_negated());
+                    trail.push(variable);
+                    changed = true;
+                },
+                _ => {}
+            }
+        }
+        if changed {
+            continue;
+        }
+
+        // Pure literal elimination over clauses not yet satisfied
+        let mut polarity: Vec<(bool, bool)> = vec![(false, false); values.len()];
+        for clause in clauses {
+            if clause.iter().any(|l| literal_value(l, values) == Some(true)) {
+                continue;
+            }
+            for literal in clause {
+                let variable = literal.var().index();
+                if values[variable].is_none() {
+                    if literal.is_negated() {
+                        polarity[variable].1 = true;
+                    } else {
+                        polarity[variable].0 = true;
+                    }
+                }
+            }
+        }
+        for (variable, &(positive, negative)) in polarity.iter().enumerate() {
+            if positive != negative {
+                values[variable] = Some(positive);
+                trail.push(variable);
+                changed = true;
+            }
+        }
+        if !changed {
+            return true
+        }
+    }
+}
+
+fn undo(trail: &mut Vec<usize>, length: usize, values: &mut [Option<bool>]) {
+    for variable in trail.drain(length..) {
+        values[variable] = None;
+    }
+}
+
+/*
+Davis-Putnam-Logemann-Loveland search over clauses in conjunctive
+normal form, `values` is indexed by variable.
+
+Returns true and leaves a satisfying assignment into `values`,
+otherwise restores `values` and returns false. Returns None once
+`deadline` has passed, leaving `values` as they were then.
+
+Decisions are kept on a stack of their variable, the length of the
+trail before it and whether false was tried yet, so deep searches do
+not grow the call stack.
+*/
+pub fn dpll(clauses: &[Vec<Literal>], values: &mut [Option<bool>], deadline: Option<Instant>) -> Option<bool> {
+    let mut trail: Vec<usize> = Vec::new();
+    let mut decisions: Vec<(usize, usize, bool)> = Vec::new();
+    loop {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return None
+        }
+        if propagate(clauses, values, &mut trail) {
+            // Branch on the first open variable of an unsatisfied clause
+            let branch = clauses
+                .iter()
+                .filter(|clause| !clause.iter().any(|l| literal_value(l, values) == Some(true)))
+                .flat_map(|clause| clause.iter())
+                .find(|literal| values[literal.var().index()].is_none());
+            let variable = match branch {
+                Some(literal) => literal.var().index(),
+                None => return Some(true)
+            };
+            decisions.push((variable, trail.len(), false));
+            values[variable] = Some(true);
+            trail.push(variable);
+            continue;
+        }
+
+        // Tries false for the innermost decision that has not had it yet
+        loop {
+            match decisions.pop() {
+                Some((variable, length, false)) => {
+                    undo(&mut trail, length, values);
+                    decisions.push((variable, length, true));
+                    values[variable] = Some(false);
+                    trail.push(variable);
+                    break;
+                },
+                Some((_, length, true)) => undo(&mut trail, length, values),
+                None => {
+                    undo(&mut trail, 0, values);
+                    return Some(false)
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::Var;
+
+    /*
+    Pairs of variables that differ, each pair needing its own decision
+    as neither propagation nor pure literals assign it
+    */
+    fn pairs(count: usize) -> Vec<Vec<Literal>> {
+        (0..count)
+            .flat_map(|pair| {
+                let (a, b) = (Var::new(2 * pair), Var::new(2 * pair + 1));
+                vec![vec![a.pos(), b.pos()], vec![a.neg(), b.neg()]]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deep_search_fits_a_small_stack() {
+        let search = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(|| {
+                let mut clauses = pairs(2000);
+                let mut values = vec![None; 4000];
+                assert_eq!(dpll(&clauses, &mut values, None), Some(true));
+                assert!(clauses.iter().all(|clause| clause.iter().any(|l| literal_value(l, &values) == Some(true))));
+
+                // Nothing differs from both variables of the last pair, which the search finds first
+                let (a, b, c) = (Var::new(3998), Var::new(3999), Var::new(4000));
+                let triangle = vec![
+                    vec![a.pos(), c.pos()],
+                    vec![a.neg(), c.neg()],
+                    vec![b.pos(), c.pos()],
+                    vec![b.neg(), c.neg()]
+                ];
+                clauses.splice(0..0, triangle);
+                let mut values = vec![None; 4001];
+                assert_eq!(dpll(&clauses, &mut values, None), Some(false));
+                assert!(values.iter().all(Option::is_none));
+            })
+            .unwrap();
+        search.join().unwrap();
+    }
+
+    #[test]
+    fn expired_deadline_gives_up() {
+        let clauses = pairs(3);
+        let mut values = vec![None; 6];
+        assert_eq!(dpll(&clauses, &mut values, Some(Instant::now())), None);
+    }
+}
@@ -0,0 +1,206 @@
+/*
+DRAT proof output
+
+A proof lists every clause the solver learns and every learned clause
+it deletes, ending with the empty clause when the clauses are refuted.
+Variable n is numbered n + 1 as in DIMACS.
+
+    text      1 -2 0          d 1 -2 0
+    binary    a <literals> 0  d <literals> 0
+
+Binary literals are 2 * number, plus 1 when negated, written as
+variable length integers of 7 bits per byte, low bits first and the
+high bit set on all but the last byte.
+*/
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::formula::Literal;
+
+/// Encoding of DRAT proofs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProofFormat {
+    #[default]
+    Text,
+    Binary
+}
+
+/*
+Proof being written, keeps the first write error for `finish`
+*/
+pub(crate) struct Proof {
+    format: ProofFormat,
+    out: Box<dyn Write>,
+    line: Vec<u8>,
+    error: Option<io::Error>
+}
+
+impl fmt::Debug for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Proof").field("format", &self.format).finish()
+    }
+}
+
+impl Proof {
+    pub fn new(out: Box<dyn Write>, format: ProofFormat) -> Proof {
+        Proof { format, out, line: Vec::new(), error: None }
+    }
+
+    pub fn add(&mut self, literals: &[Literal]) {
+        self.write(false, literals);
+    }
+
+    pub fn delete(&mut self, literals: &[Literal]) {
+        self.write(true, literals);
+    }
+
+    fn write(&mut self, deletion: bool, literals: &[Literal]) {
+        if self.error.is_some() {
+            return
+        }
+        self.line.clear();
+        match self.format {
+            ProofFormat::Text => {
+                if deletion {
+                    self.line.extend_from_slice(b"d ");
+                }
+                for literal in literals {
+                    let number = literal.var().index() + 1;
+                    let sign = if literal.is_negated() { "-" } else { "" };
+                    self.line.extend_from_slice(format!("{}{} ", sign, number).as_bytes());
+                }
+                self.line.extend_from_slice(b"0\n");
+            },
+            ProofFormat::Binary => {
+                self.line.push(if deletion { b'd' } else { b'a' });
+                for literal in literals {
+                    let mut code = 2 * (literal.var().index() as u64 + 1) + literal.is_negated() as u64;
+                    while code > 0x7f {
+                        self.line.push((code & 0x7f) as u8 | 0x80);
+                        code >>= 7;
+                    }
+                    self.line.push(code as u8);
+                }
+                self.line.push(0);
+            }
+        }
+        if let Err(error) = self.out.write_all(&self.line) {
+            self.error = Some(error);
+        }
+    }
+
+    /// Flushes the output, returns the first error writing the proof.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.out.flush()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::cnf::CnfOptions;
+    use crate::formula::{Clause, Operator, SatInstance, Var};
+    use crate::io::proof::parse_drat;
+    use crate::proof::{DratStep, Proof as Steps};
+    use crate::solver::{SolveOptions, SolveResult};
+
+    // Output that stays readable after the proof took it
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Failing;
+
+    impl Write for Failing {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn written(format: ProofFormat, steps: &[(bool, Vec<Literal>)]) -> Vec<u8> {
+        let out = Shared::default();
+        let mut proof = Proof::new(Box::new(out.clone()), format);
+        for (deletion, literals) in steps {
+            if *deletion {
+                proof.delete(literals);
+            } else {
+                proof.add(literals);
+            }
+        }
+        proof.finish().unwrap();
+        let bytes = out.0.borrow().clone();
+        bytes
+    }
+
+    fn pigeonhole(pigeons: usize) -> SatInstance {
+        let holes = pigeons - 1;
+        let mut instance = SatInstance::new();
+        let vars: Vec<Vec<Var>> = (0..pigeons)
+            .map(|pigeon| (0..holes).map(|hole| instance.var(&format!("p{}h{}", pigeon, hole))).collect())
+            .collect();
+        for pigeon in &vars {
+            instance.add_clause(Clause::or(pigeon.iter().map(|var| var.pos()).collect()));
+        }
+        for hole in 0..holes {
+            instance.add_clause(Clause::new(Operator::AtMost(1), vars.iter().map(|pigeon| pigeon[hole].pos()).collect()));
+        }
+        instance
+    }
+
+    #[test]
+    fn steps_are_encoded() {
+        let (x, y) = (Var::new(0), Var::new(63));
+        let steps = [(false, vec![x.pos(), y.neg()]), (true, vec![x.pos(), y.neg()]), (false, Vec::new())];
+        assert_eq!(written(ProofFormat::Text, &steps), b"1 -64 0\nd 1 -64 0\n0\n");
+        // 2 * 64 + 1 takes a second byte
+        assert_eq!(written(ProofFormat::Binary, &steps), b"a\x02\x81\x01\x00d\x02\x81\x01\x00a\x00");
+    }
+
+    #[test]
+    fn proofs_of_refuted_instances_check() {
+        for pigeons in 3..6 {
+            let instance = pigeonhole(pigeons);
+            let mut proofs = Vec::new();
+            for format in [ProofFormat::Text, ProofFormat::Binary] {
+                let out = Shared::default();
+                let (options, cnf) = (SolveOptions::default(), CnfOptions::default());
+                let (result, _) = instance.solve_with_proof(&options, &cnf, Box::new(out.clone()), format).unwrap();
+                assert!(matches!(result, SolveResult::Unsatisfiable));
+                let steps = parse_drat(&out.0.borrow()).unwrap();
+                assert_eq!(steps.last(), Some(&DratStep::Add(Vec::new())));
+                let check = instance.check_proof(&cnf, &Steps::Drat(steps.clone()), false);
+                assert!(check.is_valid(), "{} {:?} {:?}", pigeons, format, check.verdict);
+                proofs.push(steps);
+            }
+            assert_eq!(proofs[0], proofs[1]);
+        }
+    }
+
+    #[test]
+    fn first_write_error_is_returned() {
+        let mut proof = Proof::new(Box::new(Failing), ProofFormat::Text);
+        proof.add(&[Var::new(0).pos()]);
+        proof.delete(&[Var::new(0).pos()]);
+        assert_eq!(proof.finish().unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,283 @@
+/*
+Gauss-Jordan elimination over the XOR constraints of the solver
+
+Every XOR constraint is a row "x1 + x2 + ... = parity" over GF(2),
+stored as a bit set over the columns, one column per variable that
+occurs in some XOR. Negated literals flip the parity.
+
+Under a partial assignment the rows are brought into reduced row
+echelon form, pivoting only on unassigned columns. Rows are combined
+whole, assigned columns included, so every reduced row is itself an
+XOR implied by the constraints. Then
+
+    row without unassigned columns, wrong parity   conflict
+    row whose only unassigned column is its pivot  implies the pivot
+
+and both are explained by the clause of the row's variables that
+is false under the current values, the pivot taking the value that
+satisfies the row. Pivot columns are cleared from all other rows, so
+the implications of one elimination never affect each other. For
+every assignment this finds all consequences of the XOR constraints
+alone, a conflict included whenever they have no solution.
+*/
+use crate::formula::{Literal, Var};
+
+const WORD: usize = 64;
+
+#[derive(Debug, Clone)]
+struct Row {
+    bits: Vec<u64>,
+    parity: bool
+}
+
+impl Row {
+    fn get(&self, column: usize) -> bool {
+        self.bits[column / WORD] >> (column % WORD) & 1 == 1
+    }
+
+    fn flip(&mut self, column: usize) {
+        self.bits[column / WORD] ^= 1 << (column % WORD);
+    }
+
+    fn add(&mut self, other: &Row) {
+        for (word, &other) in self.bits.iter_mut().zip(&other.bits) {
+            *word ^= other;
+        }
+        self.parity ^= other.parity;
+    }
+
+    fn columns(&self) -> impl Iterator<Item = usize> + '_ {
+        self.bits.iter().enumerate().flat_map(|(index, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(index * WORD + bit)
+            })
+        })
+    }
+}
+
+/// Outcome of an elimination.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Propagation {
+    /// Clauses of implied literals, the implied literal first
+    /// and all others false.
+    Implied(Vec<Vec<Literal>>),
+    /// Clause of false literals that the XOR constraints forbid.
+    Conflict(Vec<Literal>)
+}
+
+/// XOR constraints as a matrix over GF(2).
+#[derive(Debug, Clone, Default)]
+pub struct Matrix {
+    columns: Vec<Option<usize>>,
+    vars: Vec<Var>,
+    rows: Vec<Row>
+}
+
+impl Matrix {
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// True when `var` occurs in some XOR constraint.
+    pub fn contains(&self, var: Var) -> bool {
+        self.columns.get(var.index()).is_some_and(Option::is_some)
+    }
+
+    fn column(&mut self, var: Var) -> usize {
+        if self.columns.len() <= var.index() {
+            self.columns.resize(var.index() + 1, None);
+        }
+        if let Some(column) = self.columns[var.index()] {
+            return column
+        }
+        let column = self.vars.len();
+        self.columns[var.index()] = Some(column);
+        self.vars.push(var);
+        let words = self.vars.len().div_ceil(WORD);
+        for row in &mut self.rows {
+            row.bits.resize(words, 0);
+        }
+        column
+    }
+
+    /// Adds the constraint that an odd number of `literals` is true
+    /// when `odd`, an even number otherwise. Returns false when the
+    /// constraint has no variables left and the wrong parity.
+    pub fn add(&mut self, literals: &[Literal], odd: bool) -> bool {
+        let columns: Vec<usize> = literals.iter().map(|l| self.column(l.var())).collect();
+        let mut row = Row { bits: vec![0; self.vars.len().div_ceil(WORD)], parity: odd };
+        for (&column, literal) in columns.iter().zip(literals) {
+            row.flip(column);
+            row.parity ^= literal.is_negated();
+        }
+        // Variables listed twice cancel out
+        if row.bits.iter().all(|&word| word == 0) {
+            return !row.parity
+        }
+        self.rows.push(row);
+        true
+    }
+
+    /// Removes the constraints over any of the variables `remove`
+    /// picks, their columns stay.
+    pub fn remove<F: Fn(Var) -> bool>(&mut self, remove: F) {
+        let vars = &self.vars;
+        self.rows.retain(|row| !row.columns().any(|column| remove(vars[column])));
+    }
+
+    /// Eliminates under the assignment `value` gives.
+    pub fn eliminate<F: Fn(Var) -> Option<bool>>(&self, value: F) -> Propagation {
+        let mut rows = self.rows.clone();
+        let mut pivots = Vec::new();
+        for column in 0..self.vars.len() {
+            if value(self.vars[column]).is_some() {
+                continue;
+            }
+            let rank = pivots.len();
+            let found = match (rank..rows.len()).find(|&r| rows[r].get(column)) {
+                Some(found) => found,
+                None => continue
+            };
+            rows.swap(rank, found);
+            let pivot = rows[rank].clone();
+            for (index, row) in rows.iter_mut().enumerate() {
+                if index != rank && row.get(column) {
+                    row.add(&pivot);
+                }
+            }
+            pivots.push(column);
+        }
+
+        // Variables of `row` other than `skip` with their false literals
+        let falsified = |row: &Row, skip: Option<usize>, clause: &mut Vec<Literal>| -> bool {
+            let mut parity = false;
+            for column in row.columns().filter(|&c| Some(c) != skip) {
+                let var = self.vars[column];
+                let value = value(var).unwrap();
+                parity ^= value;
+                clause.push(Literal::new(var, value));
+            }
+            parity
+        };
+
+        for row in &rows[pivots.len()..] {
+            let mut clause = Vec::new();
+            if falsified(row, None, &mut clause) != row.parity {
+                return Propagation::Conflict(clause)
+            }
+        }
+        let mut implied = Vec::new();
+        for (row, &pivot) in rows.iter().zip(&pivots) {
+            let unassigned = row.columns().filter(|&c| value(self.vars[c]).is_none()).count();
+            if unassigned > 1 {
+                continue;
+            }
+            let mut clause = vec![Literal::pos(self.vars[pivot])];
+            let assigned = falsified(row, Some(pivot), &mut clause);
+            if assigned == row.parity {
+                clause[0] = !clause[0];
+            }
+            implied.push(clause);
+        }
+        Propagation::Implied(implied)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars<const N: usize>() -> [Var; N] {
+        std::array::from_fn(Var::new)
+    }
+
+    fn unassigned(_: Var) -> Option<bool> {
+        None
+    }
+
+    #[test]
+    fn variables_listed_twice_cancel_out() {
+        let [x, y] = vars();
+        let mut matrix = Matrix::default();
+        assert!(!matrix.add(&[x.pos(), x.pos()], true));
+        assert!(matrix.add(&[x.pos(), x.pos()], false));
+        assert!(matrix.add(&[x.pos(), x.neg()], true));
+        assert!(!matrix.add(&[x.neg(), x.pos()], false));
+        assert!(matrix.is_empty());
+
+        assert!(matrix.add(&[x.pos(), y.neg(), x.pos()], false));
+        assert_eq!(matrix.eliminate(unassigned), Propagation::Implied(vec![vec![y.pos()]]));
+    }
+
+    #[test]
+    fn elimination_implies_pivots() {
+        let [x, y, z] = vars();
+        let mut matrix = Matrix::default();
+        assert!(matrix.add(&[x.pos(), y.pos(), z.pos()], true));
+        assert!(matrix.add(&[y.pos(), z.neg()], true));
+        assert_eq!(matrix.eliminate(unassigned), Propagation::Implied(vec![vec![x.pos()]]));
+
+        // y = z follows once z is assigned, explained by the value of z
+        let value = |var: Var| if var == z { Some(true) } else { None };
+        assert_eq!(matrix.eliminate(value), Propagation::Implied(vec![vec![x.pos()], vec![y.pos(), z.neg()]]));
+        let value = |var: Var| if var == z { Some(false) } else { None };
+        assert_eq!(matrix.eliminate(value), Propagation::Implied(vec![vec![x.pos()], vec![y.neg(), z.pos()]]));
+    }
+
+    #[test]
+    fn elimination_finds_conflicts() {
+        let [x, y, z] = vars();
+        let mut matrix = Matrix::default();
+        assert!(matrix.add(&[x.pos(), y.pos()], true));
+        assert!(matrix.add(&[y.pos(), z.pos()], true));
+        assert!(matrix.add(&[x.pos(), z.pos()], true));
+        assert_eq!(matrix.eliminate(unassigned), Propagation::Conflict(Vec::new()));
+
+        let mut matrix = Matrix::default();
+        assert!(matrix.add(&[x.pos(), y.pos()], true));
+        assert!(matrix.add(&[y.pos(), z.pos()], false));
+        let value = |var: Var| Some(var != y);
+        assert_eq!(matrix.eliminate(value), Propagation::Conflict(vec![y.pos(), z.neg()]));
+    }
+
+    #[test]
+    fn removed_constraints_keep_their_columns() {
+        let [x, y, z] = vars();
+        let mut matrix = Matrix::default();
+        assert!(matrix.add(&[x.pos(), y.pos()], true));
+        assert!(matrix.add(&[y.pos(), z.pos()], false));
+        matrix.remove(|var| var == z);
+        assert!(matrix.contains(z));
+        let value = |var: Var| if var == x { Some(true) } else { None };
+        assert_eq!(matrix.eliminate(value), Propagation::Implied(vec![vec![y.neg(), x.neg()]]));
+
+        matrix.remove(|var| var == y);
+        assert!(matrix.is_empty());
+        assert!(matrix.contains(x));
+    }
+
+    #[test]
+    fn rows_span_several_words() {
+        let vars: [Var; 100] = vars();
+        let mut matrix = Matrix::default();
+        for pair in vars.windows(2) {
+            assert!(matrix.add(&[pair[0].pos(), pair[1].pos()], false));
+        }
+        assert!(matrix.add(&[vars[99].pos()], true));
+        let implied: Vec<Vec<Literal>> = vars.iter().map(|var| vec![var.pos()]).collect();
+        assert_eq!(matrix.eliminate(unassigned), Propagation::Implied(implied));
+
+        let value = |var: Var| if var == vars[70] { Some(false) } else { None };
+        match matrix.eliminate(value) {
+            Propagation::Conflict(clause) => assert_eq!(clause, vec![vars[70].pos()]),
+            propagation => panic!("{:?}", propagation)
+        }
+    }
+}
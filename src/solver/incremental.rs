@@ -0,0 +1,407 @@
+/*
+Incremental solving over named variables
+
+One CDCL solver lives as long as the IncrementalSolver, constraints are
+added to it as they come and it keeps its learned clauses, activities
+and phases between solves. Clauses without a native form are lowered
+into CNF on their own, with their auxiliary variables allocated as
+internal variables of the VarTable so later clauses never reuse them.
+
+Every scope has an activation literal, a fresh variable, which the
+constraints added in the scope depend on as selectors do in
+`SatInstance::load`, and which every solve assumes while the scope is
+open. Popping the scope makes it false for good. Learned clauses only
+depend on the constraints of a scope through its activation literal,
+which conflict analysis never resolves away as it is a decision, so
+they contain its negation as well and `cdcl::Solver::simplify` removes
+them together with the constraints.
+
+XOR constraints are the exception, they depend on the activation
+literal through a switch variable of their own that only occurs in
+them and in the clause that turns them off. Learned clauses can
+mention the switch instead, so popping the scope removes its XOR
+constraints and those clauses by their switches.
+*/
+use crate::cnf::Cnf;
+use crate::formula::{Clause, Literal, SatInstance, Var, VarTable};
+use crate::pb::{Overflow, PbConstraint};
+use super::{add_constraint, add_pb_constraint, cdcl, complete, new_solver, SolveOptions, SolveResult, Stats};
+
+/// Solver that takes constraints between solves and solves
+/// under assumptions, keeping what it learned.
+///
+/// ```
+/// use solver::{Clause, IncrementalSolver, SolveResult};
+///
+/// let mut solver = IncrementalSolver::new();
+/// let (a, b) = (solver.var("a"), solver.var("b"));
+/// solver.add_clause(&Clause::or(vec![a.pos(), b.pos()]));
+/// assert!(matches!(solver.solve_with_assumptions(&[a.neg()]), SolveResult::Satisfiable(_)));
+///
+/// solver.add_clause(&Clause::or(vec![a.neg(), b.neg()]));
+/// let result = solver.solve_with_assumptions(&[a.pos(), b.pos()]);
+/// assert!(matches!(result, SolveResult::Unsatisfiable));
+/// assert_eq!(solver.failed_assumptions().len(), 2);
+/// assert!(matches!(solver.solve(), SolveResult::Satisfiable(_)));
+///
+/// solver.push();
+/// solver.add_clause(&Clause::and(vec![a.pos(), b.pos()]));
+/// assert!(matches!(solver.solve(), SolveResult::Unsatisfiable));
+/// solver.pop();
+/// assert!(matches!(solver.solve(), SolveResult::Satisfiable(_)));
+/// ```
+#[derive(Debug)]
+pub struct IncrementalSolver {
+    /// Variables of the constraints, auxiliary ones internal.
+    pub vars: VarTable,
+    solver: cdcl::Solver,
+    // Open scopes, innermost last
+    scopes: Vec<Scope>,
+    failed: Vec<Literal>
+}
+
+/*
+Activation literal of a scope and the switch variables
+of the XOR constraints added in it
+*/
+#[derive(Debug, Clone)]
+struct Scope {
+    activation: Literal,
+    switches: Vec<Var>
+}
+
+impl Default for IncrementalSolver {
+    fn default() -> IncrementalSolver {
+        IncrementalSolver::new()
+    }
+}
+
+impl IncrementalSolver {
+    pub fn new() -> IncrementalSolver {
+        IncrementalSolver::with_options(&SolveOptions::default())
+    }
+
+    /// Solver with the time limit of `options` for every solve.
+    pub fn with_options(options: &SolveOptions) -> IncrementalSolver {
+        IncrementalSolver {
+            vars: VarTable::new(),
+            solver: new_solver(options),
+            scopes: Vec::new(),
+            failed: Vec::new()
+        }
+    }
+
+    /// Solver holding the constraints of `instance`, with its variables.
+    pub fn from_instance(instance: &SatInstance, options: &SolveOptions) -> IncrementalSolver {
+        let mut solver = IncrementalSolver::with_options(options);
+        solver.vars = instance.vars.clone();
+        for clause in &instance.clauses {
+            solver.add_clause(clause);
+        }
+        for constraint in &instance.pb_constraints {
+            solver.cover(constraint.terms.iter().map(|&(_, literal)| literal));
+            add_pb_constraint(&mut solver.solver, constraint, None);
+        }
+        solver
+    }
+
+    /// Variable called `name`, added on first use.
+    pub fn var(&mut self, name: &str) -> Var {
+        self.vars.intern(name)
+    }
+
+    /*
+    Variables used without a name are named after their numbers, with
+    primes added while another variable has that name
+    */
+    fn cover(&mut self, literals: impl Iterator<Item = Literal>) {
+        if let Some(max_var) = literals.map(|l| l.var().index()).max() {
+            while self.vars.len() <= max_var {
+                let mut name = (self.vars.len() + 1).to_string();
+                while self.vars.lookup(&name).is_some() {
+                    name.push('\'');
+                }
+                self.vars.intern(&name);
+            }
+        }
+    }
+
+    /// Opens a scope, the constraints added until the matching `pop`
+    /// only hold while it is open.
+    pub fn push(&mut self) {
+        let activation = self.vars.fresh("scope").pos();
+        self.scopes.push(Scope { activation, switches: Vec::new() });
+    }
+
+    /// Retracts the constraints of the innermost scope and the clauses
+    /// learned from them. Returns false when no scope is open.
+    pub fn pop(&mut self) -> bool {
+        match self.scopes.pop() {
+            Some(scope) => {
+                self.solver.add_clause(&[!scope.activation]);
+                self.solver.remove_xors(&scope.switches);
+                self.solver.simplify();
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Number of open scopes.
+    pub fn scopes(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Adds `clause` until the innermost scope is popped, for good
+    /// outside scopes. Returns false once the constraints outside
+    /// scopes are known to be unsatisfiable.
+    pub fn add_clause(&mut self, clause: &Clause) -> bool {
+        let selector = self.scopes.last().map(|scope| scope.activation);
+        self.cover(clause.literals.iter().copied().chain(selector));
+        let mut cnf = Cnf::new(self.vars.len());
+        let switch = add_constraint(&mut self.solver, &mut cnf, clause, selector);
+        while self.vars.len() < cnf.vars {
+            self.vars.fresh("aux");
+        }
+        if let (Some(scope), Some(switch)) = (self.scopes.last_mut(), switch) {
+            scope.switches.push(switch);
+        }
+        for clause in &cnf.clauses {
+            self.solver.add_clause(clause);
+        }
+        self.solver.is_ok()
+    }
+
+    /// Adds a pseudo-Boolean constraint as `add_clause` does,
+    /// unless its normal form overflows.
+    pub fn add_pb_constraint(&mut self, constraint: &PbConstraint) -> Result<bool, Overflow> {
+        constraint.normalize()?;
+        self.cover(constraint.terms.iter().map(|&(_, literal)| literal));
+        add_pb_constraint(&mut self.solver, constraint, self.scopes.last().map(|scope| scope.activation));
+        Ok(self.solver.is_ok())
+    }
+
+    pub fn solve(&mut self) -> SolveResult {
+        self.solve_with_assumptions(&[])
+    }
+
+    /// Solves under `assumptions`, which only hold for this call, with
+    /// the constraints of the open scopes. Models leave internal
+    /// variables unassigned. When unsatisfiable, `failed_assumptions`
+    /// tells which assumptions were to blame.
+    pub fn solve_with_assumptions(&mut self, assumptions: &[Literal]) -> SolveResult {
+        self.cover(assumptions.iter().copied());
+        let mut all: Vec<Literal> = self.scopes.iter().map(|scope| scope.activation).collect();
+        let activations = all.len();
+        all.extend_from_slice(assumptions);
+        let status = self.solver.solve_with_assumptions(&all);
+        let scopes = &all[..activations];
+        self.failed = self.solver.failed_assumptions().iter().copied().filter(|l| !scopes.contains(l)).collect();
+        match status {
+            cdcl::Status::Satisfiable => {
+                let mut state = self.solver.model().clone();
+                complete(&mut state, self.vars.len());
+                SolveResult::Satisfiable(state.project(&self.vars))
+            },
+            cdcl::Status::Unsatisfiable => SolveResult::Unsatisfiable,
+            cdcl::Status::Unknown => SolveResult::Unknown
+        }
+    }
+
+    /// Assumptions of the last unsatisfiable solve that are
+    /// unsatisfiable together with the constraints, as the IPASIR
+    /// `failed` call reports them. Empty when the constraints alone
+    /// are unsatisfiable.
+    pub fn failed_assumptions(&self) -> &[Literal] {
+        &self.failed
+    }
+
+    /// True when `literal` is one of the failed assumptions.
+    pub fn failed(&self, literal: Literal) -> bool {
+        self.failed.contains(&literal)
+    }
+
+    /// Asks `terminate` during search whether to give up, solving then
+    /// returns SolveResult::Unknown. None removes the callback.
+    pub fn set_terminate(&mut self, terminate: Option<cdcl::Terminate>) {
+        self.solver.set_terminate(terminate);
+    }
+
+    /// Passes learned clauses of at most `max_length` literals to `learn`,
+    /// which may contain internal variables. None removes the callback.
+    pub fn set_learn(&mut self, max_length: usize, learn: Option<cdcl::Learn>) {
+        self.solver.set_learn(max_length, learn);
+    }
+
+    /// Search statistics summed over all solves.
+    pub fn stats(&self) -> &Stats {
+        &self.solver.stats
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assignment::InstanceState;
+    use crate::formula::Operator;
+
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn literals(&mut self, vars: usize, len: usize) -> Vec<Literal> {
+            (0..len).map(|_| Literal::new(Var::new(self.below(vars)), self.below(2) == 1)).collect()
+        }
+    }
+
+    fn satisfiable(clauses: &[Clause], vars: usize) -> bool {
+        (0..1u32 << vars).any(|bits| {
+            let values: Vec<bool> = (0..vars).map(|var| bits >> var & 1 == 1).collect();
+            let state = InstanceState::from_values(&values);
+            clauses.iter().all(|clause| clause.satisfied_by(&state))
+        })
+    }
+
+    /*
+    Random pushes, pops and constraints, each solve compared with
+    brute force over the constraints of the open scopes
+    */
+    fn check_scopes(seed: u64, operators: &[fn(&mut Random) -> Operator]) {
+        let vars = 8;
+        let mut random = Random(seed);
+        for round in 0..60 {
+            let mut solver = IncrementalSolver::new();
+            for var in 0..vars {
+                solver.var(&format!("x{}", var));
+            }
+            let mut scopes: Vec<Vec<Clause>> = vec![Vec::new()];
+            for _ in 0..40 {
+                match random.below(6) {
+                    0 => {
+                        solver.push();
+                        scopes.push(Vec::new());
+                    },
+                    1 if scopes.len() > 1 => {
+                        assert!(solver.pop());
+                        scopes.pop();
+                    },
+                    _ => {
+                        let operator = operators[random.below(operators.len())](&mut random);
+                        let len = 2 + random.below(3);
+                        let clause = Clause::new(operator, random.literals(vars, len));
+                        solver.add_clause(&clause);
+                        scopes.last_mut().unwrap().push(clause);
+                    }
+                }
+                let clauses: Vec<Clause> = scopes.iter().flatten().cloned().collect();
+                let expected = satisfiable(&clauses, vars);
+                match solver.solve() {
+                    SolveResult::Satisfiable(model) => {
+                        assert!(expected, "round {}", round);
+                        assert!(clauses.iter().all(|clause| clause.satisfied_by(&model)), "round {}", round);
+                    },
+                    SolveResult::Unsatisfiable => assert!(!expected, "round {}", round),
+                    SolveResult::Unknown => unreachable!()
+                }
+                // Outer constraints that are unsatisfiable stay that way
+                if !expected && scopes.len() == 1 {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn xor_constraints_in_scopes() {
+        check_scopes(0x243f_6a88_85a3_08d3, &[|_| Operator::OR, |_| Operator::XOR, |_| Operator::XNOR]);
+    }
+
+    #[test]
+    fn cardinality_constraints_in_scopes() {
+        check_scopes(0x1319_8a2e_0370_7344, &[
+            |_| Operator::OR,
+            |random| Operator::AtMost(random.below(3)),
+            |random| Operator::AtLeast(1 + random.below(3)),
+            |random| Operator::Exactly(1 + random.below(2))
+        ]);
+    }
+
+    #[test]
+    fn unnamed_variables_avoid_taken_numbers() {
+        let mut solver = IncrementalSolver::new();
+        let two = solver.var("2");
+        let other = Var::new(1);
+        solver.add_clause(&Clause::or(vec![two.pos(), other.pos()]));
+        solver.add_clause(&Clause::or(vec![two.neg(), other.neg()]));
+        assert_eq!(solver.vars.len(), 2);
+        assert_eq!(solver.vars.name(other), "2'");
+        solver.add_clause(&Clause::and(vec![Var::new(2).pos()]));
+        assert_eq!(solver.vars.name(Var::new(2)), "3");
+        match solver.solve() {
+            SolveResult::Satisfiable(model) => assert_ne!(model.value(two), model.value(other)),
+            _ => panic!("not satisfiable")
+        }
+    }
+
+    #[test]
+    fn user_variables_named_like_activations_are_their_own() {
+        let mut solver = IncrementalSolver::new();
+        let a = solver.var("a");
+        solver.push();
+        let scope = solver.var("scope1");
+        solver.add_clause(&Clause::and(vec![a.pos(), scope.neg()]));
+        assert!(matches!(solver.solve(), SolveResult::Satisfiable(_)));
+        assert!(solver.pop());
+        solver.add_clause(&Clause::and(vec![scope.pos()]));
+        assert!(matches!(solver.solve(), SolveResult::Satisfiable(_)));
+        assert_eq!(solver.vars.lookup("scope1"), Some(scope));
+    }
+
+    #[test]
+    fn user_variables_named_like_auxiliary_ones_are_their_own() {
+        let mut solver = IncrementalSolver::new();
+        let (a, b) = (solver.var("a"), solver.var("b"));
+        solver.push();
+        solver.add_clause(&Clause::xor(vec![a.pos(), b.pos()]));
+        let aux = solver.vars.vars().find(|&var| solver.vars.name(var).starts_with("aux")).unwrap();
+        let name = solver.vars.name(aux).to_string();
+        let user = solver.var(&name);
+        assert_ne!(user, aux);
+        assert!(solver.vars.is_internal(aux));
+
+        // The switch of the XOR constraint is off while the scope is open
+        solver.add_clause(&Clause::and(vec![user.pos()]));
+        match solver.solve() {
+            SolveResult::Satisfiable(model) => assert_eq!(model.value(user), Some(true)),
+            _ => panic!("not satisfiable")
+        }
+    }
+
+    #[test]
+    fn pop_removes_xor_constraints() {
+        let mut solver = IncrementalSolver::new();
+        let (a, b) = (solver.var("a"), solver.var("b"));
+        for _ in 0..3 {
+            solver.push();
+            solver.add_clause(&Clause::xor(vec![a.pos(), b.pos()]));
+            solver.add_clause(&Clause::and(vec![a.pos()]));
+            solver.push();
+            solver.add_clause(&Clause::xor(vec![a.pos(), b.neg()]));
+            assert!(matches!(solver.solve(), SolveResult::Unsatisfiable));
+            assert!(solver.pop());
+            assert!(matches!(solver.solve(), SolveResult::Satisfiable(_)));
+            assert!(solver.pop());
+            assert!(solver.solver.xors.is_empty());
+            solver.add_clause(&Clause::and(vec![b.neg()]));
+            assert!(matches!(solver.solve(), SolveResult::Satisfiable(_)));
+        }
+        assert!(!solver.pop());
+    }
+}
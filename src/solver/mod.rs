@@ -0,0 +1,279 @@
+/*
+Solving entry points for SatInstance
+
+The default solver is conflict driven clause learning (see `cdcl`),
+plain DPLL is kept as a simple reference implementation.
+*/
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::assignment::InstanceState;
+use crate::cnf::{Cnf, CnfOptions};
+use crate::formula::{Clause, Literal, Operator, SatInstance, Var};
+use crate::pb::PbConstraint;
+
+pub mod cdcl;
+mod dpll;
+mod drat;
+mod gauss;
+mod incremental;
+
+pub use cdcl::Stats;
+pub use drat::ProofFormat;
+pub use incremental::IncrementalSolver;
+
+/// Outcome of solving an instance.
+#[derive(Debug, Clone)]
+pub enum SolveResult {
+    /// Model assigning every variable of the instance,
+    /// except internal ones which are left unassigned.
+    Satisfiable(InstanceState),
+    Unsatisfiable,
+    /// The time limit ran out before an answer was found.
+    Unknown
+}
+
+/// Limits and tuning for `SatInstance::solve_with`.
+#[derive(Debug, Clone, Default)]
+pub struct SolveOptions {
+    pub time_limit: Option<Duration>,
+    /// Seed for breaking ties in the variable order, 0 keeps input order.
+    pub seed: u64
+}
+
+impl SatInstance {
+    /// Solves the instance with the conflict driven clause learning solver.
+    pub fn solve(&self) -> SolveResult {
+        self.solve_with(&SolveOptions::default()).0
+    }
+
+    /// Solves the instance with `options`, also returning search statistics.
+    pub fn solve_with(&self, options: &SolveOptions) -> (SolveResult, Stats) {
+        let mut solver = new_solver(options);
+        let (vars, _) = self.load(&mut solver, false);
+
+        let result = match solver.solve() {
+            cdcl::Status::Satisfiable => {
+                let mut state = solver.model().clone();
+                complete(&mut state, vars);
+                SolveResult::Satisfiable(state.project(&self.vars))
+            },
+            cdcl::Status::Unsatisfiable => SolveResult::Unsatisfiable,
+            cdcl::Status::Unknown => SolveResult::Unknown
+        };
+        (result, solver.stats)
+    }
+
+    /// Solves the instance like `solve_with`. When it is unsatisfiable,
+    /// also returns the indices of a subset of `clauses` that is
+    /// unsatisfiable together with the pseudo-Boolean constraints,
+    /// which always take part.
+    ///
+    /// ```
+    /// use solver::{SatInstance, SolveOptions};
+    ///
+    /// let instance = SatInstance::builder()
+    ///     .or(&["a", "b"])
+    ///     .and(&["!a"])
+    ///     .or(&["c"])
+    ///     .and(&["!b"])
+    ///     .build();
+    /// let (_, core, _) = instance.solve_with_core(&SolveOptions::default());
+    /// assert_eq!(core, Some(vec![0, 1, 3]));
+    /// ```
+    pub fn solve_with_core(&self, options: &SolveOptions) -> (SolveResult, Option<Vec<usize>>, Stats) {
+        let mut solver = new_solver(options);
+        let (vars, selectors) = self.load(&mut solver, true);
+
+        let mut core = None;
+        let result = match solver.solve_with_assumptions(&selectors) {
+            cdcl::Status::Satisfiable => {
+                let mut state = solver.model().clone();
+                complete(&mut state, vars);
+                SolveResult::Satisfiable(state.project(&self.vars))
+            },
+            cdcl::Status::Unsatisfiable => {
+                let mut failed = vec![false; vars];
+                for literal in solver.failed_assumptions() {
+                    failed[literal.var().index()] = true;
+                }
+                core = Some((0..selectors.len()).filter(|&k| failed[selectors[k].var().index()]).collect());
+                SolveResult::Unsatisfiable
+            },
+            cdcl::Status::Unknown => SolveResult::Unknown
+        };
+        (result, core, solver.stats)
+    }
+
+    /// Indices of a subset of `clauses` that is unsatisfiable,
+    /// None when the instance is satisfiable.
+    pub fn unsat_core(&self) -> Option<Vec<usize>> {
+        self.solve_with_core(&SolveOptions::default()).1
+    }
+
+    /// Solves the CNF that `io::dimacs::write_with` writes for `cnf`
+    /// while writing a DRAT proof to `proof`, which refutes that CNF
+    /// when the instance is unsatisfiable. Proof variables are numbered
+    /// as in the DIMACS output.
+    pub fn solve_with_proof(
+        &self,
+        options: &SolveOptions,
+        cnf: &CnfOptions,
+        proof: Box<dyn Write>,
+        format: ProofFormat
+    ) -> io::Result<(SolveResult, Stats)> {
+        let mut solver = new_solver(options);
+        solver.set_proof(proof, format);
+        let cnf = self.to_cnf_with(cnf);
+        for clause in &cnf.clauses {
+            solver.add_clause(clause);
+        }
+
+        let result = match solver.solve() {
+            cdcl::Status::Satisfiable => {
+                let mut state = solver.model().clone();
+                complete(&mut state, cnf.vars);
+                SolveResult::Satisfiable(state.project(&self.vars))
+            },
+            cdcl::Status::Unsatisfiable => SolveResult::Unsatisfiable,
+            cdcl::Status::Unknown => SolveResult::Unknown
+        };
+        solver.finish_proof()?;
+        Ok((result, solver.stats))
+    }
+
+    /*
+    Adds the constraints of the instance to `solver`, cardinality, XOR
+    and pseudo-Boolean constraints natively and the others lowered into
+    CNF. Returns the number of variables including auxiliary ones.
+
+    When `selected`, clause k only holds while the selector literal at
+    index k of the returned list is true, as in
+
+        AtLeast(k)      k !s + sum of literals >= k
+        XOR             XOR of the literals and t, !s | !t
+        lowered         !s added to each clause
+
+    with fresh variables s and t. Pseudo-Boolean constraints always hold.
+    */
+    pub(crate) fn load(&self, solver: &mut cdcl::Solver, selected: bool) -> (usize, Vec<Literal>) {
+        let mut cnf = Cnf::new(self.used_vars());
+        let mut selectors = Vec::new();
+        for clause in &self.clauses {
+            let selector = if selected {
+                let selector = cnf.fresh().pos();
+                selectors.push(selector);
+                Some(selector)
+            } else {
+                None
+            };
+            add_constraint(solver, &mut cnf, clause, selector);
+        }
+        for constraint in &self.pb_constraints {
+            add_pb_constraint(solver, constraint, None);
+        }
+        for clause in &cnf.clauses {
+            solver.add_clause(clause);
+        }
+        (cnf.vars, selectors)
+    }
+
+    /// Solves the instance with plain DPLL search.
+    pub fn solve_dpll(&self) -> SolveResult {
+        self.solve_dpll_with(&SolveOptions::default())
+    }
+
+    /// Solves the instance with DPLL search within the time limit of
+    /// `options`, the seed is not used.
+    pub fn solve_dpll_with(&self, options: &SolveOptions) -> SolveResult {
+        let deadline = options.time_limit.and_then(|limit| Instant::now().checked_add(limit));
+        let cnf = self.to_cnf();
+        let mut values: Vec<Option<bool>> = vec![None; cnf.vars];
+        match dpll::dpll(&cnf.clauses, &mut values, deadline) {
+            Some(true) => {},
+            Some(false) => return SolveResult::Unsatisfiable,
+            None => return SolveResult::Unknown
+        }
+
+        // Variables left open by the search can take either value
+        let values: Vec<bool> = values.into_iter().map(|value| value.unwrap_or(false)).collect();
+        SolveResult::Satisfiable(InstanceState::from_values(&values).project(&self.vars))
+    }
+}
+
+
+/*
+Adds `clause` to `solver` if it has a native form, otherwise lowers it
+into `cnf` for the caller to add, see `SatInstance::load`. Returns the
+switch variable t of a selected XOR constraint.
+*/
+fn add_constraint(solver: &mut cdcl::Solver, cnf: &mut Cnf, clause: &Clause, selector: Option<Literal>) -> Option<Var> {
+    let literals = &clause.literals;
+    let negated: Vec<Literal> = literals.iter().map(|&l| !l).collect();
+    let at_most = |k: usize| literals.len().saturating_sub(k);
+    let mut at_least = |literals: &[Literal], k: usize| {
+        let mut terms: Vec<(u64, Literal)> = literals.iter().map(|&l| (1, l)).collect();
+        terms.extend(selector.map(|s| (k as u64, !s)));
+        solver.add_linear(&terms, k as i64);
+    };
+    match clause.operator {
+        Operator::AtLeast(k) => at_least(literals, k),
+        Operator::AtMost(k) => at_least(&negated, at_most(k)),
+        Operator::Exactly(k) => {
+            at_least(literals, k);
+            at_least(&negated, at_most(k));
+        },
+        Operator::XOR | Operator::XNOR => {
+            let mut literals = literals.clone();
+            let switch = selector.map(|selector| {
+                let switch = cnf.fresh();
+                literals.push(switch.pos());
+                cnf.clauses.push(vec![!selector, switch.neg()]);
+                switch
+            });
+            solver.add_xor(&literals, clause.operator == Operator::XOR);
+            return switch
+        },
+        _ => {
+            let start = cnf.clauses.len();
+            cnf.add_clause(clause, &CnfOptions::default());
+            for lowered in &mut cnf.clauses[start..] {
+                lowered.extend(selector.map(|s| !s));
+            }
+        }
+    }
+    None
+}
+
+fn add_pb_constraint(solver: &mut cdcl::Solver, constraint: &PbConstraint, selector: Option<Literal>) {
+    for normalized in constraint.normalized() {
+        let (mut terms, bound) = (normalized.terms, normalized.bound);
+        if bound > 0 {
+            terms.extend(selector.map(|s| (bound as u64, !s)));
+        }
+        solver.add_linear(&terms, bound);
+    }
+}
+
+fn new_solver(options: &SolveOptions) -> cdcl::Solver {
+    let mut solver = cdcl::Solver::new();
+    if let Some(limit) = options.time_limit {
+        solver.set_time_limit(limit);
+    }
+    solver.set_seed(options.seed);
+    solver
+}
+
+/*
+Assigns false to the variables the solver never saw,
+such as ones that only appear in the VarTable
+*/
+fn complete(state: &mut InstanceState, vars: usize) {
+    state.grow(vars);
+    for index in 0..vars {
+        let var = Var::new(index);
+        if !state.is_assigned(var) {
+            state.assign(var.neg());
+        }
+    }
+}
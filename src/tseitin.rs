@@ -0,0 +1,275 @@
+/*
+Lowering of general formulas into clauses
+
+Every compound subformula gets an auxiliary variable t and clauses
+defining t <-> subformula (Tseitin). Plaisted-Greenbaum only keeps the
+direction the polarity of the subformula needs: t -> subformula where
+it occurs positively and subformula -> t where it occurs negatively,
+which is equisatisfiable with roughly half the clauses.
+
+Auxiliary variables are internal variables of the VarTable, so
+models are projected back onto the variables of the formula and
+names interned later never refer to them.
+*/
+use crate::expression::Formula;
+use crate::formula::{Clause, Literal, SatInstance, VarTable};
+
+/// How `SatInstance::add_formula` defines its auxiliary variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Both directions of every definition.
+    Tseitin,
+    /// Only the direction the polarity of each subformula needs.
+    PlaistedGreenbaum
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Polarity {
+    Positive,
+    Negative,
+    Both
+}
+
+impl Polarity {
+    fn flip(self) -> Polarity {
+        match self {
+            Polarity::Positive => Polarity::Negative,
+            Polarity::Negative => Polarity::Positive,
+            Polarity::Both => Polarity::Both
+        }
+    }
+
+    fn positive(self) -> bool {
+        self != Polarity::Negative
+    }
+
+    fn negative(self) -> bool {
+        self != Polarity::Positive
+    }
+}
+
+struct Encoder<'a> {
+    instance: &'a mut SatInstance,
+    encoding: Encoding,
+    truth: Option<Literal>
+}
+
+impl<'a> Encoder<'a> {
+    fn clause(&mut self, literals: Vec<Literal>) {
+        self.instance.add_clause(Clause::or(literals));
+    }
+
+    fn fresh(&mut self) -> Literal {
+        self.instance.vars.fresh("_t").pos()
+    }
+
+    fn constant(&mut self, value: bool) -> Literal {
+        let truth = match self.truth {
+            Some(truth) => truth,
+            None => {
+                let truth = self.fresh();
+                self.clause(vec![truth]);
+                self.truth = Some(truth);
+                truth
+            }
+        };
+        if value { truth } else { !truth }
+    }
+
+    /*
+    Literal equivalent to `formula` as far as `polarity` requires
+    */
+    fn encode(&mut self, formula: &Formula, polarity: Polarity) -> Literal {
+        let polarity = match self.encoding {
+            Encoding::Tseitin => Polarity::Both,
+            Encoding::PlaistedGreenbaum => polarity
+        };
+
+        match formula {
+            Formula::Const(value) => self.constant(*value),
+            Formula::Var(var) => var.pos(),
+            Formula::Not(formula) => !self.encode(formula, polarity.flip()),
+            Formula::And(formulas) => {
+                let literals: Vec<Literal> = formulas
+                    .iter()
+                    .map(|f| self.encode(f, polarity))
+                    .collect();
+                let t = self.fresh();
+                if polarity.positive() {
+                    for &literal in &literals {
+                        self.clause(vec![!t, literal]);
+                    }
+                }
+                if polarity.negative() {
+                    let mut clause: Vec<Literal> = literals.iter().map(|&l| !l).collect();
+                    clause.push(t);
+                    self.clause(clause);
+                }
+                t
+            },
+            Formula::Or(formulas) => {
+                let literals: Vec<Literal> = formulas
+                    .iter()
+                    .map(|f| self.encode(f, polarity))
+                    .collect();
+                let t = self.fresh();
+                if polarity.positive() {
+                    let mut clause = literals.clone();
+                    clause.push(!t);
+                    self.clause(clause);
+                }
+                if polarity.negative() {
+                    for &literal in &literals {
+                        self.clause(vec![t, !literal]);
+                    }
+                }
+                t
+            },
+            Formula::Xor(a, b) => {
+                let a = self.encode(a, Polarity::Both);
+                let b = self.encode(b, Polarity::Both);
+                let t = self.fresh();
+                if polarity.positive() {
+                    self.clause(vec![!t, a, b]);
+                    self.clause(vec![!t, !a, !b]);
+                }
+                if polarity.negative() {
+                    self.clause(vec![t, !a, b]);
+                    self.clause(vec![t, a, !b]);
+                }
+                t
+            },
+            Formula::Iff(a, b) => {
+                let a = self.encode(a, Polarity::Both);
+                let b = self.encode(b, Polarity::Both);
+                let t = self.fresh();
+                if polarity.positive() {
+                    self.clause(vec![!t, !a, b]);
+                    self.clause(vec![!t, a, !b]);
+                }
+                if polarity.negative() {
+                    self.clause(vec![t, a, b]);
+                    self.clause(vec![t, !a, !b]);
+                }
+                t
+            },
+            Formula::Implies(a, b) => {
+                let a = self.encode(a, polarity.flip());
+                let b = self.encode(b, polarity);
+                let t = self.fresh();
+                if polarity.positive() {
+                    self.clause(vec![!t, !a, b]);
+                }
+                if polarity.negative() {
+                    self.clause(vec![t, a]);
+                    self.clause(vec![t, !b]);
+                }
+                t
+            },
+            Formula::Ite(condition, then, otherwise) => {
+                let condition = self.encode(condition, Polarity::Both);
+                let then = self.encode(then, polarity);
+                let otherwise = self.encode(otherwise, polarity);
+                let t = self.fresh();
+                if polarity.positive() {
+                    self.clause(vec![!t, !condition, then]);
+                    self.clause(vec![!t, condition, otherwise]);
+                }
+                if polarity.negative() {
+                    self.clause(vec![t, !condition, !then]);
+                    self.clause(vec![t, condition, !otherwise]);
+                }
+                t
+            }
+        }
+    }
+
+    /*
+    Adds clauses that force `formula` true, conjunctions and
+    disjunctions at the top need no auxiliary variable of their own
+    */
+    fn assert(&mut self, formula: &Formula) {
+        match formula {
+            Formula::And(formulas) => {
+                for formula in formulas {
+                    self.assert(formula);
+                }
+            },
+            Formula::Or(formulas) => {
+                let literals = formulas
+                    .iter()
+                    .map(|f| self.encode(f, Polarity::Positive))
+                    .collect();
+                self.clause(literals);
+            },
+            Formula::Const(true) => {},
+            Formula::Const(false) => self.clause(Vec::new()),
+            formula => {
+                let literal = self.encode(formula, Polarity::Positive);
+                self.clause(vec![literal]);
+            }
+        }
+    }
+}
+
+impl SatInstance {
+    /// Adds clauses that are satisfiable exactly when `formula` is,
+    /// introducing internal variables as `encoding` needs.
+    ///
+    /// ```
+    /// use solver::{Encoding, Formula, SatInstance, SolveResult, Truth};
+    ///
+    /// let mut instance = SatInstance::new();
+    /// let a = Formula::var(instance.var("a"));
+    /// let b = Formula::var(instance.var("b"));
+    /// let formula = Formula::and(vec![Formula::xor(a.clone(), b), Formula::not(a)]);
+    /// instance.add_formula(&formula, Encoding::PlaistedGreenbaum);
+    ///
+    /// match instance.solve() {
+    ///     SolveResult::Satisfiable(model) => assert_eq!(formula.evaluate(&model), Truth::True),
+    ///     _ => unreachable!()
+    /// }
+    /// ```
+    pub fn add_formula(&mut self, formula: &Formula, encoding: Encoding) {
+        let mut encoder = Encoder { instance: self, encoding, truth: None };
+        encoder.assert(formula);
+    }
+
+    /// Instance of the clauses of `formula` over the variables of `vars`.
+    pub fn from_formula(formula: &Formula, vars: VarTable, encoding: Encoding) -> SatInstance {
+        let mut instance = SatInstance { vars, ..SatInstance::default() };
+        instance.add_formula(formula, encoding);
+        instance
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::Var;
+    use crate::solver::SolveResult;
+
+    #[test]
+    fn variables_named_like_auxiliary_ones_are_their_own() {
+        for encoding in [Encoding::Tseitin, Encoding::PlaistedGreenbaum] {
+            for value in [false, true] {
+                let mut instance = SatInstance::new();
+                let a = Formula::var(instance.var("a"));
+                let b = Formula::var(instance.var("b"));
+                instance.add_formula(&Formula::and(vec![Formula::xor(a.clone(), b), a]), encoding);
+                let auxiliary: Vec<Var> = instance.vars.vars().filter(|&var| instance.vars.is_internal(var)).collect();
+                assert!(!auxiliary.is_empty());
+
+                // Units over variables taking the names of the auxiliary ones
+                for var in auxiliary {
+                    let name = instance.vars.name(var).to_string();
+                    let user = instance.var(&name);
+                    assert_ne!(user, var);
+                    instance.add_clause(Clause::and(vec![Literal::new(user, !value)]));
+                }
+                assert!(matches!(instance.solve(), SolveResult::Satisfiable(_)), "{:?} {}", encoding, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+/*
+The tests of the Python bindings in python/tests/test_solver.py over
+the Rust API they wrap, so that cargo test covers what they rely on
+without Python. Models are compared by name as the bindings return them.
+*/
+use solver::io::{dimacs, expr};
+use solver::{Clause, InstanceState, Literal, Operator, SatInstance, SolveOptions, SolveResult};
+
+/*
+Values of the variables of the instance that are not internal, in
+variable order, as in the model dict of the bindings
+*/
+fn model(instance: &SatInstance) -> Option<Vec<(String, bool)>> {
+    let vars = &instance.vars;
+    match instance.solve() {
+        SolveResult::Satisfiable(state) => Some(
+            vars.vars()
+                .filter_map(|var| state.value(var).map(|value| (vars.name(var).to_string(), value)))
+                .collect()
+        ),
+        _ => None
+    }
+}
+
+fn named(values: &[(&str, bool)]) -> Option<Vec<(String, bool)>> {
+    Some(values.iter().map(|&(name, value)| (name.to_string(), value)).collect())
+}
+
+fn state(instance: &SatInstance, values: &[(&str, bool)]) -> InstanceState {
+    let mut state = InstanceState::new(instance.vars.len());
+    for &(name, value) in values {
+        state.assign(Literal::new(instance.vars.lookup(name).unwrap(), !value));
+    }
+    state
+}
+
+#[test]
+fn test_model_by_name() {
+    let instance = SatInstance::builder().or(&["a", "b"]).and(&["c", "!b"]).build();
+    assert_eq!(model(&instance), named(&[("a", true), ("b", false), ("c", true)]));
+    assert!(instance.satisfied_by(&state(&instance, &[("a", true), ("b", false), ("c", true)])));
+}
+
+#[test]
+fn test_unsat_core() {
+    let instance = SatInstance::builder()
+        .or(&["a", "b"])
+        .label("a or b")
+        .and(&["!a"])
+        .label("not a")
+        .or(&["c"])
+        .label("c")
+        .and(&["!b"])
+        .label("not b")
+        .build();
+    let (result, core, _) = instance.solve_with_core(&SolveOptions::default());
+    assert!(matches!(result, SolveResult::Unsatisfiable));
+    let core = core.unwrap();
+    assert_eq!(core, vec![0, 1, 3]);
+    let labels: Vec<_> = core.iter().map(|&k| instance.clauses[k].label.as_deref().unwrap()).collect();
+    assert_eq!(labels, vec!["a or b", "not a", "not b"]);
+    assert_eq!(instance.unsat_core(), Some(vec![0, 1, 3]));
+}
+
+#[test]
+fn test_cardinality() {
+    let mut instance = SatInstance::new();
+    let (a, b, c) = (instance.var("a"), instance.var("b"), instance.var("c"));
+    instance.add_clause(Clause::new(Operator::Exactly(2), vec![a.pos(), b.pos(), c.pos()]));
+    instance.add_clause(Clause::and(vec![a.neg()]));
+    assert_eq!(model(&instance), named(&[("a", false), ("b", true), ("c", true)]));
+    instance.add_clause(Clause::new(Operator::AtMost(1), vec![b.pos(), c.pos()]));
+    assert_eq!(model(&instance), None);
+}
+
+#[test]
+fn test_xor_parity() {
+    let instance = SatInstance::builder().xor(&["a", "b", "c"]).and(&["a", "b"]).build();
+    assert_eq!(model(&instance), named(&[("a", true), ("b", true), ("c", true)]));
+}
+
+#[test]
+fn test_literals() {
+    let instance = SatInstance::builder().or(&["!a", "-b", "!c", "d"]).build();
+    let names: Vec<_> = instance.clauses[0].literals.iter().map(|&l| instance.vars.literal_name(l)).collect();
+    assert_eq!(names, vec!["!a", "!b", "!c", "d"]);
+    let a = instance.vars.lookup("a").unwrap().pos();
+    assert_eq!(!!a, a);
+    assert_ne!(!a, a);
+}
+
+#[test]
+fn test_dimacs() {
+    let instance = dimacs::parse("p cnf 2 2\n1 2 0\n-1 0\n").unwrap();
+    let names: Vec<_> = instance.vars.vars().map(|var| instance.vars.name(var)).collect();
+    assert_eq!(names, vec!["1", "2"]);
+    assert_eq!(model(&instance), named(&[("1", false), ("2", true)]));
+
+    let mut out = Vec::new();
+    dimacs::write(&instance, &mut out).unwrap();
+    assert_eq!(dimacs::parse(&String::from_utf8(out).unwrap()).unwrap().clauses.len(), 2);
+    assert!(dimacs::parse("p cnf 1 1\n1 x 0\n").is_err());
+}
+
+#[test]
+fn test_expression_hides_internal_variables() {
+    let instance = expr::parse_instance("(a | b) & (c & !b)").unwrap();
+    let vars = &instance.vars;
+    let names: Vec<_> = vars.vars().filter(|&var| !vars.is_internal(var)).map(|var| vars.name(var)).collect();
+    assert_eq!(names, vec!["a", "b", "c"]);
+    assert_eq!(model(&instance), named(&[("a", true), ("b", false), ("c", true)]));
+    assert!(expr::parse_instance("a & | b").is_err());
+}
+
+#[test]
+fn test_satisfied_by() {
+    let mut instance = SatInstance::new();
+    let (a, b) = (instance.var("a"), instance.var("b"));
+    instance.add_clause(Clause::new(Operator::IMPLIES, vec![a.pos(), b.pos()]));
+    assert!(instance.satisfied_by(&state(&instance, &[("a", true), ("b", true)])));
+    assert!(!instance.satisfied_by(&state(&instance, &[("a", true), ("b", false)])));
+    assert!(!instance.satisfied_by(&state(&instance, &[("a", true)])));
+    assert_eq!(instance.vars.lookup("z"), None);
+}
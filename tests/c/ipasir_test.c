@@ -0,0 +1,198 @@
+/*
+ * Exercises the IPASIR interface of the solver library, see Makefile.
+ * Exits with status 0 when every check passes.
+ */
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "ipasir.h"
+
+static int failures = 0;
+
+#define CHECK(condition) \
+    do { \
+        if (!(condition)) { \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition); \
+            failures++; \
+        } \
+    } while (0)
+
+static void add_clause(void *solver, const int32_t *literals) {
+    for (; *literals; literals++) {
+        ipasir_add(solver, *literals);
+    }
+    ipasir_add(solver, 0);
+}
+
+/* Variable of pigeon p in hole h, both from 0 */
+static int32_t hole(int holes, int p, int h) {
+    return p * holes + h + 1;
+}
+
+/* Every pigeon in some hole, no two pigeons in the same hole */
+static void add_pigeonhole(void *solver, int pigeons, int holes) {
+    for (int p = 0; p < pigeons; p++) {
+        for (int h = 0; h < holes; h++) {
+            ipasir_add(solver, hole(holes, p, h));
+        }
+        ipasir_add(solver, 0);
+    }
+    for (int h = 0; h < holes; h++) {
+        for (int p = 0; p < pigeons; p++) {
+            for (int q = p + 1; q < pigeons; q++) {
+                int32_t clause[] = {-hole(holes, p, h), -hole(holes, q, h), 0};
+                add_clause(solver, clause);
+            }
+        }
+    }
+}
+
+static void test_signature(void) {
+    const char *signature = ipasir_signature();
+    CHECK(signature != NULL && signature[0] != '\0');
+}
+
+static void test_incremental(void) {
+    void *solver = ipasir_init();
+    int32_t a[] = {1, 2, 0};
+    int32_t b[] = {-1, 2, 0};
+    add_clause(solver, a);
+    add_clause(solver, b);
+    CHECK(ipasir_solve(solver) == 10);
+    CHECK(ipasir_val(solver, 2) == 2);
+    CHECK(ipasir_val(solver, -2) == 2);
+    CHECK(ipasir_val(solver, 7) == 0);
+    CHECK(ipasir_val(solver, 0) == 0);
+
+    /* Assumptions only hold for one solve, 0 is not one */
+    ipasir_assume(solver, 0);
+    ipasir_assume(solver, -2);
+    CHECK(ipasir_solve(solver) == 20);
+    CHECK(ipasir_failed(solver, -2) == 1);
+    CHECK(ipasir_failed(solver, 0) == 0);
+    CHECK(ipasir_solve(solver) == 10);
+
+    /* Clauses added after a solve */
+    int32_t c[] = {-2, 3, 0};
+    add_clause(solver, c);
+    CHECK(ipasir_solve(solver) == 10);
+    CHECK(ipasir_val(solver, 3) == 3);
+    int32_t d[] = {-3, 0};
+    add_clause(solver, d);
+    CHECK(ipasir_solve(solver) == 20);
+    ipasir_release(solver);
+}
+
+static void test_failed(void) {
+    void *solver = ipasir_init();
+    int32_t a[] = {-1, -2, 0};
+    int32_t b[] = {3, 4, 0};
+    add_clause(solver, a);
+    add_clause(solver, b);
+    ipasir_assume(solver, 3);
+    ipasir_assume(solver, 1);
+    ipasir_assume(solver, 2);
+    CHECK(ipasir_solve(solver) == 20);
+    CHECK(ipasir_failed(solver, 3) == 0);
+    CHECK(ipasir_failed(solver, 1) + ipasir_failed(solver, 2) >= 1);
+
+    ipasir_assume(solver, 1);
+    ipasir_assume(solver, -4);
+    CHECK(ipasir_solve(solver) == 10);
+    CHECK(ipasir_val(solver, 1) == 1);
+    CHECK(ipasir_val(solver, 2) == -2);
+    CHECK(ipasir_val(solver, 3) == 3);
+    ipasir_release(solver);
+}
+
+static int calls = 0;
+
+static int terminate_now(void *data) {
+    calls++;
+    return *(int *) data;
+}
+
+static void test_terminate(void) {
+    void *solver = ipasir_init();
+    add_pigeonhole(solver, 7, 6);
+    int stop = 1;
+    ipasir_set_terminate(solver, &stop, terminate_now);
+    CHECK(ipasir_solve(solver) == 0);
+    CHECK(calls > 0);
+
+    ipasir_set_terminate(solver, NULL, NULL);
+    CHECK(ipasir_solve(solver) == 20);
+    ipasir_release(solver);
+}
+
+struct learned {
+    int clauses;
+    int too_long;
+};
+
+static void learn(void *data, int32_t *clause) {
+    struct learned *learned = data;
+    int length = 0;
+    while (clause[length] != 0) {
+        length++;
+    }
+    learned->clauses++;
+    if (length > 3) {
+        learned->too_long++;
+    }
+}
+
+static void test_learn(void) {
+    void *solver = ipasir_init();
+    add_pigeonhole(solver, 6, 5);
+    struct learned learned = {0, 0};
+    ipasir_set_learn(solver, &learned, 3, learn);
+    CHECK(ipasir_solve(solver) == 20);
+    CHECK(learned.clauses > 0);
+    CHECK(learned.too_long == 0);
+    ipasir_release(solver);
+}
+
+/* Random 3-SAT below the threshold, models must satisfy every clause */
+static void test_models(void) {
+    enum { VARS = 100, CLAUSES = 350 };
+    static int32_t clauses[CLAUSES][4];
+    srand(1);
+    for (int round = 0; round < 10; round++) {
+        void *solver = ipasir_init();
+        for (int c = 0; c < CLAUSES; c++) {
+            for (int k = 0; k < 3; k++) {
+                int32_t var = rand() % VARS + 1;
+                clauses[c][k] = rand() % 2 ? var : -var;
+            }
+            clauses[c][3] = 0;
+            add_clause(solver, clauses[c]);
+        }
+        int result = ipasir_solve(solver);
+        CHECK(result == 10 || result == 20);
+        for (int c = 0; result == 10 && c < CLAUSES; c++) {
+            int satisfied = 0;
+            for (int k = 0; k < 3; k++) {
+                satisfied |= ipasir_val(solver, clauses[c][k]) == clauses[c][k];
+            }
+            CHECK(satisfied);
+        }
+        ipasir_release(solver);
+    }
+}
+
+int main(void) {
+    test_signature();
+    test_incremental();
+    test_failed();
+    test_terminate();
+    test_learn();
+    test_models();
+    if (failures) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("all checks passed (%s)\n", ipasir_signature());
+    return 0;
+}